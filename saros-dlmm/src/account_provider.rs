@@ -0,0 +1,241 @@
+use std::{fs::File, path::Path, str::FromStr};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use glob::glob;
+use jupiter_amm_interface::{AccountMap, Amm};
+use solana_account_decoder::{encode_ui_account, UiAccountEncoding};
+use solana_client::{
+    nonblocking::rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::RpcFilterType,
+    rpc_response::RpcKeyedAccount,
+};
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use spl_token_2022::{extension::StateWithExtensions, state::Account as TokenAccount};
+
+// Maximum number of accounts per getMultipleAccounts call
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// File of a snapshot directory recording why each account was captured
+pub const SNAPSHOT_MANIFEST_FILE: &str = "manifest.json";
+
+/// Source of on-chain accounts, backed by an RPC node or by accounts held in memory
+#[async_trait]
+pub trait AccountProvider: Send + Sync {
+    async fn get_account(&self, key: &Pubkey) -> Result<Option<Account>> {
+        Ok(self.get_multiple_accounts(&[*key]).await?.pop().flatten())
+    }
+
+    /// Accounts in the same order as `keys`, `None` for the ones that do not exist
+    async fn get_multiple_accounts(&self, keys: &[Pubkey]) -> Result<Vec<Option<Account>>>;
+
+    /// Accounts owned by `program_id` matching every filter
+    async fn get_program_accounts(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> Result<Vec<(Pubkey, Account)>>;
+}
+
+#[async_trait]
+impl AccountProvider for RpcClient {
+    async fn get_multiple_accounts(&self, keys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        let mut accounts = Vec::with_capacity(keys.len());
+        for chunk in keys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            accounts.extend(RpcClient::get_multiple_accounts(self, chunk).await?);
+        }
+        Ok(accounts)
+    }
+
+    async fn get_program_accounts(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> Result<Vec<(Pubkey, Account)>> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(filters),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        Ok(self
+            .get_program_accounts_with_config(program_id, config)
+            .await?)
+    }
+}
+
+#[async_trait]
+impl AccountProvider for AccountMap {
+    async fn get_multiple_accounts(&self, keys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        Ok(keys.iter().map(|key| self.get(key).cloned()).collect())
+    }
+
+    async fn get_program_accounts(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> Result<Vec<(Pubkey, Account)>> {
+        let mut accounts: Vec<(Pubkey, Account)> = self
+            .iter()
+            .filter(|(_, account)| account.owner == *program_id)
+            .filter(|(_, account)| filters.iter().all(|filter| filter_allows(filter, account)))
+            .map(|(key, account)| (*key, account.clone()))
+            .collect();
+        accounts.sort_by_key(|(key, _)| *key);
+
+        Ok(accounts)
+    }
+}
+
+// Same semantics as the RPC node applies to getProgramAccounts filters
+fn filter_allows(filter: &RpcFilterType, account: &Account) -> bool {
+    match filter {
+        RpcFilterType::DataSize(size) => account.data.len() as u64 == *size,
+        RpcFilterType::Memcmp(memcmp) => memcmp.bytes_match(&account.data),
+        RpcFilterType::TokenAccountState => {
+            StateWithExtensions::<TokenAccount>::unpack(&account.data).is_ok()
+        }
+    }
+}
+
+/// Fetch `keys` into an account map, accounts that do not exist are left out
+pub async fn get_account_map<P>(provider: &P, keys: &[Pubkey]) -> Result<AccountMap>
+where
+    P: AccountProvider + ?Sized,
+{
+    Ok(keys
+        .iter()
+        .zip(provider.get_multiple_accounts(keys).await?)
+        .filter_map(|(key, account)| account.map(|account| (*key, account)))
+        .collect())
+}
+
+/// Refresh `amm` with the accounts it asks for
+pub async fn update_amm<P>(amm: &mut dyn Amm, provider: &P) -> Result<()>
+where
+    P: AccountProvider + ?Sized,
+{
+    let account_map = get_account_map(provider, &amm.get_accounts_to_update()).await?;
+    amm.update(&account_map)
+}
+
+/// Load every `<address>.json` keyed account of a snapshot directory
+pub fn load_fixture_accounts(directory: &Path) -> Result<AccountMap> {
+    let mut account_map = AccountMap::default();
+    let pattern = directory.join("*.json");
+
+    for entry in glob(&pattern.to_string_lossy())?.flatten() {
+        if entry.ends_with("params.json") || entry.ends_with(SNAPSHOT_MANIFEST_FILE) {
+            continue;
+        }
+
+        let file = File::open(&entry)?;
+        let keyed_account: RpcKeyedAccount = serde_json::from_reader(file)
+            .with_context(|| format!("Invalid keyed account {}", entry.display()))?;
+        let account: Account = keyed_account
+            .account
+            .decode()
+            .with_context(|| format!("Failed to decode account {}", entry.display()))?;
+
+        account_map.insert(Pubkey::from_str(&keyed_account.pubkey)?, account);
+    }
+
+    Ok(account_map)
+}
+
+/// Keyed account as stored in a snapshot directory
+pub fn encode_fixture_account(address: &Pubkey, account: &Account) -> RpcKeyedAccount {
+    RpcKeyedAccount {
+        pubkey: address.to_string(),
+        account: encode_ui_account(address, account, UiAccountEncoding::Base64, None, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_client::rpc_filter::Memcmp;
+
+    fn account(owner: Pubkey, data: Vec<u8>) -> Account {
+        Account {
+            lamports: 1,
+            data,
+            owner,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_provider() {
+        let program_id = Pubkey::new_unique();
+        let [a, b, c, missing] = [
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ];
+
+        let mut accounts = AccountMap::default();
+        accounts.insert(a, account(program_id, vec![1, 2, 3, 4]));
+        accounts.insert(b, account(program_id, vec![1, 2, 9]));
+        accounts.insert(c, account(Pubkey::new_unique(), vec![1, 2, 3, 4]));
+
+        assert!(accounts.get_account(&missing).await.unwrap().is_none());
+        assert_eq!(
+            accounts.get_account(&a).await.unwrap().unwrap().data,
+            vec![1, 2, 3, 4]
+        );
+
+        let multiple = AccountProvider::get_multiple_accounts(&accounts, &[b, missing, a])
+            .await
+            .unwrap();
+        assert!(multiple[0].is_some() && multiple[1].is_none() && multiple[2].is_some());
+
+        let owned = accounts
+            .get_program_accounts(&program_id, vec![])
+            .await
+            .unwrap();
+        assert_eq!(owned.len(), 2);
+
+        let filtered = accounts
+            .get_program_accounts(
+                &program_id,
+                vec![
+                    RpcFilterType::DataSize(4),
+                    RpcFilterType::Memcmp(Memcmp::new_raw_bytes(1, vec![2, 3])),
+                ],
+            )
+            .await
+            .unwrap();
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].0, a);
+
+        let map = get_account_map(&accounts, &[a, missing]).await.unwrap();
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn test_fixture_round_trip() {
+        let directory =
+            std::env::temp_dir().join(format!("saros-fixture-{}", Pubkey::new_unique()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let address = Pubkey::new_unique();
+        let original = account(Pubkey::new_unique(), vec![7; 32]);
+        let file = File::create(directory.join(format!("{}.json", address))).unwrap();
+        serde_json::to_writer(file, &encode_fixture_account(&address, &original)).unwrap();
+        std::fs::write(directory.join("params.json"), "{}").unwrap();
+        std::fs::write(directory.join(SNAPSHOT_MANIFEST_FILE), "{}").unwrap();
+
+        let accounts = load_fixture_accounts(&directory).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[&address], original);
+    }
+}
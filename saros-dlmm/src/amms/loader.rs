@@ -1,12 +1,12 @@
 use std::collections::HashSet;
 
 use anyhow::{Result, anyhow};
-use jupiter_amm_interface::{Amm, AmmContext, KeyedAccount};
+use jupiter_amm_interface::{AccountMap, Amm, AmmContext, ClockRef, KeyedAccount};
 use lazy_static::lazy_static;
-use solana_sdk::pubkey::Pubkey;
+use solana_sdk::{pubkey::Pubkey, sysvar::clock};
 use std::collections::HashMap;
 
-use crate::SarosDlmm;
+use crate::{account_provider::AccountProvider, SarosDlmm};
 
 mod spl_token_swap_programs {
 
@@ -43,3 +43,465 @@ pub fn amm_factory(
         ))
     }
 }
+
+impl SarosDlmm {
+    /// Load a ready to quote pool in two fetches: the pair, then its bin arrays, mints, hook and
+    /// the clock in a single `get_multiple_accounts`. The pool reads its time from the clock
+    /// sysvar, switch to a shared clock with `set_shared_clock`.
+    pub async fn load<P>(pair_key: Pubkey, provider: &P) -> Result<Self>
+    where
+        P: AccountProvider + ?Sized,
+    {
+        let pair_account = provider
+            .get_account(&pair_key)
+            .await?
+            .ok_or_else(|| anyhow!("Pair account does not exist: {}", pair_key))?;
+        if pair_account.owner != liquidity_book::ID {
+            return Err(anyhow!(
+                "Pair {} is owned by {}, not by the liquidity book program",
+                pair_key,
+                pair_account.owner
+            ));
+        }
+
+        let mut amm = Self::from_keyed_account(
+            &KeyedAccount {
+                key: pair_key,
+                account: pair_account.clone(),
+                params: None,
+            },
+            &AmmContext {
+                clock_ref: ClockRef::default(),
+            },
+        )?;
+        amm.set_sysvar_clock();
+
+        // The pair comes from the first fetch so the bin arrays always match its active id
+        let [bin_array_lower_key, bin_array_middle_key, bin_array_upper_key] = amm.bin_array_key;
+        let mut keys = vec![
+            bin_array_lower_key,
+            bin_array_middle_key,
+            bin_array_upper_key,
+            amm.pair.token_mint_x,
+            amm.pair.token_mint_y,
+            clock::ID,
+        ];
+        if let Some(hook) = amm.pair.hook {
+            keys.push(hook);
+        }
+
+        let mut account_map = AccountMap::default();
+        account_map.insert(pair_key, pair_account);
+        for (key, account) in keys
+            .iter()
+            .zip(provider.get_multiple_accounts(&keys).await?)
+        {
+            if let Some(account) = account {
+                account_map.insert(*key, account);
+            }
+        }
+
+        if !account_map.contains_key(&bin_array_middle_key) {
+            return Err(anyhow!(
+                "Active bin array {} of pair {} does not exist",
+                bin_array_middle_key,
+                pair_key
+            ));
+        }
+        if !account_map.contains_key(&bin_array_lower_key)
+            && !account_map.contains_key(&bin_array_upper_key)
+        {
+            return Err(anyhow!(
+                "Neither bin array {} nor {} of pair {} exists",
+                bin_array_lower_key,
+                bin_array_upper_key,
+                pair_key
+            ));
+        }
+        if let Some(hook) = amm.pair.hook {
+            if !account_map.contains_key(&hook) {
+                return Err(anyhow!(
+                    "Hook account {} of pair {} does not exist",
+                    hook,
+                    pair_key
+                ));
+            }
+        }
+
+        amm.update(&account_map)?;
+        amm.compute_bin_array_swap()?;
+
+        Ok(amm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use async_trait::async_trait;
+
+    use jupiter_amm_interface::{QuoteParams, SwapMode};
+    use saros_sdk::{
+        state::{bin::BIN_ARRAY_SIZE, pair::Pair},
+        utils::helper::get_swap_pair_bin_array,
+    };
+    use solana_client::rpc_filter::RpcFilterType;
+    use solana_sdk::{
+        account::Account,
+        program_pack::{IsInitialized, Pack},
+    };
+
+    use super::*;
+    use crate::amms::test_pool::{build_test_pool, build_test_pool_accounts};
+    use crate::ClockSource;
+
+    // Counts the round trips made to the underlying accounts
+    struct CountingProvider {
+        accounts: AccountMap,
+        fetches: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl AccountProvider for CountingProvider {
+        async fn get_multiple_accounts(&self, keys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+            self.fetches.fetch_add(1, Ordering::Relaxed);
+            self.accounts.get_multiple_accounts(keys).await
+        }
+
+        async fn get_program_accounts(
+            &self,
+            program_id: &Pubkey,
+            filters: Vec<RpcFilterType>,
+        ) -> Result<Vec<(Pubkey, Account)>> {
+            self.fetches.fetch_add(1, Ordering::Relaxed);
+            self.accounts
+                .get_program_accounts(program_id, filters)
+                .await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_pool() {
+        let mint_x = Pubkey::new_unique();
+        let mint_y = Pubkey::new_unique();
+        let pool = build_test_pool(Pubkey::new_unique(), mint_x, mint_y, 10, 10_000, 1_000_000);
+
+        let provider = CountingProvider {
+            accounts: build_test_pool_accounts(&pool, 1_700_000_000),
+            fetches: AtomicUsize::new(0),
+        };
+        let loaded = SarosDlmm::load(pool.key, &provider).await.unwrap();
+
+        assert_eq!(provider.fetches.load(Ordering::Relaxed), 2);
+        assert_eq!(loaded.bin_array_key, pool.bin_array_key);
+        assert_eq!(loaded.token_program, pool.token_program);
+        assert_eq!(
+            loaded.timestamp.load(Ordering::Relaxed),
+            1_700_000_000
+        );
+
+        let quote_params = QuoteParams {
+            amount: 1_000_000,
+            input_mint: mint_x,
+            output_mint: mint_y,
+            swap_mode: SwapMode::ExactIn,
+        };
+        assert_eq!(
+            loaded.quote(&quote_params).unwrap().out_amount,
+            pool.quote(&quote_params).unwrap().out_amount
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_missing_accounts() {
+        let pool = build_test_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            10,
+            10_000,
+            1_000_000,
+        );
+        let accounts = build_test_pool_accounts(&pool, 0);
+        let [lower, middle, upper] = pool.bin_array_key;
+
+        assert!(SarosDlmm::load(Pubkey::new_unique(), &accounts)
+            .await
+            .is_err());
+
+        let mut without_middle = accounts.clone();
+        without_middle.remove(&middle);
+        let error = SarosDlmm::load(pool.key, &without_middle)
+            .await
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains(&middle.to_string()));
+
+        let mut without_sides = accounts.clone();
+        without_sides.remove(&lower);
+        without_sides.remove(&upper);
+        assert!(SarosDlmm::load(pool.key, &without_sides).await.is_err());
+
+        let mut without_upper = accounts.clone();
+        without_upper.remove(&upper);
+        assert!(SarosDlmm::load(pool.key, &without_upper).await.is_ok());
+
+        let mut without_mint = accounts;
+        without_mint.remove(&pool.pair.token_mint_y);
+        assert!(SarosDlmm::load(pool.key, &without_mint).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_update_with_slot() {
+        let pool = build_test_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            10,
+            10_000,
+            1_000_000,
+        );
+        let accounts = build_test_pool_accounts(&pool, 0);
+        let mut loaded = SarosDlmm::load(pool.key, &accounts).await.unwrap();
+
+        loaded.update_with_slot(&accounts, 50).unwrap();
+        assert_eq!(loaded.slots.pair, 50);
+        assert_eq!(loaded.slots.bin_arrays, [50; 3]);
+
+        assert!(loaded.update_with_slot(&accounts, 49).is_err());
+        assert_eq!(loaded.slots.pair, 50);
+
+        // Only the pair is refreshed, the bin arrays fall behind
+        let mut pair_only = AccountMap::default();
+        for key in [
+            pool.key,
+            pool.pair.token_mint_x,
+            pool.pair.token_mint_y,
+            clock::ID,
+        ] {
+            pair_only.insert(key, accounts[&key].clone());
+        }
+        loaded
+            .update_with_slot(&pair_only, 50 + loaded.max_slot_spread + 1)
+            .unwrap();
+        assert!(loaded
+            .quote(&QuoteParams {
+                amount: 1_000,
+                input_mint: pool.pair.token_mint_x,
+                output_mint: pool.pair.token_mint_y,
+                swap_mode: SwapMode::ExactIn,
+            })
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_update_with_slot_keeps_newer_bin_array() {
+        let pool = build_test_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            10,
+            10_000,
+            1_000_000,
+        );
+        let accounts = build_test_pool_accounts(&pool, 0);
+        let mut loaded = SarosDlmm::load(pool.key, &accounts).await.unwrap();
+        loaded.update_with_slot(&accounts, 50).unwrap();
+
+        // A bin array write lands before the bulk update fetched at an older slot
+        let mut bin_array = loaded.bin_array_middle;
+        bin_array.bins[255].reserve_y = 1;
+        loaded
+            .apply_bin_array(&pool.bin_array_key[1], bin_array, 60)
+            .unwrap();
+
+        loaded.update_with_slot(&accounts, 55).unwrap();
+        assert_eq!(loaded.slots.pair, 55);
+        assert_eq!(loaded.slots.bin_arrays, [55, 60, 55]);
+        assert_eq!(loaded.bin_array_middle.bins[255].reserve_y, 1);
+    }
+
+    #[tokio::test]
+    async fn test_update_with_slot_window_move_keeps_slots() {
+        let pool = build_test_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            10,
+            10_000,
+            1_000_000,
+        );
+        let accounts = build_test_pool_accounts(&pool, 0);
+        let mut loaded = SarosDlmm::load(pool.key, &accounts).await.unwrap();
+        loaded.update_with_slot(&accounts, 50).unwrap();
+
+        // The pair moves up one bin array, only the bin array entering the window is fetched
+        let mut pair = pool.pair.clone();
+        pair.active_id += BIN_ARRAY_SIZE;
+        let mut pair_data = vec![0u8; Pair::LEN];
+        pair.pack_into_slice(&mut pair_data);
+
+        let mut moved = AccountMap::default();
+        for key in [pool.pair.token_mint_x, pool.pair.token_mint_y, clock::ID] {
+            moved.insert(key, accounts[&key].clone());
+        }
+        let mut pair_account = accounts[&pool.key].clone();
+        pair_account.data = pair_data;
+        moved.insert(pool.key, pair_account);
+
+        let (_, _, upper_key) =
+            get_swap_pair_bin_array(pair.bin_array_index(), &pool.key, &pool.program_id);
+        moved.insert(upper_key, accounts[&pool.bin_array_key[2]].clone());
+
+        loaded.update_with_slot(&moved, 60).unwrap();
+        assert_eq!(
+            loaded.bin_array_key,
+            [pool.bin_array_key[1], pool.bin_array_key[2], upper_key]
+        );
+        assert_eq!(loaded.slots.bin_arrays, [50, 50, 60]);
+        assert!(loaded.bin_array_lower.is_initialized());
+        assert!(loaded.bin_array_upper.is_initialized());
+        loaded
+            .check_slot_spread(&loaded.compute_bin_array_swap().unwrap())
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_update_after_update_with_slot() {
+        let pool = build_test_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            10,
+            10_000,
+            1_000_000,
+        );
+        let accounts = build_test_pool_accounts(&pool, 0);
+        let mut loaded = SarosDlmm::load(pool.key, &accounts).await.unwrap();
+        loaded.update_with_slot(&accounts, 100).unwrap();
+
+        // The host refreshes the bin arrays without slots, the slot they were seen at is lost
+        let mut without_upper = accounts.clone();
+        without_upper.remove(&pool.bin_array_key[2]);
+        loaded.update(&without_upper).unwrap();
+        assert_eq!(loaded.slots.pair, 0);
+        assert_eq!(loaded.slots.bin_arrays, [0, 0, 100]);
+
+        // Older slotted writes are no longer rejected as stale against the replaced accounts
+        let mut bin_array = loaded.bin_array_middle;
+        bin_array.bins[255].reserve_y = 1;
+        assert!(loaded
+            .apply_bin_array(&pool.bin_array_key[1], bin_array, 85)
+            .unwrap());
+        loaded
+            .check_slot_spread(&loaded.compute_bin_array_swap().unwrap())
+            .unwrap();
+
+        loaded.update_with_slot(&accounts, 90).unwrap();
+        assert_eq!(loaded.slots.pair, 90);
+        assert_eq!(loaded.slots.bin_arrays, [90, 90, 100]);
+    }
+
+    #[test]
+    fn test_sysvar_clock_leaves_shared_clock() {
+        let pool = build_test_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            10,
+            10_000,
+            1_000_000,
+        );
+        let accounts = build_test_pool_accounts(&pool, 1_700_000_000);
+
+        let clock_ref = ClockRef::default();
+        clock_ref.unix_timestamp.store(1_000, Ordering::Relaxed);
+        let keyed_account = KeyedAccount {
+            key: pool.key,
+            account: accounts[&pool.key].clone(),
+            params: None,
+        };
+        let mut amm = SarosDlmm::from_keyed_account(
+            &keyed_account,
+            &AmmContext {
+                clock_ref: clock_ref.clone(),
+            },
+        )
+        .unwrap();
+
+        amm.set_sysvar_clock();
+        assert_eq!(amm.clock_source(), ClockSource::Sysvar);
+        let clone = amm.clone();
+        amm.update(&accounts).unwrap();
+
+        assert_eq!(clock_ref.unix_timestamp.load(Ordering::Relaxed), 1_000);
+        for pool in [&amm, &clone] {
+            assert_eq!(pool.timestamp.load(Ordering::Relaxed), 1_700_000_000);
+        }
+
+        amm.set_shared_clock(&clock_ref);
+        assert_eq!(amm.clock_source(), ClockSource::Shared);
+        assert!(std::sync::Arc::ptr_eq(
+            &amm.timestamp,
+            &clock_ref.unix_timestamp
+        ));
+    }
+
+    #[test]
+    fn test_shared_clock_behind_pair() {
+        let mint_x = Pubkey::new_unique();
+        let mint_y = Pubkey::new_unique();
+        let mut pool = build_test_pool(Pubkey::new_unique(), mint_x, mint_y, 10, 10_000, 1_000_000);
+        pool.pair.static_fee_parameters.filter_period = 30;
+        pool.pair.static_fee_parameters.decay_period = 600;
+        pool.pair.static_fee_parameters.variable_fee_control = 100_000;
+        pool.pair.static_fee_parameters.max_volatility_accumulator = 350_000;
+        pool.pair.dynamic_fee_parameters.time_last_updated = 1_000;
+        pool.pair.dynamic_fee_parameters.volatility_reference = 20_000;
+        let accounts = build_test_pool_accounts(&pool, 0);
+
+        let clock_ref = ClockRef::default();
+        let keyed_account = KeyedAccount {
+            key: pool.key,
+            account: accounts[&pool.key].clone(),
+            params: None,
+        };
+        let mut amm = SarosDlmm::from_keyed_account(
+            &keyed_account,
+            &AmmContext {
+                clock_ref: clock_ref.clone(),
+            },
+        )
+        .unwrap();
+        amm.update(&accounts).unwrap();
+        amm.update(&accounts).unwrap();
+
+        assert!(!amm.get_accounts_to_update().contains(&clock::ID));
+        assert!(std::sync::Arc::ptr_eq(
+            &amm.timestamp,
+            &clock_ref.unix_timestamp
+        ));
+
+        let quote_at = |timestamp| {
+            clock_ref
+                .unix_timestamp
+                .store(timestamp, Ordering::Relaxed);
+            amm.quote(&QuoteParams {
+                amount: 3_000_000,
+                input_mint: mint_x,
+                output_mint: mint_y,
+                swap_mode: SwapMode::ExactIn,
+            })
+            .unwrap()
+        };
+
+        let at_last_update = quote_at(1_000);
+        let lagging = quote_at(400);
+        assert_eq!(lagging.out_amount, at_last_update.out_amount);
+        assert_eq!(lagging.fee_amount, at_last_update.fee_amount);
+
+        // Once the volatility decays the fee drops
+        assert!(quote_at(2_000).fee_amount < at_last_update.fee_amount);
+    }
+}
@@ -2,3 +2,5 @@ pub mod amm;
 pub mod loader;
 pub mod test_harness;
 pub mod position_manager;
+#[cfg(test)]
+pub(crate) mod test_pool;
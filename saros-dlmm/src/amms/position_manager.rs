@@ -1,8 +1,18 @@
 use anchor_lang::prelude::AccountMeta;
 use anyhow::Result;
 use jupiter_amm_interface::Amm;
-use saros_sdk::instruction::{CreatePositionParams, ModifierPositionParams};
-use solana_sdk::pubkey::Pubkey;
+use saros_sdk::{
+    instruction::{
+        build_close_position_instruction_data, build_create_position_instruction_data,
+        build_decrease_position_instruction_data, build_increase_position_instruction_data,
+        CreatePositionParams, DecreasePositionParams, IncreasePositionParams,
+        ModifierPositionParams,
+    },
+    state::bin::BIN_ARRAY_SIZE,
+    utils::helper::{find_hook_bin_array_at_position, find_position, get_pair_bin_array},
+};
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
 
 pub trait SarosPositionManagement: Amm {
     fn has_hook(&self) -> bool;
@@ -16,4 +26,171 @@ pub trait SarosPositionManagement: Amm {
         &self,
         modifier_position_params: ModifierPositionParams,
     ) -> Result<Vec<AccountMeta>>;
+
+    fn get_create_position_instruction(
+        &self,
+        create_position_params: CreatePositionParams,
+    ) -> Result<Instruction> {
+        Ok(Instruction {
+            program_id: self.program_id(),
+            accounts: self.get_create_position_account_metas(create_position_params.clone())?,
+            data: build_create_position_instruction_data(create_position_params)?,
+        })
+    }
+
+    fn get_increase_position_instruction(
+        &self,
+        modifier_position_params: ModifierPositionParams,
+        increase_position_params: IncreasePositionParams,
+    ) -> Result<Instruction> {
+        Ok(Instruction {
+            program_id: self.program_id(),
+            accounts: self.get_modifier_position_account_metas(modifier_position_params)?,
+            data: build_increase_position_instruction_data(increase_position_params)?,
+        })
+    }
+
+    fn get_decrease_position_instruction(
+        &self,
+        modifier_position_params: ModifierPositionParams,
+        decrease_position_params: DecreasePositionParams,
+    ) -> Result<Instruction> {
+        Ok(Instruction {
+            program_id: self.program_id(),
+            accounts: self.get_modifier_position_account_metas(modifier_position_params)?,
+            data: build_decrease_position_instruction_data(decrease_position_params)?,
+        })
+    }
+
+    fn get_close_position_instruction(
+        &self,
+        modifier_position_params: ModifierPositionParams,
+    ) -> Result<Instruction> {
+        Ok(Instruction {
+            program_id: self.program_id(),
+            accounts: self.get_modifier_position_account_metas(modifier_position_params)?,
+            data: build_close_position_instruction_data()?,
+        })
+    }
+}
+
+/// Resolve the accounts needed to modify the position minted by `position_mint`, whose lowest bin
+/// is `lower_bin_id`
+pub fn get_modifier_position_params<T>(
+    amm: &T,
+    user: Pubkey,
+    position_mint: Pubkey,
+    lower_bin_id: u32,
+    user_vault_x: Pubkey,
+    user_vault_y: Pubkey,
+) -> ModifierPositionParams
+where
+    T: SarosPositionManagement + ?Sized,
+{
+    let position_bin_array_index = lower_bin_id / BIN_ARRAY_SIZE;
+
+    let (bin_array_position_lower, bin_array_position_upper) =
+        get_pair_bin_array(position_bin_array_index, &amm.key(), &amm.program_id());
+
+    let [position_hook_bin_array_lower, position_hook_bin_array_upper] = match amm.get_hook() {
+        Some(hook) => find_hook_bin_array_at_position(position_bin_array_index, hook).1,
+        None => [Pubkey::default(), Pubkey::default()],
+    };
+
+    ModifierPositionParams {
+        user,
+        position_key: find_position(position_mint),
+        position_token_account: get_associated_token_address_with_program_id(
+            &user,
+            &position_mint,
+            &spl_token_2022::ID,
+        ),
+        position_mint,
+        user_vault_x,
+        user_vault_y,
+        bin_array_position_lower,
+        bin_array_position_upper,
+        position_hook_bin_array_lower,
+        position_hook_bin_array_upper,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::amms::test_pool::build_test_pool;
+
+    // Signer flag of every account of `instruction` in the liquidity book IDL, in order
+    fn get_idl_signers(instruction: &str) -> Vec<bool> {
+        let idl: Value =
+            serde_json::from_str(include_str!("../../../idls/liquidity_book.json")).unwrap();
+        idl["instructions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|ix| ix["name"] == instruction)
+            .unwrap()["accounts"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|account| account["signer"].as_bool().unwrap_or(false))
+            .collect()
+    }
+
+    #[test]
+    fn test_position_account_metas_signers() {
+        let pool = build_test_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            10,
+            10_000,
+            1_000_000,
+        );
+        let user = Pubkey::new_unique();
+        let position_mint = Pubkey::new_unique();
+
+        let create_metas = pool
+            .get_create_position_account_metas(CreatePositionParams {
+                relative_bin_id_left: -5,
+                relative_bin_id_right: 5,
+                user,
+                source_position: Pubkey::new_unique(),
+                position_mint,
+            })
+            .unwrap();
+        let signers: Vec<bool> = create_metas.iter().map(|meta| meta.is_signer).collect();
+        assert_eq!(signers, get_idl_signers("create_position"));
+
+        // Only the owner signs once the position exists, the mint keypair is not kept around
+        let modifier_metas = pool
+            .get_modifier_position_account_metas(get_modifier_position_params(
+                &pool,
+                user,
+                position_mint,
+                pool.pair.active_id - 5,
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+            ))
+            .unwrap();
+        for instruction in ["increase_position", "decrease_position", "close_position"] {
+            let idl_signers = get_idl_signers(instruction);
+            let signers: Vec<bool> = modifier_metas
+                .iter()
+                .take(idl_signers.len())
+                .map(|meta| meta.is_signer)
+                .collect();
+            assert_eq!(signers, idl_signers);
+        }
+        assert_eq!(
+            modifier_metas
+                .iter()
+                .filter(|meta| meta.is_signer)
+                .map(|meta| meta.pubkey)
+                .collect::<Vec<_>>(),
+            vec![user]
+        );
+    }
 }
@@ -1,11 +1,9 @@
-use anchor_lang::prelude::AccountMeta;
-use anyhow::{Context, Result};
+use anchor_lang::{prelude::AccountMeta, AccountDeserialize};
+use anyhow::{anyhow, Context, Result};
 use assert_matches::assert_matches;
 use async_trait::async_trait;
-use glob::glob;
 use jupiter_amm_interface::{
-    AccountMap, AmmContext, ClockRef, KeyedUiAccount, QuoteParams, SwapAndAccountMetas, SwapMode,
-    SwapParams,
+    AccountMap, AmmContext, ClockRef, QuoteParams, SwapAndAccountMetas, SwapMode, SwapParams,
 };
 use lazy_static::lazy_static;
 
@@ -15,7 +13,11 @@ use saros_sdk::{
         get_initialize_hook_position_instruction, BuildSwapInstructionDataParams,
     },
     math::swap_manager::SwapType,
-    utils::helper::{find_hook_bin_array_at_position, find_hook_position, is_swap_for_y},
+    state::{bin::BIN_ARRAY_SIZE, pair::Pair},
+    utils::helper::{
+        find_bin_step_config, find_hook_bin_array_at_position, find_hook_position,
+        get_hook_bin_array, get_pair_bin_array, is_swap_for_y,
+    },
 };
 use serde_json::{json, Value};
 use solana_account_decoder::{encode_ui_account, UiAccount, UiAccountEncoding};
@@ -31,8 +33,8 @@ use saros_sdk::{
     utils::helper::{find_bin_array_at_position, find_position},
 };
 use solana_client::{
-    nonblocking,
-    rpc_client::{RpcClient, RpcClientConfig},
+    nonblocking::rpc_client::RpcClient,
+    rpc_client::RpcClientConfig,
     rpc_request::RpcRequest,
     rpc_response::{Response, RpcKeyedAccount, RpcResponseContext},
     rpc_sender::{RpcSender, RpcTransportStats},
@@ -48,17 +50,24 @@ use spl_token_2022::extension::StateWithExtensions;
 // use stakedex_sdk::test_utils::spl_stake_pool;
 use super::amm::{Amm, KeyedAccount};
 use crate::{
-    amms::loader::amm_factory, amms::position_manager::SarosPositionManagement,
+    account_provider::{
+        encode_fixture_account, load_fixture_accounts, update_amm, AccountProvider,
+        SNAPSHOT_MANIFEST_FILE,
+    },
+    amms::position_manager::SarosPositionManagement,
+    inspect::get_state_account,
     route::get_token_mints_permutations,
+    SarosDlmm,
 };
 use ahash::RandomState;
+use rewarder_hook::rewarder_hook::accounts::Hook;
 use solana_sdk::pubkey;
 use std::fs::{create_dir_all, remove_dir_all};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::HashMap,
     fs::File,
     io::Write,
-    path::Path,
+    path::{Path, PathBuf},
 };
 use std::{hint::black_box, str::FromStr, time::Instant};
 pub const SAROS_MINT: Pubkey = pubkey!("SarosY6Vscao718M4A778z4CGtvcwcGef5M9MEH1LGL");
@@ -121,8 +130,19 @@ pub struct AmmTestSwapParams<'a> {
     pub expected_error: Option<&'a anyhow::Error>,
 }
 
+/// Accounts captured by a snapshot on top of the ones a swap reads
+#[derive(Clone, Debug, Default)]
+pub struct SnapshotScope {
+    /// Bin arrays (and hook bin arrays) captured on each side of the active bin array
+    pub bin_array_radius: Option<u32>,
+    /// Capture the config, bin step config and hook accounts of the pair
+    pub pair_accounts: bool,
+    /// Positions captured with their bin arrays, hook position and hook bin arrays
+    pub positions: Vec<Pubkey>,
+}
+
 pub struct AmmTestHarness {
-    pub client: RpcClient,
+    pub provider: Box<dyn AccountProvider + Send + Sync>,
     pub key: Pubkey,
     pub option: Option<String>,
 }
@@ -214,11 +234,11 @@ impl AmmTestHarnessProgramTest {
     }
 
     /// Limited as we clone banks_client to avoid convoluting the general case
-    pub fn get_test_rpc_client(&self) -> nonblocking::rpc_client::RpcClient {
+    pub fn get_test_rpc_client(&self) -> RpcClient {
         let test_rpc_sender = TestRpcSender {
             banks_client: self.context.banks_client.clone(),
         };
-        nonblocking::rpc_client::RpcClient::new_sender(test_rpc_sender, RpcClientConfig::default())
+        RpcClient::new_sender(test_rpc_sender, RpcClientConfig::default())
     }
 
     pub fn get_user(&self) -> Pubkey {
@@ -657,7 +677,7 @@ impl AmmTestHarnessProgramTest {
         ixs_increase.push(increase_position_ix);
 
         let _increase_result = self
-            .process_transaction(&ixs_increase, &[&user_keypair, &position_mint])
+            .process_transaction(&ixs_increase, &[&user_keypair])
             .await
             .unwrap();
 
@@ -697,7 +717,7 @@ impl AmmTestHarnessProgramTest {
         ixs_decrease.push(decrease_position_ix);
 
         let _decrease_result = self
-            .process_transaction(&ixs_decrease, &[&user_keypair, &position_mint])
+            .process_transaction(&ixs_decrease, &[&user_keypair])
             .await
             .unwrap();
 
@@ -713,7 +733,7 @@ impl AmmTestHarnessProgramTest {
         };
         ixs_close.push(close_position_ix);
         let _close_result = self
-            .process_transaction(&ixs_close, &[&user_keypair, &position_mint])
+            .process_transaction(&ixs_close, &[&user_keypair])
             .await
             .unwrap();
     }
@@ -806,13 +826,25 @@ fn update_amm_precise(amm: &mut dyn Amm, account_map: &AccountMap) -> Result<()>
 
 impl AmmTestHarness {
     pub fn new_with_rpc_url(rpc_url: String, key: Pubkey, option: Option<String>) -> Self {
+        Self::new_with_provider(Box::new(RpcClient::new(rpc_url)), key, option)
+    }
+
+    pub fn new_with_provider(
+        provider: Box<dyn AccountProvider + Send + Sync>,
+        key: Pubkey,
+        option: Option<String>,
+    ) -> Self {
         Self {
-            client: RpcClient::new(rpc_url),
+            provider,
             key,
             option,
         }
     }
 
+    fn snapshot_directory(&self) -> PathBuf {
+        Path::new("tests/fixtures/accounts").join(self.directory_name())
+    }
+
     pub fn directory_name(&self) -> String {
         let option = match &self.option {
             Some(option) => format!("-{}", option),
@@ -822,8 +854,12 @@ impl AmmTestHarness {
         format!("{}{option}", self.key)
     }
 
-    pub fn get_keyed_account(&self, key: Pubkey) -> Result<KeyedAccount> {
-        let account = self.client.get_account(&key)?;
+    pub async fn get_keyed_account(&self, key: Pubkey) -> Result<KeyedAccount> {
+        let account = self
+            .provider
+            .get_account(&key)
+            .await?
+            .with_context(|| format!("Account not found: {}", key))?;
         Ok(KeyedAccount {
             key,
             account,
@@ -870,43 +906,12 @@ impl AmmTestHarness {
         UiAccount::decode(&keyed_account.account).unwrap()
     }
 
-    pub fn update_amm(&self, amm: &mut dyn Amm) {
-        let accounts_to_update = amm.get_accounts_to_update();
-
-        let account_map: HashMap<Pubkey, Account, RandomState> = self
-            .client
-            .get_multiple_accounts(&accounts_to_update)
-            .unwrap()
-            .into_iter()
-            .zip(accounts_to_update)
-            .fold(HashMap::default(), |mut m, (account, address)| {
-                if let Some(account) = account {
-                    m.insert(address, account);
-                }
-                m
-            });
-
-        amm.update(&account_map).unwrap();
+    pub async fn update_amm(&self, amm: &mut dyn Amm) {
+        update_amm(amm, self.provider.as_ref()).await.unwrap();
     }
 
     fn load_accounts_snapshot(&self) -> HashMap<Pubkey, Account, RandomState> {
-        let mut account_map: HashMap<Pubkey, Account, RandomState> = HashMap::default();
-        for entry in glob(&format!(
-            "tests/fixtures/accounts/{0}/*.json",
-            self.directory_name()
-        ))
-        .unwrap()
-        .flatten()
-        {
-            if entry.ends_with("params.json") {
-                continue;
-            }
-            let file = File::open(entry).unwrap();
-            let keyed_account: RpcKeyedAccount = serde_json::from_reader(file).unwrap();
-            let account: Account = UiAccount::decode(&keyed_account.account).unwrap();
-            account_map.insert(Pubkey::from_str(&keyed_account.pubkey).unwrap(), account);
-        }
-        account_map
+        load_fixture_accounts(&self.snapshot_directory()).unwrap()
     }
 
     pub fn update_amm_from_snapshot(&self, amm: &mut dyn Amm) -> Result<()> {
@@ -1038,54 +1043,62 @@ impl AmmTestHarness {
     /// Setup AMM
 
     /// Snapshot necessary accounts to perform a swap so that we can reload it later on for reproducible tests
-    /// Saved as <amm-id><option>/<address>.json, with the amm id to avoid collision between AMMs
-    pub fn snapshot_amm_accounts(
+    /// Saved as <snapshot_root>/<amm-id><option>/<address>.json, with the amm id to avoid collision
+    /// between AMMs, along with a manifest.json recording why each account was captured
+    pub async fn snapshot_amm_accounts(
         &self,
-        amm: &dyn Amm,
+        amm: &SarosDlmm,
         params: Option<Value>,
+        scope: &SnapshotScope,
+        snapshot_root: &Path,
         force: bool,
     ) -> Result<()> {
         let placeholder = Pubkey::new_unique();
-        let mut addresses_for_snapshot = HashSet::new();
+        let mut addresses_for_snapshot: HashMap<Pubkey, String> = HashMap::new();
+        let mut add_address = |address: Pubkey, reason: &str| {
+            addresses_for_snapshot
+                .entry(address)
+                .or_insert_with(|| reason.to_string());
+        };
+        // Captured whatever the clock source, a pool on the shared clock does not list it in
+        // `get_accounts_to_update` but the tests build its `ClockRef` from the snapshot
+        add_address(sysvar::clock::ID, "clock sysvar");
         for (source_mint, destination_mint) in get_token_mints_permutations(amm) {
-            let swap_leg_and_account_metas: jupiter_amm_interface::SwapAndAccountMetas = amm
-                .get_swap_and_account_metas(&SwapParams {
-                    source_mint,
-                    destination_mint,
-                    source_token_account: placeholder,
-                    destination_token_account: placeholder,
-                    token_transfer_authority: placeholder,
-                    quote_mint_to_referrer: None,
-                    in_amount: *TOKEN_MINT_TO_IN_AMOUNT
-                        .get(&source_mint)
-                        .unwrap_or_else(|| panic!("No in amount for mint: {}", source_mint)),
-                    out_amount: *TOKEN_MINT_TO_IN_AMOUNT
-                        .get(&source_mint)
-                        .unwrap_or_else(|| panic!("No in amount for mint: {}", destination_mint)),
-                    jupiter_program_id: &placeholder,
-                    missing_dynamic_accounts_as_default: false,
-                    swap_mode: SwapMode::ExactIn,
-                })?;
-
-            addresses_for_snapshot.extend(
-                swap_leg_and_account_metas
-                    .account_metas
-                    .iter()
-                    .map(|account_meta| account_meta.pubkey),
-            );
+            let account_metas = amm.get_swap_account_metas(&SwapParams {
+                source_mint,
+                destination_mint,
+                source_token_account: placeholder,
+                destination_token_account: placeholder,
+                token_transfer_authority: placeholder,
+                quote_mint_to_referrer: None,
+                in_amount: *TOKEN_MINT_TO_IN_AMOUNT
+                    .get(&source_mint)
+                    .unwrap_or_else(|| panic!("No in amount for mint: {}", source_mint)),
+                out_amount: *TOKEN_MINT_TO_IN_AMOUNT
+                    .get(&source_mint)
+                    .unwrap_or_else(|| panic!("No in amount for mint: {}", destination_mint)),
+                jupiter_program_id: &placeholder,
+                missing_dynamic_accounts_as_default: false,
+                swap_mode: SwapMode::ExactIn,
+            })?;
+
+            for account_meta in account_metas.iter() {
+                add_address(account_meta.pubkey, "swap account");
+            }
         }
 
-        addresses_for_snapshot.extend(amm.get_accounts_to_update());
-        addresses_for_snapshot.extend(amm.get_reserve_mints());
+        for address in amm.get_accounts_to_update() {
+            add_address(address, "amm update account");
+        }
+        for address in amm.get_reserve_mints() {
+            add_address(address, "reserve mint");
+        }
+        for (address, reason) in self.get_scope_addresses(amm.key(), scope).await? {
+            add_address(address, &reason);
+        }
         addresses_for_snapshot.remove(&placeholder);
-        // Some AMMs read the clock sysvar
-        addresses_for_snapshot.insert(sysvar::clock::ID);
 
-        let snapshot_path_string = format!(
-            "saros-dlmm/tests/fixtures/accounts/{}",
-            self.directory_name()
-        );
-        let snapshot_path = Path::new(&snapshot_path_string);
+        let snapshot_path = &snapshot_root.join(self.directory_name());
         if force && snapshot_path.exists() && snapshot_path.is_dir() {
             // Remove the directory if it exists
             remove_dir_all(snapshot_path)?;
@@ -1098,28 +1111,23 @@ impl AmmTestHarness {
                 .unwrap();
         }
 
-        let addresses = addresses_for_snapshot.into_iter().collect::<Vec<_>>();
-        self.client
+        let addresses = addresses_for_snapshot.keys().copied().collect::<Vec<_>>();
+        let mut captured = serde_json::Map::new();
+        let mut missing = serde_json::Map::new();
+        self.provider
             .get_multiple_accounts(&addresses)
-            .unwrap()
+            .await?
             .iter()
             .zip(addresses)
             .for_each(|(account, address)| {
+                let reason = addresses_for_snapshot[&address].clone();
                 if let Some(account) = account {
                     if account.executable {
                         // Avoid snapshotting programs as it breaks program test
                         return;
                     }
-                    let keyed_account = RpcKeyedAccount {
-                        pubkey: address.to_string(),
-                        account: encode_ui_account(
-                            &address,
-                            account,
-                            UiAccountEncoding::Base64,
-                            None,
-                            None,
-                        ),
-                    };
+                    captured.insert(address.to_string(), reason.into());
+                    let keyed_account = encode_fixture_account(&address, account);
                     let mut f =
                         File::create(snapshot_path.join(format!("{}.json", address))).unwrap();
                     f.write_all(
@@ -1129,11 +1137,108 @@ impl AmmTestHarness {
                             .as_bytes(),
                     )
                     .unwrap();
+                } else {
+                    missing.insert(address.to_string(), reason.into());
                 }
             });
 
+        if missing.contains_key(&sysvar::clock::ID.to_string()) {
+            return Err(anyhow!(
+                "Clock sysvar {} is missing, the snapshot cannot build a ClockRef",
+                sysvar::clock::ID
+            ));
+        }
+
+        let mut f = File::create(snapshot_path.join(SNAPSHOT_MANIFEST_FILE))?;
+        f.write_all(
+            serde_json::to_string_pretty(&json!({ "accounts": captured, "missing": missing }))?
+                .as_bytes(),
+        )?;
+
         Ok(())
     }
+
+    /// Accounts of `pair_key` requested by `scope`, with the reason they are captured
+    async fn get_scope_addresses(
+        &self,
+        pair_key: Pubkey,
+        scope: &SnapshotScope,
+    ) -> Result<Vec<(Pubkey, String)>> {
+        let provider = self.provider.as_ref();
+        let pair: Pair = get_state_account(provider, &pair_key).await?;
+        let mut addresses = Vec::new();
+
+        if let Some(radius) = scope.bin_array_radius {
+            let active_index = pair.active_id / BIN_ARRAY_SIZE;
+            for index in active_index.saturating_sub(radius)..=active_index.saturating_add(radius) {
+                addresses.push((
+                    get_pair_bin_array(index, &pair_key, &liquidity_book::ID).0,
+                    format!("bin array {}", index),
+                ));
+                if let Some(hook) = pair.hook {
+                    addresses.push((
+                        get_hook_bin_array(index, hook).0,
+                        format!("hook bin array {}", index),
+                    ));
+                }
+            }
+        }
+
+        if scope.pair_accounts {
+            addresses.push((pair.liquidity_book_config, "liquidity book config".into()));
+            addresses.push((
+                find_bin_step_config(pair.liquidity_book_config, pair.bin_step),
+                "bin step config".into(),
+            ));
+            if let Some(hook) = pair.hook {
+                addresses.push((hook, "hook".into()));
+                if let Some(account) = provider.get_account(&hook).await? {
+                    let hook_state = Hook::try_deserialize(&mut account.data.as_slice())?;
+                    addresses.push((
+                        hook_state.reward_token_mint,
+                        "hook reward token mint".into(),
+                    ));
+                    addresses.push((hook_state.hook_reserve, "hook reserve".into()));
+                }
+            }
+        }
+
+        for position_key in scope.positions.iter() {
+            let position: Position = get_state_account(provider, position_key).await?;
+            if position.pair != pair_key {
+                return Err(anyhow!(
+                    "Position {} belongs to pair {}",
+                    position_key,
+                    position.pair
+                ));
+            }
+
+            addresses.push((*position_key, "position".into()));
+            addresses.push((
+                position.position_mint,
+                format!("position mint of {}", position_key),
+            ));
+
+            let (index, bin_arrays) = find_bin_array_at_position(position.clone());
+            for bin_array in bin_arrays {
+                addresses.push((bin_array, format!("bin array of position {}", position_key)));
+            }
+            if let Some(hook) = pair.hook {
+                addresses.push((
+                    find_hook_position(*position_key, hook),
+                    format!("hook position of {}", position_key),
+                ));
+                for hook_bin_array in find_hook_bin_array_at_position(index, hook).1 {
+                    addresses.push((
+                        hook_bin_array,
+                        format!("hook bin array of position {}", position_key),
+                    ));
+                }
+            }
+        }
+
+        Ok(addresses)
+    }
 }
 
 async fn setup_token_accounts(
@@ -1275,52 +1380,51 @@ fn create_ata_account(
     )
 }
 
+/// Snapshot root of the CLI, relative to the workspace
+pub const SNAPSHOT_ROOT: &str = "saros-dlmm/tests/fixtures/accounts";
+
 pub async fn take_snapshot(
     rpc_url: String,
     amm_id: String,
     option: Option<String>,
+    scope: &SnapshotScope,
     force: bool,
 ) -> Result<()> {
     let amm_key = Pubkey::from_str(&amm_id).unwrap();
+    let test_harness =
+        AmmTestHarness::new_with_provider(Box::new(RpcClient::new(rpc_url)), amm_key, option);
 
-    let client = RpcClient::new(&rpc_url);
-
-    let amm_context = get_amm_context(&client).await?;
-
-    let account = client
-        .get_account(&amm_key)
-        .expect("Should find AMM in markets cache or on-chain");
-
-    let ui_account = encode_ui_account(&amm_key, &account, UiAccountEncoding::Base64, None, None);
-
-    let keyed_ui_account = KeyedUiAccount {
-        pubkey: amm_id,
-        ui_account,
-        params: None,
-    };
-
-    let keyed_account: KeyedAccount = keyed_ui_account.try_into()?;
-
-    let test_harness = AmmTestHarness::new_with_rpc_url(rpc_url, amm_key, option);
+    take_snapshot_with_harness(&test_harness, scope, Path::new(SNAPSHOT_ROOT), force).await
+}
 
-    let mut saber_wrapper_mints = HashSet::new();
+/// Snapshot the pool of `test_harness` under `snapshot_root`
+pub async fn take_snapshot_with_harness(
+    test_harness: &AmmTestHarness,
+    scope: &SnapshotScope,
+    snapshot_root: &Path,
+    force: bool,
+) -> Result<()> {
+    let provider = test_harness.provider.as_ref();
+    let amm_context = get_amm_context(provider).await?;
 
-    let mut amm = amm_factory(&keyed_account, &amm_context, &mut saber_wrapper_mints)?;
+    let keyed_account = test_harness.get_keyed_account(test_harness.key).await?;
 
-    let amm: &mut (dyn Amm + Send + Sync) = amm.as_mut();
+    let mut amm = SarosDlmm::from_keyed_account(&keyed_account, &amm_context)?;
     for _ in 0..3 {
-        test_harness.update_amm(amm);
+        test_harness.update_amm(&mut amm).await;
     }
 
-    test_harness.snapshot_amm_accounts(amm, keyed_account.params, force)?;
+    test_harness
+        .snapshot_amm_accounts(&amm, keyed_account.params, scope, snapshot_root, force)
+        .await?;
 
     Ok(())
 }
 
-pub async fn get_clock(rpc_client: &RpcClient) -> anyhow::Result<Clock> {
-    let clock_data = rpc_client
-        .get_account_with_commitment(&sysvar::clock::ID, rpc_client.commitment())?
-        .value
+pub async fn get_clock(provider: &(dyn AccountProvider + Send + Sync)) -> anyhow::Result<Clock> {
+    let clock_data = provider
+        .get_account(&sysvar::clock::ID)
+        .await?
         .context("Failed to get clock account")?;
 
     let clock: Clock = bincode::deserialize(&clock_data.data)
@@ -1329,14 +1433,18 @@ pub async fn get_clock(rpc_client: &RpcClient) -> anyhow::Result<Clock> {
     Ok(clock)
 }
 
-pub async fn get_clock_ref(rpc_client: &RpcClient) -> anyhow::Result<ClockRef> {
-    let clock = get_clock(rpc_client).await?;
+pub async fn get_clock_ref(
+    provider: &(dyn AccountProvider + Send + Sync),
+) -> anyhow::Result<ClockRef> {
+    let clock = get_clock(provider).await?;
     Ok(ClockRef::from(clock))
 }
 
-pub async fn get_amm_context(rpc_client: &RpcClient) -> anyhow::Result<AmmContext> {
+pub async fn get_amm_context(
+    provider: &(dyn AccountProvider + Send + Sync),
+) -> anyhow::Result<AmmContext> {
     Ok(AmmContext {
-        clock_ref: get_clock_ref(rpc_client).await?,
+        clock_ref: get_clock_ref(provider).await?,
     })
 }
 
@@ -1396,3 +1504,142 @@ pub async fn ensure_hook_bin_arrays_initialized(
     }
     Ok(ixs)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amms::test_pool::{build_test_pool, build_test_pool_accounts};
+    use saros_sdk::utils::helper::find_bin_array_at_position;
+
+    #[tokio::test]
+    async fn test_take_snapshot_with_harness() {
+        let pool = build_test_pool(
+            Pubkey::new_unique(),
+            SAROS_MINT,
+            USDC_MINT,
+            10,
+            10_000,
+            1_000_000,
+        );
+        let accounts = build_test_pool_accounts(&pool, 0);
+        let test_harness =
+            AmmTestHarness::new_with_provider(Box::new(accounts.clone()), pool.key, None);
+
+        let snapshot_root =
+            std::env::temp_dir().join(format!("saros-snapshot-{}", Pubkey::new_unique()));
+        take_snapshot_with_harness(
+            &test_harness,
+            &SnapshotScope::default(),
+            &snapshot_root,
+            false,
+        )
+        .await
+        .unwrap();
+
+        let snapshot_path = snapshot_root.join(test_harness.directory_name());
+        let manifest: Value = serde_json::from_str(
+            &std::fs::read_to_string(snapshot_path.join(SNAPSHOT_MANIFEST_FILE)).unwrap(),
+        )
+        .unwrap();
+        let snapshot = load_fixture_accounts(&snapshot_path).unwrap();
+        std::fs::remove_dir_all(&snapshot_root).unwrap();
+
+        let captured = manifest["accounts"].as_object().unwrap();
+        assert_eq!(captured[&sysvar::clock::ID.to_string()], "clock sysvar");
+        assert_eq!(captured[&pool.key.to_string()], "swap account");
+        assert_eq!(captured[&SAROS_MINT.to_string()], "swap account");
+        for bin_array in pool.bin_array_key {
+            assert!(captured.contains_key(&bin_array.to_string()));
+        }
+        // The test pool accounts lack the vaults, event authority and programs of the swap
+        let missing = manifest["missing"].as_object().unwrap();
+        assert_eq!(missing[&pool.event_authority.to_string()], "swap account");
+
+        // Every captured account is written as a fixture, as fetched
+        assert_eq!(snapshot.len(), captured.len());
+        for (address, account) in snapshot.iter() {
+            assert!(captured.contains_key(&address.to_string()));
+            assert_eq!(account, &accounts[address]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_scope_addresses() {
+        let pool = build_test_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            10,
+            10_000,
+            1_000_000,
+        );
+        let mut accounts = build_test_pool_accounts(&pool, 0);
+
+        let mut position = Position::default();
+        position.pair = pool.key;
+        position.position_mint = Pubkey::new_unique();
+        position.lower_bin_id = pool.pair.active_id - 100;
+        position.upper_bin_id = pool.pair.active_id - 40;
+        let mut data = vec![0u8; Position::LEN];
+        position.pack_into_slice(&mut data);
+        let position_key = find_position(position.position_mint);
+        accounts.insert(
+            position_key,
+            Account {
+                lamports: 1,
+                data,
+                owner: liquidity_book::ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        let test_harness = AmmTestHarness::new_with_provider(Box::new(accounts), pool.key, None);
+        assert!(test_harness
+            .get_scope_addresses(pool.key, &SnapshotScope::default())
+            .await
+            .unwrap()
+            .is_empty());
+
+        let scope = SnapshotScope {
+            bin_array_radius: Some(2),
+            pair_accounts: true,
+            positions: vec![position_key],
+        };
+        // First reason wins, as in the snapshot
+        let mut addresses: HashMap<Pubkey, String> = HashMap::new();
+        for (address, reason) in test_harness
+            .get_scope_addresses(pool.key, &scope)
+            .await
+            .unwrap()
+        {
+            addresses.entry(address).or_insert(reason);
+        }
+
+        let active_index = pool.pair.active_id / BIN_ARRAY_SIZE;
+        for index in active_index - 2..=active_index + 2 {
+            let bin_array = get_pair_bin_array(index, &pool.key, &liquidity_book::ID).0;
+            assert_eq!(addresses[&bin_array], format!("bin array {}", index));
+        }
+        assert_eq!(
+            addresses[&find_bin_step_config(pool.pair.liquidity_book_config, pool.pair.bin_step)],
+            "bin step config"
+        );
+        assert_eq!(addresses[&position_key], "position");
+        assert!(addresses.contains_key(&position.position_mint));
+        for bin_array in find_bin_array_at_position(position.clone()).1 {
+            assert!(addresses.contains_key(&bin_array));
+        }
+        // The test pool has no hook
+        assert!(addresses.values().all(|reason| !reason.contains("hook")));
+
+        let other_pair = SnapshotScope {
+            positions: vec![pool.key],
+            ..Default::default()
+        };
+        assert!(test_harness
+            .get_scope_addresses(pool.key, &other_pair)
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use std::sync::{
+    atomic::{AtomicI64, AtomicU64},
+    Arc,
+};
+
+use jupiter_amm_interface::AccountMap;
+use saros_sdk::{
+    constants::MIDDLE_BIN_ID,
+    math::fees::TokenTransferFee,
+    state::{bin::BIN_ARRAY_SIZE, bin_array::BinArray, pair::Pair},
+    utils::helper::{find_event_authority, get_pair_bin_array, get_swap_pair_bin_array},
+};
+use solana_sdk::{
+    account::Account,
+    program_pack::{IsInitialized, Pack},
+    pubkey::Pubkey,
+    sysvar::{clock, clock::Clock},
+};
+use spl_token::state::Mint;
+
+use crate::{AccountSlots, ClockSource, SarosDlmm};
+
+/// In memory pool centred on `MIDDLE_BIN_ID` with `reserve` of Y in every bin below the active bin,
+/// `reserve` of X in every bin above it and both in the active bin
+pub fn build_test_pool(
+    key: Pubkey,
+    token_mint_x: Pubkey,
+    token_mint_y: Pubkey,
+    bin_step: u8,
+    base_factor: u16,
+    reserve: u64,
+) -> SarosDlmm {
+    let mut pair = Pair::unpack_from_slice(&[0u8; Pair::LEN]).unwrap();
+    pair.token_mint_x = token_mint_x;
+    pair.token_mint_y = token_mint_y;
+    pair.bin_step = bin_step;
+    pair.bin_step_seed = [bin_step];
+    pair.active_id = MIDDLE_BIN_ID as u32;
+    pair.static_fee_parameters.base_factor = base_factor;
+
+    let bin_array_index = pair.bin_array_index();
+
+    let mut bin_arrays = [BinArray::default(); 3];
+    for (i, bin_array) in bin_arrays.iter_mut().enumerate() {
+        let index = bin_array_index - 1 + i as u32;
+        bin_array.initialize(key, index);
+
+        for (j, bin) in bin_array.bins.iter_mut().enumerate() {
+            let bin_id = index * BIN_ARRAY_SIZE + j as u32;
+            if bin_id <= pair.active_id {
+                bin.reserve_y = reserve;
+            }
+            if bin_id >= pair.active_id {
+                bin.reserve_x = reserve;
+            }
+            bin.total_supply = u128::from(reserve) << 64;
+        }
+    }
+
+    let (bin_array_lower_key, bin_array_middle_key, bin_array_upper_key) =
+        get_swap_pair_bin_array(bin_array_index, &key, &liquidity_book::ID);
+    let (active_bin_array_lower_key, active_bin_array_upper_key) =
+        get_pair_bin_array(bin_array_index, &key, &liquidity_book::ID);
+
+    SarosDlmm {
+        program_id: liquidity_book::ID,
+        key,
+        label: "saros_dlmm".into(),
+        pair,
+        token_transfer_fee: TokenTransferFee::default(),
+        bin_array_lower: bin_arrays[0],
+        bin_array_middle: bin_arrays[1],
+        bin_array_upper: bin_arrays[2],
+        bin_array_key: [
+            bin_array_lower_key,
+            bin_array_middle_key,
+            bin_array_upper_key,
+        ],
+        active_bin_array_key: [active_bin_array_lower_key, active_bin_array_upper_key],
+        token_vault: [Pubkey::new_unique(), Pubkey::new_unique()],
+        token_program: [spl_token::ID, spl_token::ID],
+        event_authority: find_event_authority(liquidity_book::ID),
+        hook: key,
+        hook_bin_array_key: [Pubkey::default(); 3],
+        active_hook_bin_array_key: [Pubkey::default(); 2],
+        clock_source: ClockSource::Shared,
+        epoch: Arc::new(AtomicU64::new(0)),
+        timestamp: Arc::new(AtomicI64::new(0)),
+        slots: AccountSlots::default(),
+        max_slot_spread: SarosDlmm::DEFAULT_MAX_SLOT_SPREAD,
+    }
+}
+
+fn test_account(owner: Pubkey, data: Vec<u8>) -> Account {
+    Account {
+        lamports: 1,
+        data,
+        owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// On-chain accounts of `pool`: the pair, its initialized bin arrays, both mints and the clock
+pub fn build_test_pool_accounts(pool: &SarosDlmm, unix_timestamp: i64) -> AccountMap {
+    let mut accounts = AccountMap::default();
+
+    let mut pair_data = vec![0u8; Pair::LEN];
+    pool.pair.pack_into_slice(&mut pair_data);
+    accounts.insert(pool.key, test_account(pool.program_id, pair_data));
+
+    let bin_arrays = [
+        pool.bin_array_lower,
+        pool.bin_array_middle,
+        pool.bin_array_upper,
+    ];
+    for (key, bin_array) in pool.bin_array_key.iter().zip(bin_arrays) {
+        if bin_array.is_initialized() {
+            let mut data = vec![0u8; BinArray::LEN];
+            bin_array.pack_into_slice(&mut data);
+            accounts.insert(*key, test_account(pool.program_id, data));
+        }
+    }
+
+    for (mint, token_program) in [pool.pair.token_mint_x, pool.pair.token_mint_y]
+        .into_iter()
+        .zip(pool.token_program)
+    {
+        let mut data = vec![0u8; Mint::LEN];
+        Mint {
+            decimals: 6,
+            is_initialized: true,
+            ..Default::default()
+        }
+        .pack_into_slice(&mut data);
+        accounts.insert(mint, test_account(token_program, data));
+    }
+
+    let clock = Clock {
+        unix_timestamp,
+        ..Default::default()
+    };
+    accounts.insert(
+        clock::ID,
+        test_account(solana_sdk::sysvar::ID, bincode::serialize(&clock).unwrap()),
+    );
+
+    accounts
+}
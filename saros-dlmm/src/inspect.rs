@@ -0,0 +1,262 @@
+use anyhow::{anyhow, Result};
+use saros_sdk::{
+    state::{
+        bin::BIN_ARRAY_SIZE,
+        bin_array::{BinArray, BinArrayPair},
+        pair::Pair,
+        position::Position,
+        StateAccount,
+    },
+    utils::helper::{find_bin_array_at_position, get_pair_bin_array},
+};
+use solana_sdk::{account::Account, program_pack::Pack, pubkey::Pubkey};
+
+use crate::{account_provider::AccountProvider, quote_report::get_bin_price};
+
+/// Bin holding liquidity, as read from its bin array
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BinInfo {
+    pub bin_id: u32,
+    /// Price of token X in token Y atoms
+    pub price: f64,
+    pub total_supply: u128,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+}
+
+/// Bin of a position with the amounts its shares are worth
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PositionBin {
+    pub bin_id: u32,
+    pub price: f64,
+    pub liquidity_share: u128,
+    pub amount_x: u64,
+    pub amount_y: u64,
+}
+
+/// Decode the account `key` with `T`, checking it exists, is large enough and holds a `T`
+pub async fn get_state_account<T, P>(provider: &P, key: &Pubkey) -> Result<T>
+where
+    T: StateAccount,
+    P: AccountProvider + ?Sized,
+{
+    let account = provider
+        .get_account(key)
+        .await?
+        .ok_or_else(|| anyhow!("Account does not exist: {}", key))?;
+    decode_state_account(key, &account)
+}
+
+/// Decode `account` with `T`, checking it is a liquidity book account large enough to hold a `T`
+/// and carrying the discriminator of `T`
+pub fn decode_state_account<T: StateAccount>(key: &Pubkey, account: &Account) -> Result<T> {
+    if account.owner != liquidity_book::ID || account.data.len() < T::LEN {
+        return Err(anyhow!("Account {} is not a liquidity book account", key));
+    }
+    if account.data[..8] != T::DISCRIMINATOR {
+        return Err(anyhow!(
+            "Account {} does not hold the requested state, its discriminator differs",
+            key
+        ));
+    }
+
+    Ok(T::unpack_from_slice(&account.data)?)
+}
+
+/// Bins of `pair_key` between `lower_bin_id` and `upper_bin_id` holding liquidity, bin arrays that
+/// were never initialized are skipped
+pub async fn get_bins<P>(
+    provider: &P,
+    pair_key: &Pubkey,
+    pair: &Pair,
+    lower_bin_id: u32,
+    upper_bin_id: u32,
+) -> Result<Vec<BinInfo>>
+where
+    P: AccountProvider + ?Sized,
+{
+    let indexes: Vec<u32> =
+        (lower_bin_id / BIN_ARRAY_SIZE..=upper_bin_id / BIN_ARRAY_SIZE).collect();
+    let keys: Vec<Pubkey> = indexes
+        .iter()
+        .map(|index| get_pair_bin_array(*index, pair_key, &liquidity_book::ID).0)
+        .collect();
+
+    let mut bins = Vec::new();
+    for account in provider
+        .get_multiple_accounts(&keys)
+        .await?
+        .into_iter()
+        .flatten()
+    {
+        if account.data.len() < BinArray::LEN {
+            continue;
+        }
+        let bin_array = BinArray::unpack_from_slice(&account.data)?;
+
+        for (offset, bin) in bin_array.bins.iter().enumerate() {
+            let bin_id = bin_array.index * BIN_ARRAY_SIZE + offset as u32;
+            if bin_id < lower_bin_id || bin_id > upper_bin_id {
+                continue;
+            }
+            if bin.total_supply == 0 && bin.reserve_x == 0 && bin.reserve_y == 0 {
+                continue;
+            }
+
+            bins.push(BinInfo {
+                bin_id,
+                price: get_bin_price(pair.bin_step, bin_id)?,
+                total_supply: bin.total_supply,
+                reserve_x: bin.reserve_x,
+                reserve_y: bin.reserve_y,
+            });
+        }
+    }
+
+    Ok(bins)
+}
+
+/// Both bin arrays a position can span, an array that does not exist yet holds empty bins
+pub async fn get_position_bin_array<P>(provider: &P, position: &Position) -> Result<BinArrayPair>
+where
+    P: AccountProvider + ?Sized,
+{
+    let (index, keys) = find_bin_array_at_position(position.clone());
+
+    let mut bin_arrays = [BinArray::default(); 2];
+    for (i, (bin_array, account)) in bin_arrays
+        .iter_mut()
+        .zip(provider.get_multiple_accounts(&keys).await?)
+        .enumerate()
+    {
+        match account {
+            Some(account) => *bin_array = BinArray::unpack_from_slice(&account.data)?,
+            None => bin_array.initialize(position.pair, index + i as u32),
+        }
+    }
+
+    BinArrayPair::merge(bin_arrays[0], bin_arrays[1])
+}
+
+/// Bins where `position` holds shares, `bin_array` must hold its bins
+pub fn get_position_bins(
+    position: &Position,
+    bin_array: &BinArrayPair,
+    bin_step: u8,
+) -> Result<Vec<PositionBin>> {
+    position
+        .get_bin_amounts(bin_array)?
+        .into_iter()
+        .map(|bin_amounts| {
+            Ok(PositionBin {
+                bin_id: bin_amounts.bin_id,
+                price: get_bin_price(bin_step, bin_amounts.bin_id)?,
+                liquidity_share: bin_amounts.liquidity_share,
+                amount_x: bin_amounts.amount_x,
+                amount_y: bin_amounts.amount_y,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amms::test_pool::{build_test_pool, build_test_pool_accounts};
+    use saros_sdk::utils::helper::find_position;
+    use solana_sdk::account::Account;
+
+    #[tokio::test]
+    async fn test_get_bins() {
+        let pool = build_test_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            10,
+            10_000,
+            1_000_000,
+        );
+        let accounts = build_test_pool_accounts(&pool, 0);
+
+        let pair: Pair = get_state_account(&accounts, &pool.key).await.unwrap();
+        assert_eq!(pair.active_id, pool.pair.active_id);
+        assert!(get_state_account::<Position, _>(&accounts, &pool.key)
+            .await
+            .is_err());
+        // Large enough for a pair but holds a bin array
+        assert!(
+            get_state_account::<Pair, _>(&accounts, &pool.bin_array_key[1])
+                .await
+                .is_err()
+        );
+
+        // Crosses from the middle bin array into the upper one
+        let active_id = pool.pair.active_id;
+        let bins = get_bins(&accounts, &pool.key, &pair, active_id - 5, active_id + 250)
+            .await
+            .unwrap();
+        assert_eq!(bins.len(), 256);
+        assert!(bins
+            .windows(2)
+            .all(|bins| bins[0].bin_id + 1 == bins[1].bin_id));
+
+        let active = bins.iter().find(|bin| bin.bin_id == active_id).unwrap();
+        assert_eq!((active.reserve_x, active.reserve_y), (1_000_000, 1_000_000));
+        assert_eq!(active.price, 1f64);
+        assert!(bins[0].price < 1f64 && bins[255].price > 1f64);
+
+        // Bin arrays outside the pool are skipped
+        let far = active_id + 10 * BIN_ARRAY_SIZE;
+        assert!(get_bins(&accounts, &pool.key, &pair, far, far + 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_position_bins() {
+        let pool = build_test_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            10,
+            10_000,
+            1_000_000,
+        );
+        let mut accounts = build_test_pool_accounts(&pool, 0);
+
+        let active_id = pool.pair.active_id;
+        let mut position = Position::default();
+        position.pair = pool.key;
+        position.position_mint = Pubkey::new_unique();
+        position.lower_bin_id = active_id - 1;
+        position.upper_bin_id = active_id + 62;
+        // Half of the active bin and of the bin below
+        position.liquidity_shares[0] = u128::from(1_000_000u64) << 63;
+        position.liquidity_shares[1] = u128::from(1_000_000u64) << 63;
+
+        let mut data = vec![0u8; Position::LEN];
+        position.pack_into_slice(&mut data);
+        let position_key = find_position(position.position_mint);
+        accounts.insert(
+            position_key,
+            Account {
+                lamports: 1,
+                data,
+                owner: liquidity_book::ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        let position: Position = get_state_account(&accounts, &position_key).await.unwrap();
+        let bin_array = get_position_bin_array(&accounts, &position).await.unwrap();
+        let bins = get_position_bins(&position, &bin_array, pool.pair.bin_step).unwrap();
+
+        assert_eq!(bins.len(), 2);
+        assert_eq!(bins[0].bin_id, active_id - 1);
+        assert_eq!((bins[0].amount_x, bins[0].amount_y), (0, 500_000));
+        assert_eq!(bins[1].bin_id, active_id);
+        assert_eq!((bins[1].amount_x, bins[1].amount_y), (500_000, 500_000));
+    }
+}
@@ -1,18 +1,32 @@
+pub mod account_provider;
 pub mod amms;
+pub mod inspect;
+pub mod order_split;
+pub mod pair_registry;
+pub mod position_discovery;
+pub mod quote_report;
+pub mod range_order;
+pub mod rebalance;
 pub mod route;
+pub mod subscription;
+pub mod transaction_builder;
+pub mod wide_position;
 
 use crate::amms::position_manager::SarosPositionManagement;
 pub use amms::amm;
 use anchor_lang::prelude::AccountMeta;
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use bincode::deserialize;
 use jupiter_amm_interface::{
-    try_get_account_data, try_get_account_data_and_owner, AccountMap, Amm, AmmContext,
+    try_get_account_data, try_get_account_data_and_owner, AccountMap, Amm, AmmContext, ClockRef,
     KeyedAccount, Quote, QuoteParams, Swap, SwapAndAccountMetas, SwapMode, SwapParams,
 };
 use saros_sdk::utils::helper::{get_hook_bin_array, get_pair_bin_array, get_swap_pair_bin_array};
 use saros_sdk::{
-    instruction::{CreatePositionParams, ModifierPositionParams},
+    instruction::{
+        build_swap_instruction_data, BuildSwapInstructionDataParams, CreatePositionParams,
+        ModifierPositionParams,
+    },
     math::{
         fees::{
             compute_transfer_amount_for_expected_output, compute_transfer_fee, TokenTransferFee,
@@ -29,6 +43,7 @@ use saros_sdk::{
     },
 };
 use solana_sdk::{
+    instruction::Instruction,
     program_pack::IsInitialized,
     program_pack::Pack,
     pubkey,
@@ -59,8 +74,30 @@ pub struct SarosDlmm {
     // // Remaining accounts of the LB program cpi call to hooks, will be checked at hook program.
     pub hook_bin_array_key: [Pubkey; 3],
     pub active_hook_bin_array_key: [Pubkey; 2],
+    // Switched through `set_sysvar_clock` and `set_shared_clock` so the host clock is never written
+    pub(crate) clock_source: ClockSource,
     pub epoch: Arc<AtomicU64>,
     pub timestamp: Arc<AtomicI64>,
+    pub slots: AccountSlots,
+    // Quotes are refused when the pair and the swap bin arrays are further apart than this
+    pub max_slot_spread: u64,
+}
+
+/// Where a pool reads the epoch and timestamp used for transfer and dynamic fees
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClockSource {
+    /// `AmmContext::clock_ref`, kept up to date by the host and shared by every pool
+    #[default]
+    Shared,
+    /// Clock sysvar account, fetched with the pool accounts and written into `epoch`/`timestamp`
+    Sysvar,
+}
+
+/// Slot each account was observed at, 0 when unknown
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AccountSlots {
+    pub pair: u64,
+    pub bin_arrays: [u64; 3],
 }
 
 pub struct BinForSwap {
@@ -73,6 +110,27 @@ impl SarosDlmm {
     pub const ASSOCIATED_TOKEN_PROGRAM_ADDRESS: Pubkey =
         pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
 
+    pub const DEFAULT_MAX_SLOT_SPREAD: u64 = 20;
+
+    pub fn clock_source(&self) -> ClockSource {
+        self.clock_source
+    }
+
+    /// Read the time from the clock sysvar, into atomics owned by this pool and its clones so the
+    /// clock shared by the host and the other pools is left untouched
+    pub fn set_sysvar_clock(&mut self) {
+        self.epoch = Arc::new(AtomicU64::new(self.epoch.load(Ordering::Relaxed)));
+        self.timestamp = Arc::new(AtomicI64::new(self.timestamp.load(Ordering::Relaxed)));
+        self.clock_source = ClockSource::Sysvar;
+    }
+
+    /// Read the time from `clock_ref`, kept up to date by the host
+    pub fn set_shared_clock(&mut self, clock_ref: &ClockRef) {
+        self.epoch = clock_ref.epoch.clone();
+        self.timestamp = clock_ref.unix_timestamp.clone();
+        self.clock_source = ClockSource::Shared;
+    }
+
     pub fn compute_bin_array_swap(&self) -> Result<BinForSwap> {
         // unpack fixed order
         let [lower_key, middle_key, upper_key] = self.bin_array_key;
@@ -114,6 +172,262 @@ impl SarosDlmm {
             )),
         }
     }
+
+    /// Slot the account `key` was observed at, `None` when the pool does not hold it
+    pub fn get_account_slot(&self, key: &Pubkey) -> Option<u64> {
+        if *key == self.key {
+            return Some(self.slots.pair);
+        }
+
+        self.bin_array_key
+            .iter()
+            .position(|bin_array_key| bin_array_key == key)
+            .map(|i| self.slots.bin_arrays[i])
+    }
+
+    /// Replace the pair state observed at `slot` and the keys derived from it, returns true when
+    /// the swap bin arrays moved, in which case the previously loaded bin arrays are dropped
+    pub fn apply_pair(&mut self, pair: Pair, slot: u64) -> Result<bool> {
+        if slot < self.slots.pair {
+            return Err(anyhow!(
+                "Stale pair update at slot {}, pair {} is at slot {}",
+                slot,
+                self.key,
+                self.slots.pair
+            ));
+        }
+
+        self.pair = pair;
+        self.slots.pair = slot;
+        let bin_array_index = self.pair.bin_array_index();
+
+        let (bin_array_lower_key, bin_array_middle_key, bin_array_upper_key) =
+            get_swap_pair_bin_array(bin_array_index, &self.key, &self.program_id);
+        let bin_array_key = [
+            bin_array_lower_key,
+            bin_array_middle_key,
+            bin_array_upper_key,
+        ];
+
+        if let Some(hook_key) = self.pair.hook {
+            let (hook_bin_array_lower_key, hook_bin_array_middle_key, hook_bin_array_upper_key) =
+                get_swap_hook_bin_array(bin_array_index, hook_key);
+            self.hook_bin_array_key = [
+                hook_bin_array_lower_key,
+                hook_bin_array_middle_key,
+                hook_bin_array_upper_key,
+            ];
+            self.hook = hook_key;
+        }
+
+        let active_bin_array_keys =
+            get_pair_bin_array(bin_array_index, &self.key, &self.program_id);
+        let active_hook_bin_array_keys = get_hook_bin_array(bin_array_index, self.hook);
+        self.active_bin_array_key = [active_bin_array_keys.0, active_bin_array_keys.1];
+        self.active_hook_bin_array_key =
+            [active_hook_bin_array_keys.0, active_hook_bin_array_keys.1];
+
+        if self.bin_array_key == bin_array_key {
+            return Ok(false);
+        }
+
+        self.bin_array_key = bin_array_key;
+        self.bin_array_lower = BinArray::default();
+        self.bin_array_middle = BinArray::default();
+        self.bin_array_upper = BinArray::default();
+        self.slots.bin_arrays = [0; 3];
+        Ok(true)
+    }
+
+    /// Replace the swap bin array stored under `key` observed at `slot`, returns false when `key`
+    /// is not one of them
+    pub fn apply_bin_array(
+        &mut self,
+        key: &Pubkey,
+        bin_array: BinArray,
+        slot: u64,
+    ) -> Result<bool> {
+        let Some(i) = self
+            .bin_array_key
+            .iter()
+            .position(|bin_array_key| bin_array_key == key)
+        else {
+            return Ok(false);
+        };
+
+        if slot < self.slots.bin_arrays[i] {
+            return Err(anyhow!(
+                "Stale bin array update at slot {}, bin array {} is at slot {}",
+                slot,
+                key,
+                self.slots.bin_arrays[i]
+            ));
+        }
+
+        let bin_array_slot = match i {
+            0 => &mut self.bin_array_lower,
+            1 => &mut self.bin_array_middle,
+            _ => &mut self.bin_array_upper,
+        };
+        *bin_array_slot = bin_array;
+        self.slots.bin_arrays[i] = slot;
+        Ok(true)
+    }
+
+    /// Fail when the pair and the bin arrays a swap reads were observed too many slots apart,
+    /// accounts with an unknown slot are not checked
+    pub fn check_slot_spread(&self, bin_for_swap: &BinForSwap) -> Result<()> {
+        let slots: Vec<u64> = std::iter::once(self.slots.pair)
+            .chain(
+                bin_for_swap
+                    .bin_array_keys
+                    .iter()
+                    .filter_map(|key| self.get_account_slot(key)),
+            )
+            .filter(|slot| *slot != 0)
+            .collect();
+
+        let (Some(min), Some(max)) = (slots.iter().min(), slots.iter().max()) else {
+            return Ok(());
+        };
+        if max - min > self.max_slot_spread {
+            return Err(anyhow!(
+                "Pair {} state spans slots {} to {}, more than {} apart",
+                self.key,
+                min,
+                max,
+                self.max_slot_spread
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// `Amm::update` with every account of `account_map` observed at `slot`, fails without
+    /// updating anything when the pool already holds a more recent pair. Bin arrays the pool holds
+    /// at a more recent slot than `slot` are kept, including the ones staying in the window when
+    /// the pair moves.
+    pub fn update_with_slot(&mut self, account_map: &AccountMap, slot: u64) -> Result<()> {
+        if slot < self.slots.pair {
+            return Err(anyhow!(
+                "Stale update at slot {}, pair {} is at slot {}",
+                slot,
+                self.key,
+                self.slots.pair
+            ));
+        }
+
+        let held_keys = self.bin_array_key;
+        let held_bin_arrays = [
+            self.bin_array_lower,
+            self.bin_array_middle,
+            self.bin_array_upper,
+        ];
+        let held_slots = self.slots.bin_arrays;
+
+        self.update(account_map)?;
+        self.slots.pair = slot;
+
+        // Every swap bin array comes from whichever of `account_map` and the held ones is newer
+        self.bin_array_lower = BinArray::default();
+        self.bin_array_middle = BinArray::default();
+        self.bin_array_upper = BinArray::default();
+        self.slots.bin_arrays = [0; 3];
+        for key in self.bin_array_key {
+            let held = held_keys.iter().position(|held_key| *held_key == key);
+            let is_held_newer = held.is_some_and(|i| held_slots[i] > slot);
+
+            match try_get_account_data(account_map, &key) {
+                Ok(data) if !is_held_newer => {
+                    self.apply_bin_array(&key, BinArray::unpack(data)?, slot)?;
+                }
+                _ => {
+                    if let Some(i) = held {
+                        self.apply_bin_array(&key, held_bin_arrays[i], held_slots[i])?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn get_swap_account_metas(&self, swap_params: &SwapParams) -> Result<Vec<AccountMeta>> {
+        let SwapParams {
+            token_transfer_authority,
+            source_token_account,
+            destination_token_account,
+            source_mint,
+            ..
+        } = swap_params;
+
+        let bin_for_swap = self.compute_bin_array_swap()?;
+        let swap_for_y = is_swap_for_y(*source_mint, self.pair.token_mint_x);
+
+        let (user_vault_x, user_vault_y) = if swap_for_y {
+            (source_token_account, destination_token_account)
+        } else {
+            (destination_token_account, source_token_account)
+        };
+
+        let user = *token_transfer_authority;
+        let mut account_metas = Vec::new();
+
+        {
+            account_metas.push(AccountMeta::new(self.key, false));
+            account_metas.push(AccountMeta::new_readonly(self.pair.token_mint_x, false));
+            account_metas.push(AccountMeta::new_readonly(self.pair.token_mint_y, false));
+            account_metas.push(AccountMeta::new(bin_for_swap.bin_array_keys[0], false));
+            account_metas.push(AccountMeta::new(bin_for_swap.bin_array_keys[1], false));
+            account_metas.push(AccountMeta::new(self.token_vault[0], false));
+            account_metas.push(AccountMeta::new(self.token_vault[1], false));
+            account_metas.push(AccountMeta::new(*user_vault_x, false));
+            account_metas.push(AccountMeta::new(*user_vault_y, false));
+            account_metas.push(AccountMeta::new_readonly(user, true));
+            account_metas.push(AccountMeta::new_readonly(self.token_program[0], false));
+            account_metas.push(AccountMeta::new_readonly(self.token_program[1], false));
+            account_metas.push(AccountMeta::new_readonly(spl_memo::ID, false));
+        }
+
+        // If pair does not have hook, hook should be pair key (dummy)
+        account_metas.push(AccountMeta::new(self.hook, false));
+        account_metas.push(AccountMeta::new_readonly(rewarder_hook::ID, false));
+        // This expect as the last of swap instruction
+        account_metas.push(AccountMeta::new_readonly(self.event_authority, false));
+        account_metas.push(AccountMeta::new_readonly(self.program_id, false));
+
+        // Remaining accounts for hook CPI call
+        if self.hook != self.key {
+            account_metas.push(AccountMeta::new(bin_for_swap.hook_bin_array_keys[0], false));
+            account_metas.push(AccountMeta::new(bin_for_swap.hook_bin_array_keys[1], false));
+        }
+
+        Ok(account_metas)
+    }
+
+    /// Swap instruction for `swap_params`, `other_amount_threshold` is the minimum amount out for
+    /// exact in swaps and the maximum amount in for exact out swaps
+    pub fn get_swap_instruction(
+        &self,
+        swap_params: &SwapParams,
+        other_amount_threshold: u64,
+    ) -> Result<Instruction> {
+        let (amount, swap_mode) = match swap_params.swap_mode {
+            SwapMode::ExactIn => (swap_params.in_amount, SwapType::ExactIn),
+            SwapMode::ExactOut => (swap_params.out_amount, SwapType::ExactOut),
+        };
+
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts: self.get_swap_account_metas(swap_params)?,
+            data: build_swap_instruction_data(BuildSwapInstructionDataParams {
+                amount,
+                other_amount_threshold,
+                swap_for_y: is_swap_for_y(swap_params.source_mint, self.pair.token_mint_x),
+                swap_mode,
+            })?,
+        })
+    }
 }
 
 impl Amm for SarosDlmm {
@@ -182,8 +496,11 @@ impl Amm for SarosDlmm {
             hook: hook_key,
             hook_bin_array_key,
             active_hook_bin_array_key: [Pubkey::default(), Pubkey::default()],
+            clock_source: ClockSource::Shared,
             epoch: amm_context.clock_ref.epoch.clone(),
             timestamp: amm_context.clock_ref.unix_timestamp.clone(),
+            slots: AccountSlots::default(),
+            max_slot_spread: SarosDlmm::DEFAULT_MAX_SLOT_SPREAD,
         })
     }
 
@@ -192,15 +509,18 @@ impl Amm for SarosDlmm {
     }
 
     fn get_accounts_to_update(&self) -> Vec<Pubkey> {
-        vec![
+        let mut accounts = vec![
             self.key,
             self.bin_array_key[0],
             self.bin_array_key[1],
             self.bin_array_key[2],
             self.pair.token_mint_x,
             self.pair.token_mint_y,
-            clock::ID,
-        ]
+        ];
+        if self.clock_source == ClockSource::Sysvar {
+            accounts.push(clock::ID);
+        }
+        accounts
     }
 
     fn update(&mut self, account_map: &AccountMap) -> Result<()> {
@@ -212,6 +532,8 @@ impl Amm for SarosDlmm {
         })?;
 
         self.pair = Pair::unpack(pair_data)?;
+        // The accounts replaced here come without a slot, `update_with_slot` sets them afterwards
+        self.slots.pair = 0;
         let bin_array_index = self.pair.bin_array_index();
 
         let (bin_array_lower_key, bin_array_middle_key, bin_array_upper_key) =
@@ -226,6 +548,7 @@ impl Amm for SarosDlmm {
                 bin_array_middle_key,
                 bin_array_upper_key,
             ];
+            self.slots.bin_arrays = [0; 3];
 
             if let Some(hook_key) = self.pair.hook {
                 let (hook_bin_array_lower_key, hook_bin_array_middle_key, hook_bin_array_upper_key) =
@@ -243,6 +566,7 @@ impl Amm for SarosDlmm {
             let _ = match try_get_account_data(&account_map, &bin_array_lower_key) {
                 Ok(data) => {
                     self.bin_array_lower = BinArray::unpack(data)?;
+                    self.slots.bin_arrays[0] = 0;
                 }
                 Err(_) => {}
             };
@@ -250,6 +574,7 @@ impl Amm for SarosDlmm {
             let _ = match try_get_account_data(&account_map, &bin_array_middle_key) {
                 Ok(data) => {
                     self.bin_array_middle = BinArray::unpack(data)?;
+                    self.slots.bin_arrays[1] = 0;
                 }
                 Err(_) => {}
             };
@@ -257,6 +582,7 @@ impl Amm for SarosDlmm {
             let _ = match try_get_account_data(&account_map, &bin_array_upper_key) {
                 Ok(data) => {
                     self.bin_array_upper = BinArray::unpack(data)?;
+                    self.slots.bin_arrays[2] = 0;
                 }
                 Err(_) => {}
             };
@@ -290,14 +616,19 @@ impl Amm for SarosDlmm {
                 },
             )?;
 
-        let clock_data = try_get_account_data(account_map, &clock::ID)
-            .with_context(|| format!("Sysvar Clock account does not exist : {}", clock::ID))?;
+        // A shared clock belongs to the host, only a sysvar clock is written here and always into
+        // the existing atomics so clones of this pool keep seeing the same time
+        if self.clock_source == ClockSource::Sysvar {
+            let clock_data = try_get_account_data(account_map, &clock::ID)
+                .with_context(|| format!("Sysvar Clock account does not exist : {}", clock::ID))?;
 
-        let clock: Clock =
-            deserialize(clock_data).with_context(|| "Failed to deserialize Clock")?;
+            let clock: Clock =
+                deserialize(clock_data).with_context(|| "Failed to deserialize Clock")?;
 
-        self.epoch = Arc::new(AtomicU64::new(clock.epoch));
-        self.timestamp = Arc::new(AtomicI64::new(clock.unix_timestamp));
+            self.epoch.store(clock.epoch, Ordering::Relaxed);
+            self.timestamp
+                .store(clock.unix_timestamp, Ordering::Relaxed);
+        }
 
         self.token_transfer_fee = TokenTransferFee::new(
             &mut self.token_transfer_fee,
@@ -343,6 +674,7 @@ impl Amm for SarosDlmm {
         let block_timestamp = u64::try_from(self.timestamp.load(Ordering::Relaxed))?;
 
         let bin_for_swap = self.compute_bin_array_swap()?;
+        self.check_slot_spread(&bin_for_swap)?;
 
         let bin_array =
             BinArrayPair::merge(bin_for_swap.bin_arrays[0], bin_for_swap.bin_arrays[1])?;
@@ -411,54 +743,7 @@ impl Amm for SarosDlmm {
     }
 
     fn get_swap_and_account_metas(&self, swap_params: &SwapParams) -> Result<SwapAndAccountMetas> {
-        let SwapParams {
-            token_transfer_authority,
-            source_token_account,
-            destination_token_account,
-            source_mint,
-            ..
-        } = swap_params;
-
-        let bin_for_swap = self.compute_bin_array_swap()?;
-        let swap_for_y = is_swap_for_y(*source_mint, self.pair.token_mint_x);
-
-        let (user_vault_x, user_vault_y) = if swap_for_y {
-            (source_token_account, destination_token_account)
-        } else {
-            (destination_token_account, source_token_account)
-        };
-
-        let user = *token_transfer_authority;
-        let mut account_metas = Vec::new();
-
-        {
-            account_metas.push(AccountMeta::new(self.key, false));
-            account_metas.push(AccountMeta::new_readonly(self.pair.token_mint_x, false));
-            account_metas.push(AccountMeta::new_readonly(self.pair.token_mint_y, false));
-            account_metas.push(AccountMeta::new(bin_for_swap.bin_array_keys[0], false));
-            account_metas.push(AccountMeta::new(bin_for_swap.bin_array_keys[1], false));
-            account_metas.push(AccountMeta::new(self.token_vault[0], false));
-            account_metas.push(AccountMeta::new(self.token_vault[1], false));
-            account_metas.push(AccountMeta::new(*user_vault_x, false));
-            account_metas.push(AccountMeta::new(*user_vault_y, false));
-            account_metas.push(AccountMeta::new_readonly(user, true));
-            account_metas.push(AccountMeta::new_readonly(self.token_program[0], false));
-            account_metas.push(AccountMeta::new_readonly(self.token_program[1], false));
-            account_metas.push(AccountMeta::new_readonly(spl_memo::ID, false));
-        }
-
-        // If pair does not have hook, hook should be pair key (dummy)
-        account_metas.push(AccountMeta::new(self.hook, false));
-        account_metas.push(AccountMeta::new_readonly(rewarder_hook::ID, false));
-        // This expect as the last of swap instruction
-        account_metas.push(AccountMeta::new_readonly(self.event_authority, false));
-        account_metas.push(AccountMeta::new_readonly(self.program_id, false));
-
-        // Remaining accounts for hook CPI call
-        if self.hook != self.key {
-            account_metas.push(AccountMeta::new(bin_for_swap.hook_bin_array_keys[0], false));
-            account_metas.push(AccountMeta::new(bin_for_swap.hook_bin_array_keys[1], false));
-        }
+        let _account_metas = self.get_swap_account_metas(swap_params)?;
 
         unimplemented!();
 
@@ -552,7 +837,7 @@ impl SarosPositionManagement for SarosDlmm {
         {
             account_metas.push(AccountMeta::new(self.key, false));
             account_metas.push(AccountMeta::new(position_key, false));
-            account_metas.push(AccountMeta::new(position_mint, true));
+            account_metas.push(AccountMeta::new(position_mint, false));
             account_metas.push(AccountMeta::new(position_token_account, false));
             account_metas.push(AccountMeta::new(bin_array_position_lower, false));
             account_metas.push(AccountMeta::new(bin_array_position_upper, false));
@@ -1,10 +1,89 @@
-use clap::Parser;
-use saros_dlmm_sdk::amms::test_harness::take_snapshot;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use clap::{Parser, ValueEnum};
+use jupiter_amm_interface::{QuoteParams, SwapMode};
+use saros_dlmm_sdk::{
+    account_provider::{load_fixture_accounts, AccountProvider},
+    amms::test_harness::{take_snapshot, SnapshotScope},
+    inspect::{
+        get_bins, get_position_bin_array, get_position_bins, get_state_account, BinInfo,
+        PositionBin,
+    },
+    quote_report::{get_bin_price, get_quote_report, QuoteReport},
+    transaction_builder::{
+        get_add_liquidity_instruction, get_claim_rewards_instructions,
+        get_close_position_instructions, get_existing_accounts, get_remove_liquidity_instructions,
+        get_swap_transaction,
+    },
+    wide_position::{build_transaction_batches, WidePosition},
+    SarosDlmm,
+};
+use saros_sdk::{
+    instruction::LiquidityShape,
+    state::{pair::Pair, position::Position},
+};
+use serde_json::{json, Value};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    hash::Hash,
+    instruction::Instruction,
+    message::Message,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair},
+    signer::Signer,
+    transaction::Transaction,
+};
 
 #[derive(Parser, Debug)]
 pub struct ConfigOverride {
     #[clap(long)]
-    pub rpc_url: String,
+    pub rpc_url: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Mode {
+    ExactIn,
+    ExactOut,
+}
+
+impl From<Mode> for SwapMode {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::ExactIn => SwapMode::ExactIn,
+            Mode::ExactOut => SwapMode::ExactOut,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Shape {
+    Spot,
+    Curve,
+    BidAsk,
+}
+
+#[derive(Parser, Debug)]
+pub struct TransactionArgs {
+    /// Snapshot directory to read the accounts from instead of the RPC
+    #[clap(long)]
+    pub snapshot: Option<PathBuf>,
+    /// Keypair file of the user, the transactions are left unsigned by the user without it
+    #[clap(long)]
+    pub keypair: Option<PathBuf>,
+    /// Owner and fee payer of the transactions, defaults to the keypair
+    #[clap(long)]
+    pub user: Option<Pubkey>,
+    /// Recent blockhash of the transactions, fetched from the RPC if omitted
+    #[clap(long)]
+    pub blockhash: Option<Hash>,
 }
 
 #[derive(Parser, Debug)]
@@ -19,6 +98,138 @@ pub enum Command {
         /// Overwrite the output snapshot if it exists
         #[clap(short, long)]
         force: bool,
+        /// Also capture the bin arrays (and hook bin arrays) this many arrays around the active one
+        #[clap(long)]
+        bin_array_radius: Option<u32>,
+        /// Also capture the config, bin step config and hook accounts of the pair
+        #[clap(long)]
+        pair_accounts: bool,
+        /// Also capture a position with its bin arrays and hook accounts, can be repeated
+        #[clap(long = "position")]
+        positions: Vec<Pubkey>,
+    },
+    /// Quote a swap and trace the bins it crosses
+    Quote {
+        #[clap(long)]
+        pair: Pubkey,
+        #[clap(long)]
+        input_mint: Pubkey,
+        /// Amount in for exact in, amount out for exact out, in atoms
+        #[clap(long)]
+        amount: u64,
+        #[clap(long, value_enum, default_value_t = Mode::ExactIn)]
+        mode: Mode,
+        /// Snapshot directory to load the pool from instead of the RPC
+        #[clap(long)]
+        snapshot: Option<PathBuf>,
+        #[clap(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    /// Decode a pair: fee parameters, active bin, price and hook
+    InspectPair {
+        #[clap(long)]
+        pair: Pubkey,
+        /// Snapshot directory to read the accounts from instead of the RPC
+        #[clap(long)]
+        snapshot: Option<PathBuf>,
+        #[clap(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    /// List the bins holding liquidity around the active bin of a pair
+    InspectBins {
+        #[clap(long)]
+        pair: Pubkey,
+        /// Number of bins to list on each side of the active bin
+        #[clap(long, default_value_t = 20)]
+        radius: u32,
+        /// Snapshot directory to read the accounts from instead of the RPC
+        #[clap(long)]
+        snapshot: Option<PathBuf>,
+        #[clap(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    /// Decode a position with the shares and token amounts of each of its bins
+    InspectPosition {
+        /// Position account, not the position mint
+        #[clap(long)]
+        position: Pubkey,
+        /// Snapshot directory to read the accounts from instead of the RPC
+        #[clap(long)]
+        snapshot: Option<PathBuf>,
+        #[clap(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    /// Build a swap transaction, the output token account is created if missing
+    Swap {
+        #[clap(long)]
+        pair: Pubkey,
+        #[clap(long)]
+        input_mint: Pubkey,
+        /// Amount in for exact in, amount out for exact out, in atoms
+        #[clap(long)]
+        amount: u64,
+        #[clap(long, value_enum, default_value_t = Mode::ExactIn)]
+        mode: Mode,
+        #[clap(long, default_value_t = 50)]
+        slippage_bps: u16,
+        #[clap(flatten)]
+        transaction: TransactionArgs,
+    },
+    /// Build the transactions creating positions over a bin range relative to the active bin,
+    /// ranges wider than a position are split over several positions
+    CreatePosition {
+        #[clap(long)]
+        pair: Pubkey,
+        #[clap(long, allow_hyphen_values = true)]
+        relative_bin_id_left: i32,
+        #[clap(long, allow_hyphen_values = true)]
+        relative_bin_id_right: i32,
+        #[clap(flatten)]
+        transaction: TransactionArgs,
+    },
+    /// Build a transaction depositing into a position
+    AddLiquidity {
+        /// Position account, not the position mint
+        #[clap(long)]
+        position: Pubkey,
+        #[clap(long, default_value_t = 0)]
+        amount_x: u64,
+        #[clap(long, default_value_t = 0)]
+        amount_y: u64,
+        #[clap(long, value_enum, default_value_t = Shape::Spot)]
+        shape: Shape,
+        /// Standard deviation of the curve shape, in bins
+        #[clap(long, default_value_t = 10.0)]
+        sigma: f64,
+        #[clap(flatten)]
+        transaction: TransactionArgs,
+    },
+    /// Build a transaction withdrawing a share of every bin of a position
+    RemoveLiquidity {
+        /// Position account, not the position mint
+        #[clap(long)]
+        position: Pubkey,
+        /// Share of the liquidity to withdraw, in basis points
+        #[clap(long, default_value_t = 10_000)]
+        bps: u16,
+        #[clap(flatten)]
+        transaction: TransactionArgs,
+    },
+    /// Build a transaction withdrawing everything from a position and closing it
+    ClosePosition {
+        /// Position account, not the position mint
+        #[clap(long)]
+        position: Pubkey,
+        #[clap(flatten)]
+        transaction: TransactionArgs,
+    },
+    /// Build a transaction claiming the hook rewards of a position
+    ClaimRewards {
+        /// Position account, not the position mint
+        #[clap(long)]
+        position: Pubkey,
+        #[clap(flatten)]
+        transaction: TransactionArgs,
     },
 }
 
@@ -30,8 +241,337 @@ pub struct Cli {
     pub command: Command,
 }
 
+fn get_rpc_url(config_override: &ConfigOverride) -> Result<String> {
+    config_override
+        .rpc_url
+        .clone()
+        .context("--rpc-url is required to read accounts from the network")
+}
+
+fn get_provider(
+    config_override: &ConfigOverride,
+    snapshot: Option<&Path>,
+) -> Result<Box<dyn AccountProvider>> {
+    Ok(match snapshot {
+        Some(directory) => Box::new(load_fixture_accounts(directory)?),
+        None => Box::new(RpcClient::new(get_rpc_url(config_override)?)),
+    })
+}
+
+/// Fee payer, blockhash and optional keypair of the transactions built by the CLI
+struct TransactionSigner {
+    user: Pubkey,
+    keypair: Option<Keypair>,
+    blockhash: Hash,
+}
+
+async fn get_transaction_signer(
+    config_override: &ConfigOverride,
+    transaction_args: &TransactionArgs,
+) -> Result<TransactionSigner> {
+    let keypair = match &transaction_args.keypair {
+        Some(path) => Some(
+            read_keypair_file(path)
+                .map_err(|e| anyhow!("Failed to read keypair {}: {}", path.display(), e))?,
+        ),
+        None => None,
+    };
+
+    let user = match (transaction_args.user, &keypair) {
+        (Some(user), Some(keypair)) if user != keypair.pubkey() => {
+            return Err(anyhow!(
+                "Keypair {} does not match user {}",
+                keypair.pubkey(),
+                user
+            ))
+        }
+        (Some(user), _) => user,
+        (None, Some(keypair)) => keypair.pubkey(),
+        (None, None) => return Err(anyhow!("Either --user or --keypair is required")),
+    };
+
+    let blockhash = match (transaction_args.blockhash, &transaction_args.snapshot) {
+        (Some(blockhash), _) => blockhash,
+        (None, None) => {
+            RpcClient::new(get_rpc_url(config_override)?)
+                .get_latest_blockhash()
+                .await?
+        }
+        (None, Some(_)) => return Err(anyhow!("--blockhash is required with --snapshot")),
+    };
+
+    Ok(TransactionSigner {
+        user,
+        keypair,
+        blockhash,
+    })
+}
+
+/// Print `instructions` as a base64 transaction, signed by every keypair at hand
+fn print_transaction(
+    instructions: &[Instruction],
+    signer: &TransactionSigner,
+    extra_signers: &[&Keypair],
+) -> Result<()> {
+    let message = Message::new_with_blockhash(instructions, Some(&signer.user), &signer.blockhash);
+    let mut transaction = Transaction::new_unsigned(message);
+
+    let mut keypairs: Vec<&Keypair> = signer.keypair.iter().collect();
+    keypairs.extend_from_slice(extra_signers);
+    transaction.try_partial_sign(&keypairs, signer.blockhash)?;
+
+    println!("{}", STANDARD.encode(bincode::serialize(&transaction)?));
+    Ok(())
+}
+
+async fn load_position(
+    provider: &dyn AccountProvider,
+    position: &Pubkey,
+) -> Result<(Position, SarosDlmm)> {
+    let position_state: Position = get_state_account(provider, position).await?;
+    let amm = SarosDlmm::load(position_state.pair, provider).await?;
+    Ok((position_state, amm))
+}
+
+fn print_pair(key: Pubkey, pair: &Pair, format: OutputFormat) -> Result<()> {
+    let price = get_bin_price(pair.bin_step, pair.active_id)?;
+
+    match format {
+        OutputFormat::Table => {
+            let hook = pair
+                .hook
+                .map(|hook| hook.to_string())
+                .unwrap_or_else(|| "none".into());
+            let static_fee_parameters = &pair.static_fee_parameters;
+            let dynamic_fee_parameters = &pair.dynamic_fee_parameters;
+
+            println!("pair                        {}", key);
+            println!("liquidity book config       {}", pair.liquidity_book_config);
+            println!("token mint x                {}", pair.token_mint_x);
+            println!("token mint y                {}", pair.token_mint_y);
+            println!("bin step                    {}", pair.bin_step);
+            println!("active id                   {}", pair.active_id);
+            println!("price                       {}", price);
+            println!("hook                        {}", hook);
+            println!("protocol fees x             {}", pair.protocol_fees_x);
+            println!("protocol fees y             {}", pair.protocol_fees_y);
+            println!(
+                "base factor                 {}",
+                static_fee_parameters.base_factor
+            );
+            println!(
+                "filter period               {}",
+                static_fee_parameters.filter_period
+            );
+            println!(
+                "decay period                {}",
+                static_fee_parameters.decay_period
+            );
+            println!(
+                "reduction factor            {}",
+                static_fee_parameters.reduction_factor
+            );
+            println!(
+                "variable fee control        {}",
+                static_fee_parameters.variable_fee_control
+            );
+            println!(
+                "max volatility accumulator  {}",
+                static_fee_parameters.max_volatility_accumulator
+            );
+            println!(
+                "protocol share              {}",
+                static_fee_parameters.protocol_share
+            );
+            println!(
+                "time last updated           {}",
+                dynamic_fee_parameters.time_last_updated
+            );
+            println!(
+                "volatility accumulator      {}",
+                dynamic_fee_parameters.volatility_accumulator
+            );
+            println!(
+                "volatility reference        {}",
+                dynamic_fee_parameters.volatility_reference
+            );
+            println!(
+                "id reference                {}",
+                dynamic_fee_parameters.id_reference
+            );
+        }
+        OutputFormat::Json => {
+            let mut value = serde_json::to_value(pair)?;
+            value["address"] = key.to_string().into();
+            value["price"] = price.into();
+            println!("{:#}", value);
+        }
+    }
+
+    Ok(())
+}
+
+fn print_bins(active_id: u32, bins: &[BinInfo], format: OutputFormat) {
+    match format {
+        OutputFormat::Table => {
+            println!(
+                "{:>10} {:>24} {:>20} {:>20} {:>40}",
+                "bin", "price", "reserve x", "reserve y", "total supply"
+            );
+            for bin in bins.iter() {
+                let marker = if bin.bin_id == active_id { "*" } else { " " };
+                println!(
+                    "{}{:>9} {:>24} {:>20} {:>20} {:>40}",
+                    marker, bin.bin_id, bin.price, bin.reserve_x, bin.reserve_y, bin.total_supply
+                );
+            }
+        }
+        OutputFormat::Json => {
+            let bins: Vec<_> = bins
+                .iter()
+                .map(|bin| {
+                    json!({
+                        "bin_id": bin.bin_id,
+                        "price": bin.price,
+                        "reserve_x": bin.reserve_x,
+                        "reserve_y": bin.reserve_y,
+                        "total_supply": bin.total_supply.to_string(),
+                    })
+                })
+                .collect();
+            println!("{:#}", json!({ "active_id": active_id, "bins": bins }));
+        }
+    }
+}
+
+fn print_position(
+    key: Pubkey,
+    position: &Position,
+    bins: &[PositionBin],
+    format: OutputFormat,
+) -> Result<()> {
+    let amount_x: u64 = bins.iter().map(|bin| bin.amount_x).sum();
+    let amount_y: u64 = bins.iter().map(|bin| bin.amount_y).sum();
+
+    match format {
+        OutputFormat::Table => {
+            println!("position       {}", key);
+            println!("pair           {}", position.pair);
+            println!("position mint  {}", position.position_mint);
+            println!("lower bin id   {}", position.lower_bin_id);
+            println!("upper bin id   {}", position.upper_bin_id);
+            println!("amount x       {}", amount_x);
+            println!("amount y       {}", amount_y);
+            println!();
+            println!(
+                "{:>10} {:>24} {:>40} {:>20} {:>20}",
+                "bin", "price", "liquidity share", "amount x", "amount y"
+            );
+            for bin in bins.iter() {
+                println!(
+                    "{:>10} {:>24} {:>40} {:>20} {:>20}",
+                    bin.bin_id, bin.price, bin.liquidity_share, bin.amount_x, bin.amount_y
+                );
+            }
+        }
+        OutputFormat::Json => {
+            let bins: Vec<Value> = bins
+                .iter()
+                .map(|bin| {
+                    json!({
+                        "bin_id": bin.bin_id,
+                        "price": bin.price,
+                        "liquidity_share": bin.liquidity_share.to_string(),
+                        "amount_x": bin.amount_x,
+                        "amount_y": bin.amount_y,
+                    })
+                })
+                .collect();
+
+            let mut value = serde_json::to_value(position)?;
+            value["address"] = key.to_string().into();
+            value["amount_x"] = amount_x.into();
+            value["amount_y"] = amount_y.into();
+            value["bins"] = bins.into();
+            println!("{:#}", value);
+        }
+    }
+
+    Ok(())
+}
+
+fn print_quote_report(pair: Pubkey, report: &QuoteReport, format: OutputFormat) {
+    match format {
+        OutputFormat::Table => {
+            println!("pair              {}", pair);
+            println!("mode              {:?}", report.swap_mode);
+            println!(
+                "in                {} {}",
+                report.in_amount, report.input_mint
+            );
+            println!(
+                "out               {} {}",
+                report.out_amount, report.output_mint
+            );
+            println!(
+                "fee               {} {}",
+                report.fee_amount, report.fee_mint
+            );
+            println!("transfer fee in   {}", report.transfer_fee_in);
+            println!("transfer fee out  {}", report.transfer_fee_out);
+            println!("spot price        {}", report.spot_price);
+            println!("execution price   {}", report.execution_price);
+            println!("price impact      {:.4}%", report.price_impact * 100f64);
+            println!();
+            println!(
+                "{:>10} {:>24} {:>20} {:>20} {:>20}",
+                "bin", "price", "amount in", "amount out", "fee"
+            );
+            for bin in report.bins.iter() {
+                println!(
+                    "{:>10} {:>24} {:>20} {:>20} {:>20}",
+                    bin.bin_id, bin.price, bin.amount_in, bin.amount_out, bin.fee_amount
+                );
+            }
+        }
+        OutputFormat::Json => {
+            let bins: Vec<_> = report
+                .bins
+                .iter()
+                .map(|bin| {
+                    json!({
+                        "bin_id": bin.bin_id,
+                        "price": bin.price,
+                        "amount_in": bin.amount_in,
+                        "amount_out": bin.amount_out,
+                        "fee_amount": bin.fee_amount,
+                    })
+                })
+                .collect();
+
+            let report = json!({
+                "pair": pair.to_string(),
+                "swap_mode": format!("{:?}", report.swap_mode),
+                "input_mint": report.input_mint.to_string(),
+                "output_mint": report.output_mint.to_string(),
+                "in_amount": report.in_amount,
+                "out_amount": report.out_amount,
+                "fee_amount": report.fee_amount,
+                "fee_mint": report.fee_mint.to_string(),
+                "transfer_fee_in": report.transfer_fee_in,
+                "transfer_fee_out": report.transfer_fee_out,
+                "spot_price": report.spot_price,
+                "execution_price": report.execution_price,
+                "price_impact": report.price_impact,
+                "bins": bins,
+            });
+            println!("{:#}", report);
+        }
+    }
+}
+
 #[tokio::main]
-async fn main() {
+async fn main() -> Result<()> {
     let Cli {
         config_override,
         command,
@@ -42,8 +582,225 @@ async fn main() {
             amm_id,
             option,
             force,
-        } => take_snapshot(config_override.rpc_url, amm_id, option, force)
-            .await
-            .unwrap(),
+            bin_array_radius,
+            pair_accounts,
+            positions,
+        } => {
+            let scope = SnapshotScope {
+                bin_array_radius,
+                pair_accounts,
+                positions,
+            };
+            take_snapshot(
+                get_rpc_url(&config_override)?,
+                amm_id,
+                option,
+                &scope,
+                force,
+            )
+            .await?
+        }
+        Command::Quote {
+            pair,
+            input_mint,
+            amount,
+            mode,
+            snapshot,
+            format,
+        } => {
+            let provider = get_provider(&config_override, snapshot.as_deref())?;
+            let pool = SarosDlmm::load(pair, provider.as_ref()).await?;
+            let output_mint = if input_mint == pool.pair.token_mint_x {
+                pool.pair.token_mint_y
+            } else if input_mint == pool.pair.token_mint_y {
+                pool.pair.token_mint_x
+            } else {
+                return Err(anyhow!("Pair {} does not trade {}", pair, input_mint));
+            };
+
+            let report = get_quote_report(
+                &pool,
+                &QuoteParams {
+                    amount,
+                    input_mint,
+                    output_mint,
+                    swap_mode: mode.into(),
+                },
+            )?;
+            print_quote_report(pair, &report, format);
+        }
+        Command::InspectPair {
+            pair,
+            snapshot,
+            format,
+        } => {
+            let provider = get_provider(&config_override, snapshot.as_deref())?;
+            let pair_state: Pair = get_state_account(provider.as_ref(), &pair).await?;
+            print_pair(pair, &pair_state, format)?;
+        }
+        Command::InspectBins {
+            pair,
+            radius,
+            snapshot,
+            format,
+        } => {
+            let provider = get_provider(&config_override, snapshot.as_deref())?;
+            let pair_state: Pair = get_state_account(provider.as_ref(), &pair).await?;
+            let bins = get_bins(
+                provider.as_ref(),
+                &pair,
+                &pair_state,
+                pair_state.active_id.saturating_sub(radius),
+                pair_state.active_id.saturating_add(radius),
+            )
+            .await?;
+            print_bins(pair_state.active_id, &bins, format);
+        }
+        Command::InspectPosition {
+            position,
+            snapshot,
+            format,
+        } => {
+            let provider = get_provider(&config_override, snapshot.as_deref())?;
+            let position_state: Position = get_state_account(provider.as_ref(), &position).await?;
+            let pair_state: Pair =
+                get_state_account(provider.as_ref(), &position_state.pair).await?;
+            let bin_array = get_position_bin_array(provider.as_ref(), &position_state).await?;
+            let bins = get_position_bins(&position_state, &bin_array, pair_state.bin_step)?;
+            print_position(position, &position_state, &bins, format)?;
+        }
+        Command::Swap {
+            pair,
+            input_mint,
+            amount,
+            mode,
+            slippage_bps,
+            transaction,
+        } => {
+            let signer = get_transaction_signer(&config_override, &transaction).await?;
+            let provider = get_provider(&config_override, transaction.snapshot.as_deref())?;
+            let amm = SarosDlmm::load(pair, provider.as_ref()).await?;
+            let swap = get_swap_transaction(
+                &amm,
+                signer.user,
+                input_mint,
+                amount,
+                mode.into(),
+                slippage_bps,
+            )?;
+            eprintln!(
+                "in {} out {} threshold {}",
+                swap.quote.in_amount, swap.quote.out_amount, swap.other_amount_threshold
+            );
+            print_transaction(&swap.instructions, &signer, &[])?;
+        }
+        Command::CreatePosition {
+            pair,
+            relative_bin_id_left,
+            relative_bin_id_right,
+            transaction,
+        } => {
+            let signer = get_transaction_signer(&config_override, &transaction).await?;
+            let provider = get_provider(&config_override, transaction.snapshot.as_deref())?;
+            let amm = SarosDlmm::load(pair, provider.as_ref()).await?;
+            let wide_position = WidePosition::from_relative_range(
+                &amm,
+                relative_bin_id_left,
+                relative_bin_id_right,
+            )?;
+
+            // Only bin arrays (and hook bin arrays) missing on chain get initialized
+            let existing_accounts =
+                get_existing_accounts(provider.as_ref(), &wide_position.get_bin_array_keys(&amm))
+                    .await?;
+
+            let instructions =
+                wide_position.get_create_instructions(&amm, signer.user, &existing_accounts)?;
+            for batch in build_transaction_batches(instructions, &signer.user)? {
+                print_transaction(
+                    &batch.instructions,
+                    &signer,
+                    &wide_position.get_signers(&batch),
+                )?;
+            }
+            for segment in wide_position.segments.iter() {
+                eprintln!(
+                    "position mint {} bins {}..={}",
+                    segment.position_mint.pubkey(),
+                    segment.lower_bin_id,
+                    segment.upper_bin_id
+                );
+            }
+        }
+        Command::AddLiquidity {
+            position,
+            amount_x,
+            amount_y,
+            shape,
+            sigma,
+            transaction,
+        } => {
+            let signer = get_transaction_signer(&config_override, &transaction).await?;
+            let provider = get_provider(&config_override, transaction.snapshot.as_deref())?;
+            let (position_state, amm) = load_position(provider.as_ref(), &position).await?;
+            let shape = match shape {
+                Shape::Spot => LiquidityShape::Spot,
+                Shape::Curve => LiquidityShape::Curve { sigma },
+                Shape::BidAsk => LiquidityShape::BidAsk,
+            };
+
+            let instruction = get_add_liquidity_instruction(
+                &amm,
+                &position_state,
+                signer.user,
+                shape,
+                amount_x,
+                amount_y,
+            )?;
+            print_transaction(&[instruction], &signer, &[])?;
+        }
+        Command::RemoveLiquidity {
+            position,
+            bps,
+            transaction,
+        } => {
+            let signer = get_transaction_signer(&config_override, &transaction).await?;
+            let provider = get_provider(&config_override, transaction.snapshot.as_deref())?;
+            let (position_state, amm) = load_position(provider.as_ref(), &position).await?;
+
+            let instructions =
+                get_remove_liquidity_instructions(&amm, &position_state, signer.user, bps)?;
+            print_transaction(&instructions, &signer, &[])?;
+        }
+        Command::ClosePosition {
+            position,
+            transaction,
+        } => {
+            let signer = get_transaction_signer(&config_override, &transaction).await?;
+            let provider = get_provider(&config_override, transaction.snapshot.as_deref())?;
+            let (position_state, amm) = load_position(provider.as_ref(), &position).await?;
+
+            let instructions = get_close_position_instructions(&amm, &position_state, signer.user)?;
+            print_transaction(&instructions, &signer, &[])?;
+        }
+        Command::ClaimRewards {
+            position,
+            transaction,
+        } => {
+            let signer = get_transaction_signer(&config_override, &transaction).await?;
+            let provider = get_provider(&config_override, transaction.snapshot.as_deref())?;
+            let (position_state, amm) = load_position(provider.as_ref(), &position).await?;
+
+            let instructions = get_claim_rewards_instructions(
+                provider.as_ref(),
+                &amm,
+                &position_state,
+                signer.user,
+            )
+            .await?;
+            print_transaction(&instructions, &signer, &[])?;
+        }
     }
+
+    Ok(())
 }
@@ -0,0 +1,279 @@
+use anyhow::{anyhow, Result};
+use jupiter_amm_interface::{Amm, QuoteParams, SwapMode, SwapParams};
+use saros_sdk::{
+    math::swap_manager::{get_swap_steps, SwapStep},
+    state::bin_array::BinArrayPair,
+    utils::helper::is_swap_for_y,
+};
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+use std::sync::atomic::Ordering;
+
+use crate::{route::get_minimum_amount_out, SarosDlmm};
+
+/// Part of a split order routed through a single pool
+#[derive(Clone, Debug)]
+pub struct PoolAllocation {
+    pub pool: Pubkey,
+    pub in_amount: u64,
+    pub out_amount: u64,
+    pub fee_amount: u64,
+}
+
+/// Exact in order spread over several pools trading the same mints
+#[derive(Clone, Debug)]
+pub struct SplitQuote {
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub in_amount: u64,
+    pub out_amount: u64,
+    pub allocations: Vec<PoolAllocation>,
+}
+
+/// Bins `pool` would cross to swap up to `amount` of `input_mint`
+pub fn get_pool_swap_steps(
+    pool: &SarosDlmm,
+    input_mint: Pubkey,
+    amount: u64,
+) -> Result<Vec<SwapStep>> {
+    let mut pair = pool.pair.clone();
+    let block_timestamp = u64::try_from(pool.timestamp.load(Ordering::Relaxed))?;
+
+    let bin_for_swap = pool.compute_bin_array_swap()?;
+    pool.check_slot_spread(&bin_for_swap)?;
+    let bin_array = BinArrayPair::merge(bin_for_swap.bin_arrays[0], bin_for_swap.bin_arrays[1])?;
+
+    get_swap_steps(
+        &mut pair,
+        bin_array,
+        amount,
+        is_swap_for_y(input_mint, pool.pair.token_mint_x),
+        block_timestamp,
+    )
+}
+
+// Whether `a` gives more output per unit of input than `b`
+fn has_better_rate(a: &SwapStep, b: &SwapStep) -> bool {
+    u128::from(a.amount_out) * u128::from(b.amount_in)
+        > u128::from(b.amount_out) * u128::from(a.amount_in)
+}
+
+/// Split `amount` of `input_mint` across `pools` to maximise the total output.
+///
+/// Each pool is broken down into the bins a swap would cross, then the input is handed out bin by
+/// bin to whichever pool currently offers the best marginal rate. Rates only get worse as a swap
+/// moves away from the active bin, so this greedy fill is optimal up to the granularity of a bin.
+/// Pools that can not be quoted are ignored.
+pub fn split_exact_in(
+    pools: &[&SarosDlmm],
+    input_mint: Pubkey,
+    output_mint: Pubkey,
+    amount: u64,
+) -> Result<SplitQuote> {
+    let mut candidates: Vec<(&SarosDlmm, Vec<SwapStep>)> = Vec::new();
+    for pool in pools.iter() {
+        let mints = pool.get_reserve_mints();
+        if !mints.contains(&input_mint) || !mints.contains(&output_mint) {
+            return Err(anyhow!(
+                "Pool {} does not trade {} against {}",
+                pool.key(),
+                input_mint,
+                output_mint
+            ));
+        }
+
+        if let Ok(steps) = get_pool_swap_steps(pool, input_mint, amount) {
+            candidates.push((pool, steps));
+        }
+    }
+
+    // (index of the current step, amount left in that step) for each candidate
+    let mut cursors: Vec<(usize, u64)> = candidates
+        .iter()
+        .map(|(_, steps)| (0, steps.first().map_or(0, |step| step.amount_in)))
+        .collect();
+    let mut amounts_in = vec![0u64; candidates.len()];
+    let mut remaining = amount;
+
+    while remaining > 0 {
+        let best = candidates
+            .iter()
+            .enumerate()
+            .filter(|(i, (_, steps))| cursors[*i].0 < steps.len())
+            .map(|(i, (_, steps))| (i, &steps[cursors[i].0]))
+            .reduce(|best, candidate| {
+                if has_better_rate(candidate.1, best.1) {
+                    candidate
+                } else {
+                    best
+                }
+            });
+
+        let Some((i, _)) = best else {
+            return Err(anyhow!(
+                "Not enough liquidity to swap {} {}, {} left",
+                amount,
+                input_mint,
+                remaining
+            ));
+        };
+
+        let (step_index, step_left) = cursors[i];
+        let take = step_left.min(remaining);
+        amounts_in[i] += take;
+        remaining -= take;
+
+        cursors[i] = if take == step_left {
+            let steps = &candidates[i].1;
+            let next = step_index + 1;
+            (next, steps.get(next).map_or(0, |step| step.amount_in))
+        } else {
+            (step_index, step_left - take)
+        };
+    }
+
+    let mut allocations = Vec::new();
+    for ((pool, _), in_amount) in candidates.iter().zip(amounts_in) {
+        if in_amount == 0 {
+            continue;
+        }
+
+        let quote = pool.quote(&QuoteParams {
+            amount: in_amount,
+            input_mint,
+            output_mint,
+            swap_mode: SwapMode::ExactIn,
+        })?;
+
+        allocations.push(PoolAllocation {
+            pool: pool.key(),
+            in_amount,
+            out_amount: quote.out_amount,
+            fee_amount: quote.fee_amount,
+        });
+    }
+
+    Ok(SplitQuote {
+        input_mint,
+        output_mint,
+        in_amount: amount,
+        out_amount: allocations
+            .iter()
+            .map(|allocation| allocation.out_amount)
+            .sum(),
+        allocations,
+    })
+}
+
+/// One exact in swap per allocation of `split`
+pub fn get_split_instructions(
+    pools: &[&SarosDlmm],
+    split: &SplitQuote,
+    user: Pubkey,
+    source_token_account: Pubkey,
+    destination_token_account: Pubkey,
+    slippage_bps: u16,
+) -> Result<Vec<Instruction>> {
+    split
+        .allocations
+        .iter()
+        .map(|allocation| {
+            let pool = pools
+                .iter()
+                .find(|pool| pool.key() == allocation.pool)
+                .ok_or_else(|| anyhow!("Unknown pool {}", allocation.pool))?;
+
+            pool.get_swap_instruction(
+                &SwapParams {
+                    swap_mode: SwapMode::ExactIn,
+                    in_amount: allocation.in_amount,
+                    out_amount: allocation.out_amount,
+                    source_mint: split.input_mint,
+                    destination_mint: split.output_mint,
+                    source_token_account,
+                    destination_token_account,
+                    token_transfer_authority: user,
+                    quote_mint_to_referrer: None,
+                    jupiter_program_id: &pool.program_id,
+                    missing_dynamic_accounts_as_default: false,
+                },
+                get_minimum_amount_out(allocation.out_amount, slippage_bps)?,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amms::test_pool::build_test_pool;
+
+    #[test]
+    fn test_split_beats_single_pool() {
+        let mint_x = Pubkey::new_unique();
+        let mint_y = Pubkey::new_unique();
+
+        let deep = build_test_pool(Pubkey::new_unique(), mint_x, mint_y, 1, 10_000, 1_000_000);
+        let shallow = build_test_pool(Pubkey::new_unique(), mint_x, mint_y, 5, 10_000, 300_000);
+        let pools = [&deep, &shallow];
+
+        let amount = 20_000_000;
+        let split = split_exact_in(&pools, mint_x, mint_y, amount).unwrap();
+
+        assert_eq!(split.allocations.len(), 2);
+        assert_eq!(
+            split
+                .allocations
+                .iter()
+                .map(|allocation| allocation.in_amount)
+                .sum::<u64>(),
+            amount
+        );
+
+        for pool in pools {
+            let single = pool
+                .quote(&QuoteParams {
+                    amount,
+                    input_mint: mint_x,
+                    output_mint: mint_y,
+                    swap_mode: SwapMode::ExactIn,
+                })
+                .map(|quote| quote.out_amount)
+                .unwrap_or(0);
+            assert!(split.out_amount >= single);
+        }
+
+        let instructions = get_split_instructions(
+            &pools,
+            &split,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            50,
+        )
+        .unwrap();
+        assert_eq!(instructions.len(), 2);
+    }
+
+    #[test]
+    fn test_split_prefers_cheaper_pool() {
+        let mint_x = Pubkey::new_unique();
+        let mint_y = Pubkey::new_unique();
+
+        let cheap = build_test_pool(Pubkey::new_unique(), mint_x, mint_y, 1, 1_000, 1_000_000);
+        let expensive = build_test_pool(Pubkey::new_unique(), mint_x, mint_y, 1, 50_000, 1_000_000);
+
+        let split = split_exact_in(&[&cheap, &expensive], mint_y, mint_x, 100_000).unwrap();
+
+        assert_eq!(split.allocations.len(), 1);
+        assert_eq!(split.allocations[0].pool, cheap.key());
+    }
+
+    #[test]
+    fn test_split_not_enough_liquidity() {
+        let mint_x = Pubkey::new_unique();
+        let mint_y = Pubkey::new_unique();
+        let pool = build_test_pool(Pubkey::new_unique(), mint_x, mint_y, 1, 10_000, 1_000);
+
+        assert!(split_exact_in(&[&pool], mint_x, mint_y, u64::MAX / 4).is_err());
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use jupiter_amm_interface::KeyedAccount;
+use saros_sdk::{
+    state::pair::{Pair, PAIR_DISCRIMINATOR},
+    utils::helper::find_pair,
+};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::{account::Account, program_pack::Pack, pubkey::Pubkey};
+
+use crate::account_provider::AccountProvider;
+
+/// Filters selecting every `Pair` account of the liquidity book program
+pub fn get_pair_filters() -> Vec<RpcFilterType> {
+    vec![
+        RpcFilterType::DataSize(Pair::LEN as u64),
+        RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, &PAIR_DISCRIMINATOR)),
+    ]
+}
+
+/// Index of the known pairs by mints and bin step
+#[derive(Clone, Default)]
+pub struct PairRegistry {
+    pairs: HashMap<Pubkey, (Account, Pair)>,
+    // (token_mint_x, token_mint_y, bin_step) -> pair keys
+    index: HashMap<(Pubkey, Pubkey, u8), Vec<Pubkey>>,
+}
+
+impl PairRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build a registry from program accounts, accounts that are not pairs are skipped
+    pub fn from_program_accounts<I>(accounts: I) -> Self
+    where
+        I: IntoIterator<Item = (Pubkey, Account)>,
+    {
+        let mut registry = Self::new();
+        for (key, account) in accounts {
+            registry.insert(key, account);
+        }
+        registry
+    }
+
+    /// Fetch every pair of the program
+    pub async fn fetch<P>(provider: &P) -> Result<Self>
+    where
+        P: AccountProvider + ?Sized,
+    {
+        let accounts = provider
+            .get_program_accounts(&liquidity_book::ID, get_pair_filters())
+            .await?;
+        Ok(Self::from_program_accounts(accounts))
+    }
+
+    /// Add or replace a pair, returns false when the account is not a pair
+    pub fn insert(&mut self, key: Pubkey, account: Account) -> bool {
+        let Some(pair) = decode_pair(&account) else {
+            return false;
+        };
+
+        if let Some((_, previous)) = self.pairs.get(&key) {
+            let previous_index = (
+                previous.token_mint_x,
+                previous.token_mint_y,
+                previous.bin_step,
+            );
+            if let Some(keys) = self.index.get_mut(&previous_index) {
+                keys.retain(|previous_key| *previous_key != key);
+            }
+        }
+
+        let keys = self
+            .index
+            .entry((pair.token_mint_x, pair.token_mint_y, pair.bin_step))
+            .or_default();
+        keys.push(key);
+        keys.sort();
+
+        self.pairs.insert(key, (account, pair));
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    pub fn get(&self, key: &Pubkey) -> Option<&Pair> {
+        self.pairs.get(key).map(|(_, pair)| pair)
+    }
+
+    /// Keyed account ready to be handed to `amm_factory`
+    pub fn get_keyed_account(&self, key: &Pubkey) -> Option<KeyedAccount> {
+        self.pairs.get(key).map(|(account, _)| KeyedAccount {
+            key: *key,
+            account: account.clone(),
+            params: None,
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Pubkey, &Pair)> {
+        self.pairs.iter().map(|(key, (_, pair))| (key, pair))
+    }
+
+    /// Pairs trading `mint_a` against `mint_b` with `bin_step`, in either token order
+    pub fn get_pairs_by_bin_step(
+        &self,
+        mint_a: Pubkey,
+        mint_b: Pubkey,
+        bin_step: u8,
+    ) -> Vec<Pubkey> {
+        let mut keys = Vec::new();
+        for index in [(mint_a, mint_b, bin_step), (mint_b, mint_a, bin_step)] {
+            if let Some(index_keys) = self.index.get(&index) {
+                keys.extend(index_keys.iter().copied());
+            }
+            if mint_a == mint_b {
+                break;
+            }
+        }
+        keys
+    }
+
+    /// Pairs trading `mint_a` against `mint_b` for every bin step, in either token order
+    pub fn get_pairs(&self, mint_a: Pubkey, mint_b: Pubkey) -> Vec<Pubkey> {
+        let mut keys: Vec<(u8, Pubkey)> = self
+            .index
+            .iter()
+            .filter(|((mint_x, mint_y, _), _)| {
+                (*mint_x == mint_a && *mint_y == mint_b) || (*mint_x == mint_b && *mint_y == mint_a)
+            })
+            .flat_map(|((_, _, bin_step), keys)| keys.iter().map(|key| (*bin_step, *key)))
+            .collect();
+        keys.sort();
+        keys.into_iter().map(|(_, key)| key).collect()
+    }
+
+    /// Pairs where `mint` is one of the two tokens
+    pub fn get_pairs_with_mint(&self, mint: Pubkey) -> Vec<Pubkey> {
+        let mut keys: Vec<Pubkey> = self
+            .pairs
+            .iter()
+            .filter(|(_, (_, pair))| pair.token_mint_x == mint || pair.token_mint_y == mint)
+            .map(|(key, _)| *key)
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    /// Whether `key` is the address derived from the pair config, mints and bin step
+    pub fn is_canonical_pair(&self, key: &Pubkey) -> bool {
+        self.get(key).is_some_and(|pair| {
+            find_pair(
+                pair.liquidity_book_config,
+                pair.token_mint_x,
+                pair.token_mint_y,
+                pair.bin_step,
+            ) == *key
+        })
+    }
+}
+
+fn decode_pair(account: &Account) -> Option<Pair> {
+    if account.owner != liquidity_book::ID
+        || account.data.len() != Pair::LEN
+        || account.data[..8] != PAIR_DISCRIMINATOR
+    {
+        return None;
+    }
+
+    Pair::unpack_from_slice(&account.data).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair_account(token_mint_x: Pubkey, token_mint_y: Pubkey, bin_step: u8) -> Account {
+        let mut pair = Pair::unpack_from_slice(&[0u8; Pair::LEN]).unwrap();
+        pair.token_mint_x = token_mint_x;
+        pair.token_mint_y = token_mint_y;
+        pair.bin_step = bin_step;
+        pair.bin_step_seed = [bin_step];
+
+        let mut data = vec![0u8; Pair::LEN];
+        pair.pack_into_slice(&mut data);
+
+        Account {
+            lamports: 1,
+            data,
+            owner: liquidity_book::ID,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    #[test]
+    fn test_registry_lookups() {
+        let sol = Pubkey::new_unique();
+        let usdc = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+
+        let sol_usdc_1 = Pubkey::new_unique();
+        let sol_usdc_20 = Pubkey::new_unique();
+        let usdc_other = Pubkey::new_unique();
+
+        let mut not_a_pair = pair_account(sol, usdc, 1);
+        not_a_pair.data[0] = 0;
+        let mut foreign_pair = pair_account(sol, usdc, 1);
+        foreign_pair.owner = Pubkey::new_unique();
+
+        let registry = PairRegistry::from_program_accounts(vec![
+            (sol_usdc_1, pair_account(sol, usdc, 1)),
+            (sol_usdc_20, pair_account(sol, usdc, 20)),
+            (usdc_other, pair_account(usdc, other, 1)),
+            (Pubkey::new_unique(), not_a_pair),
+            (Pubkey::new_unique(), foreign_pair),
+        ]);
+
+        assert_eq!(registry.len(), 3);
+        assert_eq!(
+            registry.get_pairs_by_bin_step(sol, usdc, 1),
+            vec![sol_usdc_1]
+        );
+        assert_eq!(
+            registry.get_pairs_by_bin_step(usdc, sol, 20),
+            vec![sol_usdc_20]
+        );
+        assert!(registry.get_pairs_by_bin_step(sol, usdc, 5).is_empty());
+        assert_eq!(registry.get_pairs(usdc, sol), vec![sol_usdc_1, sol_usdc_20]);
+
+        let mut with_usdc = vec![sol_usdc_1, sol_usdc_20, usdc_other];
+        with_usdc.sort();
+        assert_eq!(registry.get_pairs_with_mint(usdc), with_usdc);
+
+        let keyed_account = registry.get_keyed_account(&usdc_other).unwrap();
+        assert_eq!(keyed_account.key, usdc_other);
+        assert_eq!(registry.get(&usdc_other).unwrap().token_mint_y, other);
+    }
+
+    #[test]
+    fn test_registry_reinsert_updates_index() {
+        let mint_x = Pubkey::new_unique();
+        let mint_y = Pubkey::new_unique();
+        let key = Pubkey::new_unique();
+
+        let mut registry = PairRegistry::new();
+        assert!(registry.insert(key, pair_account(mint_x, mint_y, 1)));
+        assert!(registry.insert(key, pair_account(mint_x, mint_y, 1)));
+
+        assert_eq!(registry.len(), 1);
+        assert_eq!(registry.get_pairs(mint_x, mint_y), vec![key]);
+    }
+
+    #[test]
+    fn test_canonical_pair() {
+        let mint_x = Pubkey::new_unique();
+        let mint_y = Pubkey::new_unique();
+        let canonical = find_pair(Pubkey::default(), mint_x, mint_y, 1);
+        let spoofed = Pubkey::new_unique();
+
+        let registry = PairRegistry::from_program_accounts(vec![
+            (canonical, pair_account(mint_x, mint_y, 1)),
+            (spoofed, pair_account(mint_x, mint_y, 1)),
+        ]);
+
+        assert!(registry.is_canonical_pair(&canonical));
+        assert!(!registry.is_canonical_pair(&spoofed));
+        assert_ne!(canonical, find_pair(Pubkey::default(), mint_x, mint_y, 2));
+        assert_ne!(canonical, find_pair(Pubkey::default(), mint_y, mint_x, 1));
+    }
+}
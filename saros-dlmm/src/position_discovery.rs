@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use saros_sdk::{state::position::Position, utils::helper::find_position};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use spl_token_2022::{extension::StateWithExtensions, state::Account as TokenAccount};
+
+use crate::{account_provider::AccountProvider, inspect::decode_state_account};
+
+// Offset of the owner inside a token account
+const TOKEN_ACCOUNT_OWNER_OFFSET: usize = 32;
+
+/// Token-2022 token accounts owned by `owner`
+pub async fn get_token_2022_accounts_by_owner<P>(
+    provider: &P,
+    owner: &Pubkey,
+) -> Result<Vec<(Pubkey, Account)>>
+where
+    P: AccountProvider + ?Sized,
+{
+    provider
+        .get_program_accounts(
+            &spl_token_2022::ID,
+            vec![
+                RpcFilterType::TokenAccountState,
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                    TOKEN_ACCOUNT_OWNER_OFFSET,
+                    owner.as_ref(),
+                )),
+            ],
+        )
+        .await
+}
+
+/// A position held by a wallet
+#[derive(Clone, Debug)]
+pub struct OwnedPosition {
+    pub position_key: Pubkey,
+    pub position_token_account: Pubkey,
+    pub position: Position,
+}
+
+/// Positions held by `owner`, grouped by pair and sorted by lower bin id
+pub async fn find_positions_by_owner<P>(
+    provider: &P,
+    owner: &Pubkey,
+) -> Result<HashMap<Pubkey, Vec<OwnedPosition>>>
+where
+    P: AccountProvider + ?Sized,
+{
+    let token_accounts = get_token_2022_accounts_by_owner(provider, owner).await?;
+
+    // Position NFTs are non fungible, anything else can not be a position
+    let candidates: Vec<(Pubkey, Pubkey)> = token_accounts
+        .iter()
+        .filter_map(|(key, account)| {
+            let token_account = StateWithExtensions::<TokenAccount>::unpack(&account.data).ok()?;
+            (token_account.base.owner == *owner && token_account.base.amount == 1)
+                .then_some((*key, token_account.base.mint))
+        })
+        .collect();
+
+    let position_keys: Vec<Pubkey> = candidates
+        .iter()
+        .map(|(_, mint)| find_position(*mint))
+        .collect();
+    let position_accounts = provider.get_multiple_accounts(&position_keys).await?;
+
+    let mut positions: HashMap<Pubkey, Vec<OwnedPosition>> = HashMap::new();
+    for (((position_token_account, mint), position_key), account) in candidates
+        .into_iter()
+        .zip(position_keys)
+        .zip(position_accounts)
+    {
+        let Some(position) =
+            account.and_then(|account| decode_position(&position_key, &account, &mint))
+        else {
+            continue;
+        };
+
+        positions
+            .entry(position.pair)
+            .or_default()
+            .push(OwnedPosition {
+                position_key,
+                position_token_account,
+                position,
+            });
+    }
+
+    for pair_positions in positions.values_mut() {
+        pair_positions.sort_by_key(|owned| owned.position.lower_bin_id);
+    }
+
+    Ok(positions)
+}
+
+fn decode_position(key: &Pubkey, account: &Account, mint: &Pubkey) -> Option<Position> {
+    let position: Position = decode_state_account(key, account).ok()?;
+    (position.position_mint == *mint).then_some(position)
+}
@@ -0,0 +1,202 @@
+use anyhow::{anyhow, Result};
+use jupiter_amm_interface::{Amm, QuoteParams, SwapMode};
+use saros_sdk::{
+    math::{
+        bin_math::get_price_from_id,
+        fees::{compute_transfer_amount_for_expected_output, compute_transfer_fee},
+        u64x64_math::SCALE_OFFSET,
+    },
+    utils::helper::is_swap_for_y,
+};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{order_split::get_pool_swap_steps, SarosDlmm};
+
+/// Liquidity taken from a bin by a quoted swap, amounts on the pool side of the transfer fees
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BinTrace {
+    pub bin_id: u32,
+    /// Price of token X in token Y atoms
+    pub price: f64,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee_amount: u64,
+}
+
+/// Quote of a pool with the bins it crosses and what the swap costs beyond the spot price
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuoteReport {
+    pub swap_mode: SwapMode,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub in_amount: u64,
+    pub out_amount: u64,
+    pub fee_amount: u64,
+    pub fee_mint: Pubkey,
+    /// Token 2022 transfer fee withheld from the user's input
+    pub transfer_fee_in: u64,
+    /// Token 2022 transfer fee withheld from the pool's output
+    pub transfer_fee_out: u64,
+    pub bins: Vec<BinTrace>,
+    /// Output atoms per input atom at the active bin
+    pub spot_price: f64,
+    /// Output atoms per input atom received by the pool, swap fees excluded
+    pub execution_price: f64,
+    /// Relative shortfall of the execution price against the spot price
+    pub price_impact: f64,
+}
+
+/// Price of token X in token Y atoms at `bin_id`
+pub fn get_bin_price(bin_step: u8, bin_id: u32) -> Result<f64> {
+    let price = get_price_from_id(bin_step, bin_id)
+        .ok_or_else(|| anyhow!("Price of bin {} is out of range", bin_id))?;
+
+    Ok(price as f64 / 2f64.powi(SCALE_OFFSET.into()))
+}
+
+/// Quote `quote_params` on `amm` and trace the bins the swap crosses
+pub fn get_quote_report(amm: &SarosDlmm, quote_params: &QuoteParams) -> Result<QuoteReport> {
+    let quote = amm.quote(quote_params)?;
+
+    let swap_for_y = is_swap_for_y(quote_params.input_mint, amm.pair.token_mint_x);
+    let (epoch_transfer_fee_in, epoch_transfer_fee_out) = if swap_for_y {
+        (
+            amm.token_transfer_fee.epoch_transfer_fee_x,
+            amm.token_transfer_fee.epoch_transfer_fee_y,
+        )
+    } else {
+        (
+            amm.token_transfer_fee.epoch_transfer_fee_y,
+            amm.token_transfer_fee.epoch_transfer_fee_x,
+        )
+    };
+
+    // The quoted input always reaches the pool minus its transfer fee, in both modes
+    let (pool_amount_in, transfer_fee_in) =
+        compute_transfer_fee(epoch_transfer_fee_in, quote.in_amount)?;
+
+    let bins = get_pool_swap_steps(amm, quote_params.input_mint, pool_amount_in)?
+        .into_iter()
+        .map(|step| {
+            Ok(BinTrace {
+                bin_id: step.bin_id,
+                price: get_bin_price(amm.pair.bin_step, step.bin_id)?,
+                amount_in: step.amount_in,
+                amount_out: step.amount_out,
+                fee_amount: step.fee_amount,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let transfer_fee_out = match quote_params.swap_mode {
+        SwapMode::ExactIn => {
+            let pool_amount_out: u64 = bins.iter().map(|bin| bin.amount_out).sum();
+            compute_transfer_fee(epoch_transfer_fee_out, pool_amount_out)?.1
+        }
+        SwapMode::ExactOut => {
+            compute_transfer_amount_for_expected_output(epoch_transfer_fee_out, quote.out_amount)?.1
+        }
+    };
+
+    let active_price = get_bin_price(amm.pair.bin_step, amm.pair.active_id)?;
+    let spot_price = if swap_for_y {
+        active_price
+    } else {
+        1f64 / active_price
+    };
+
+    let amount_in: u64 = bins.iter().map(|bin| bin.amount_in - bin.fee_amount).sum();
+    let amount_out: u64 = bins.iter().map(|bin| bin.amount_out).sum();
+    let execution_price = if amount_in == 0 {
+        spot_price
+    } else {
+        amount_out as f64 / amount_in as f64
+    };
+
+    Ok(QuoteReport {
+        swap_mode: quote_params.swap_mode,
+        input_mint: quote_params.input_mint,
+        output_mint: quote_params.output_mint,
+        in_amount: quote.in_amount,
+        out_amount: quote.out_amount,
+        fee_amount: quote.fee_amount,
+        fee_mint: quote.fee_mint,
+        transfer_fee_in,
+        transfer_fee_out,
+        bins,
+        spot_price,
+        execution_price,
+        price_impact: 1f64 - execution_price / spot_price,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amms::test_pool::build_test_pool;
+
+    #[test]
+    fn test_quote_report() {
+        let mint_x = Pubkey::new_unique();
+        let mint_y = Pubkey::new_unique();
+        let pool = build_test_pool(Pubkey::new_unique(), mint_x, mint_y, 10, 10_000, 1_000_000);
+
+        let small = get_quote_report(
+            &pool,
+            &QuoteParams {
+                amount: 1_000,
+                input_mint: mint_x,
+                output_mint: mint_y,
+                swap_mode: SwapMode::ExactIn,
+            },
+        )
+        .unwrap();
+        assert_eq!(small.bins.len(), 1);
+        assert_eq!(small.bins[0].bin_id, pool.pair.active_id);
+        assert_eq!(small.bins[0].amount_in, small.in_amount);
+        assert_eq!(small.bins[0].amount_out, small.out_amount);
+        assert_eq!(small.bins[0].fee_amount, small.fee_amount);
+        assert_eq!((small.transfer_fee_in, small.transfer_fee_out), (0, 0));
+
+        let large = get_quote_report(
+            &pool,
+            &QuoteParams {
+                amount: 3_000_000,
+                input_mint: mint_x,
+                output_mint: mint_y,
+                swap_mode: SwapMode::ExactIn,
+            },
+        )
+        .unwrap();
+        assert!(large.bins.len() > 1);
+        assert!(large
+            .bins
+            .windows(2)
+            .all(|bins| bins[1].bin_id + 1 == bins[0].bin_id));
+        assert_eq!(
+            large.bins.iter().map(|bin| bin.amount_in).sum::<u64>(),
+            3_000_000
+        );
+        assert_eq!(
+            large.bins.iter().map(|bin| bin.amount_out).sum::<u64>(),
+            large.out_amount
+        );
+        assert!(large.price_impact > small.price_impact);
+
+        let exact_out = get_quote_report(
+            &pool,
+            &QuoteParams {
+                amount: large.out_amount,
+                input_mint: mint_x,
+                output_mint: mint_y,
+                swap_mode: SwapMode::ExactOut,
+            },
+        )
+        .unwrap();
+        assert_eq!(exact_out.out_amount, large.out_amount);
+        assert_eq!(
+            exact_out.bins.iter().map(|bin| bin.amount_in).sum::<u64>(),
+            exact_out.in_amount
+        );
+    }
+}
@@ -0,0 +1,441 @@
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Result};
+use saros_sdk::{
+    constants::MAX_BIN_PER_POSITION,
+    instruction::{DecreasePositionParams, LiquidityShape},
+    math::bin_math::get_id_from_price,
+    state::{bin_array::BinArrayPair, position::Position},
+};
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+
+use crate::{
+    amms::position_manager::{get_modifier_position_params, SarosPositionManagement},
+    wide_position::{get_close_positions_instructions, WidePosition},
+    SarosDlmm,
+};
+
+/// Limit order made of single-sided bins: token X placed above the active bin is sold for Y as the
+/// price rises through it, token Y placed below is sold for X as the price falls
+pub struct RangeOrder {
+    pub sell_x: bool,
+    pub amount: u64,
+    /// Backing position, always a single segment
+    pub position: WidePosition,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RangeOrderStatus {
+    /// The active bin did not reach the range yet
+    Open,
+    /// The active bin is inside the range
+    PartiallyFilled,
+    /// The active bin crossed the whole range, only the bought token is left
+    Filled,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RangeOrderFill {
+    pub status: RangeOrderStatus,
+    /// Token sold still in the bins
+    pub remaining: u64,
+    /// Token bought so far
+    pub proceeds: u64,
+    /// Bins the active bin crossed, they hold only the bought token
+    pub filled_bins: u32,
+}
+
+impl RangeOrder {
+    /// Sell `amount` of X between the Q64.64 prices `price_lower` and `price_upper` of the pair, or
+    /// `amount` of Y between them when `sell_x` is false. The range is rounded inwards to whole
+    /// bins and must be on the side of the active bin where the order does not fill immediately.
+    pub fn new(
+        amm: &SarosDlmm,
+        sell_x: bool,
+        price_lower: u128,
+        price_upper: u128,
+        amount: u64,
+    ) -> Result<Self> {
+        let bin_step = amm.pair.bin_step;
+        let lower_bin_id = get_id_from_price(bin_step, price_lower, true)
+            .ok_or_else(|| anyhow!("Price out of range"))?;
+        let upper_bin_id = get_id_from_price(bin_step, price_upper, false)
+            .ok_or_else(|| anyhow!("Price out of range"))?;
+
+        Self::from_bin_ids(amm, sell_x, lower_bin_id, upper_bin_id, amount)
+    }
+
+    /// Sell at a single bin, the one at or above `price` for X and at or below it for Y
+    pub fn at_price(amm: &SarosDlmm, sell_x: bool, price: u128, amount: u64) -> Result<Self> {
+        let bin_id = get_id_from_price(amm.pair.bin_step, price, sell_x)
+            .ok_or_else(|| anyhow!("Price out of range"))?;
+
+        Self::from_bin_ids(amm, sell_x, bin_id, bin_id, amount)
+    }
+
+    pub fn from_bin_ids(
+        amm: &SarosDlmm,
+        sell_x: bool,
+        lower_bin_id: u32,
+        upper_bin_id: u32,
+        amount: u64,
+    ) -> Result<Self> {
+        let active_id = amm.pair.active_id;
+
+        if lower_bin_id > upper_bin_id {
+            return Err(anyhow!("No bin between the prices"));
+        }
+        if upper_bin_id - lower_bin_id >= MAX_BIN_PER_POSITION as u32 {
+            return Err(anyhow!(
+                "Range order wider than {} bins",
+                MAX_BIN_PER_POSITION
+            ));
+        }
+        if (sell_x && lower_bin_id <= active_id) || (!sell_x && upper_bin_id >= active_id) {
+            return Err(anyhow!(
+                "Range order would fill at the active bin {}",
+                active_id
+            ));
+        }
+
+        Ok(Self {
+            sell_x,
+            amount,
+            position: WidePosition::new(lower_bin_id, upper_bin_id)?,
+        })
+    }
+
+    pub fn lower_bin_id(&self) -> u32 {
+        self.position.lower_bin_id()
+    }
+
+    pub fn upper_bin_id(&self) -> u32 {
+        self.position.upper_bin_id()
+    }
+
+    pub fn position_mint(&self) -> Pubkey {
+        self.position.position_mints()[0]
+    }
+
+    /// Create the position and deposit the order amount, spread evenly over its bins. The
+    /// position mint keypair must sign.
+    pub fn get_place_instructions(
+        &self,
+        amm: &SarosDlmm,
+        user: Pubkey,
+        user_vault_x: Pubkey,
+        user_vault_y: Pubkey,
+        existing_accounts: &HashSet<Pubkey>,
+    ) -> Result<Vec<Instruction>> {
+        let (amount_x, amount_y) = if self.sell_x {
+            (self.amount, 0)
+        } else {
+            (0, self.amount)
+        };
+
+        let mut instructions =
+            self.position
+                .get_create_instructions(amm, user, existing_accounts)?;
+        instructions.extend(self.position.get_increase_instructions(
+            amm,
+            user,
+            user_vault_x,
+            user_vault_y,
+            amount_x,
+            amount_y,
+            LiquidityShape::Spot,
+        )?);
+
+        Ok(instructions)
+    }
+}
+
+/// Fill state of a range order position at `active_id`, `bin_array` must hold its bins
+pub fn get_range_order_fill(
+    position: &Position,
+    bin_array: &BinArrayPair,
+    active_id: u32,
+    sell_x: bool,
+) -> Result<RangeOrderFill> {
+    let width = position.get_liquidity_shares()?.len() as u32;
+    let filled_bins = (position.lower_bin_id..=position.upper_bin_id)
+        .filter(|bin_id| is_crossed(*bin_id, active_id, sell_x))
+        .count() as u32;
+
+    let (amount_x, amount_y) = position.get_amounts(bin_array)?;
+    let (remaining, proceeds) = if sell_x {
+        (amount_x, amount_y)
+    } else {
+        (amount_y, amount_x)
+    };
+
+    let status = if filled_bins == width {
+        RangeOrderStatus::Filled
+    } else if filled_bins > 0
+        || (position.lower_bin_id..=position.upper_bin_id).contains(&active_id)
+    {
+        RangeOrderStatus::PartiallyFilled
+    } else {
+        RangeOrderStatus::Open
+    };
+
+    Ok(RangeOrderFill {
+        status,
+        remaining,
+        proceeds,
+        filled_bins,
+    })
+}
+
+/// Withdraw the bins the active bin crossed, closing the position once all of them are. Bins still
+/// holding the token sold are left untouched.
+pub fn get_range_order_withdraw_instructions(
+    amm: &SarosDlmm,
+    position: &Position,
+    active_id: u32,
+    sell_x: bool,
+    user: Pubkey,
+    user_vault_x: Pubkey,
+    user_vault_y: Pubkey,
+) -> Result<Vec<Instruction>> {
+    let liquidity_shares = position.get_liquidity_shares()?;
+    let bin_ids = position.lower_bin_id..=position.upper_bin_id;
+
+    if bin_ids
+        .clone()
+        .all(|bin_id| is_crossed(bin_id, active_id, sell_x))
+    {
+        return get_close_positions_instructions(
+            amm,
+            std::slice::from_ref(position),
+            user,
+            user_vault_x,
+            user_vault_y,
+        );
+    }
+
+    let shares: Vec<u128> = bin_ids
+        .zip(liquidity_shares.iter().copied())
+        .map(|(bin_id, shares)| {
+            if is_crossed(bin_id, active_id, sell_x) {
+                shares
+            } else {
+                0
+            }
+        })
+        .collect();
+    if shares.iter().all(|shares| *shares == 0) {
+        return Ok(Vec::new());
+    }
+
+    let modifier_position_params = get_modifier_position_params(
+        amm,
+        user,
+        position.position_mint,
+        position.lower_bin_id,
+        user_vault_x,
+        user_vault_y,
+    );
+    Ok(vec![amm.get_decrease_position_instruction(
+        modifier_position_params,
+        DecreasePositionParams { shares },
+    )?])
+}
+
+fn is_crossed(bin_id: u32, active_id: u32, sell_x: bool) -> bool {
+    if sell_x {
+        bin_id < active_id
+    } else {
+        bin_id > active_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amms::test_pool::build_test_pool;
+    use saros_sdk::{
+        instruction::{
+            build_close_position_instruction_data, build_decrease_position_instruction_data,
+        },
+        math::bin_math::get_price_from_id,
+    };
+
+    fn setup() -> SarosDlmm {
+        build_test_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            10,
+            10_000,
+            1_000_000,
+        )
+    }
+
+    fn price(pool: &SarosDlmm, bin_id: u32) -> u128 {
+        get_price_from_id(pool.pair.bin_step, bin_id).unwrap()
+    }
+
+    #[test]
+    fn test_range_order_bins() {
+        let pool = setup();
+        let active_id = pool.pair.active_id;
+
+        let order = RangeOrder::new(
+            &pool,
+            true,
+            price(&pool, active_id + 3) - 1,
+            price(&pool, active_id + 6) + 1,
+            1_000,
+        )
+        .unwrap();
+        assert_eq!(
+            (order.lower_bin_id(), order.upper_bin_id()),
+            (active_id + 3, active_id + 6)
+        );
+
+        let order =
+            RangeOrder::at_price(&pool, true, price(&pool, active_id + 2) + 1, 1_000).unwrap();
+        assert_eq!(
+            (order.lower_bin_id(), order.upper_bin_id()),
+            (active_id + 3, active_id + 3)
+        );
+
+        let order =
+            RangeOrder::at_price(&pool, false, price(&pool, active_id - 2) + 1, 1_000).unwrap();
+        assert_eq!(
+            (order.lower_bin_id(), order.upper_bin_id()),
+            (active_id - 2, active_id - 2)
+        );
+
+        assert!(RangeOrder::at_price(&pool, true, price(&pool, active_id), 1_000).is_err());
+        assert!(RangeOrder::at_price(&pool, false, price(&pool, active_id), 1_000).is_err());
+        assert!(RangeOrder::new(
+            &pool,
+            true,
+            price(&pool, active_id + 1),
+            price(&pool, active_id + 100),
+            1_000
+        )
+        .is_err());
+
+        let instructions = order
+            .get_place_instructions(
+                &pool,
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                &HashSet::from(pool.active_bin_array_key),
+            )
+            .unwrap();
+        // create and increase
+        assert_eq!(instructions.len(), 2);
+    }
+
+    #[test]
+    fn test_range_order_fill() {
+        let mut pool = setup();
+        let active_id = pool.pair.active_id;
+
+        // Sell X over three bins above the active bin, owning half of each
+        let mut position = Position::default();
+        position.pair = pool.key;
+        position.position_mint = Pubkey::new_unique();
+        position.lower_bin_id = active_id + 1;
+        position.upper_bin_id = active_id + 3;
+        for share in position.liquidity_shares[..3].iter_mut() {
+            *share = 500_000 << 64;
+        }
+
+        let bin_array = BinArrayPair::merge(pool.bin_array_middle, pool.bin_array_upper).unwrap();
+        let fill = get_range_order_fill(&position, &bin_array, active_id, true).unwrap();
+        assert_eq!(
+            fill,
+            RangeOrderFill {
+                status: RangeOrderStatus::Open,
+                remaining: 1_500_000,
+                proceeds: 0,
+                filled_bins: 0,
+            }
+        );
+        assert!(get_range_order_withdraw_instructions(
+            &pool,
+            &position,
+            active_id,
+            true,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        )
+        .unwrap()
+        .is_empty());
+
+        // The price rose to the last bin: the first two bins were converted to Y
+        let mut bin_array = bin_array;
+        for bin_id in active_id + 1..active_id + 3 {
+            let bin = bin_array.get_bin_mut(bin_id).unwrap();
+            bin.reserve_x = 0;
+            bin.reserve_y = 1_002_000;
+        }
+        pool.pair.active_id = active_id + 3;
+
+        let fill = get_range_order_fill(&position, &bin_array, pool.pair.active_id, true).unwrap();
+        assert_eq!(fill.status, RangeOrderStatus::PartiallyFilled);
+        assert_eq!(fill.filled_bins, 2);
+        assert_eq!(fill.remaining, 500_000);
+        assert_eq!(fill.proceeds, 1_002_000);
+
+        let instructions = get_range_order_withdraw_instructions(
+            &pool,
+            &position,
+            pool.pair.active_id,
+            true,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        )
+        .unwrap();
+        assert_eq!(instructions.len(), 1);
+        assert_eq!(
+            instructions[0].data,
+            build_decrease_position_instruction_data(DecreasePositionParams {
+                shares: vec![500_000 << 64, 500_000 << 64, 0],
+            })
+            .unwrap()
+        );
+
+        let fill = get_range_order_fill(&position, &bin_array, active_id + 4, true).unwrap();
+        assert_eq!(fill.status, RangeOrderStatus::Filled);
+
+        let instructions = get_range_order_withdraw_instructions(
+            &pool,
+            &position,
+            active_id + 4,
+            true,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        )
+        .unwrap();
+        assert_eq!(instructions.len(), 2);
+        assert_eq!(
+            instructions[1].data,
+            build_close_position_instruction_data().unwrap()
+        );
+
+        // Decoded positions with a range the program never creates are rejected
+        position.upper_bin_id = position.lower_bin_id - 1;
+        assert!(get_range_order_fill(&position, &bin_array, active_id, true).is_err());
+        position.upper_bin_id = position.lower_bin_id + 64;
+        assert!(get_range_order_withdraw_instructions(
+            &pool,
+            &position,
+            active_id + 4,
+            true,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        )
+        .is_err());
+    }
+}
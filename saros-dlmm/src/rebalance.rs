@@ -0,0 +1,406 @@
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Result};
+use jupiter_amm_interface::{Amm, QuoteParams, SwapMode, SwapParams};
+use ruint::aliases::U256;
+use saros_sdk::{
+    constants::MAX_BIN_PER_POSITION,
+    errors::ErrorCode,
+    instruction::{create_distribution, LiquidityShape},
+    math::{bin_math::get_price_from_id, u64x64_math::SCALE_OFFSET},
+    state::{bin_array::BinArrayPair, position::Position},
+};
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+
+use crate::{
+    route::get_minimum_amount_out,
+    wide_position::{get_close_positions_instructions, WidePosition},
+    SarosDlmm,
+};
+
+/// When and how a position is moved around the active bin
+#[derive(Clone, Copy, Debug)]
+pub struct RebalancePolicy {
+    /// Leave the position alone while the active bin is inside its range
+    pub only_when_out_of_range: bool,
+    /// Reuse the width of the current position, otherwise use the relative range below
+    pub keep_width: bool,
+    pub relative_bin_id_left: i32,
+    pub relative_bin_id_right: i32,
+    pub shape: LiquidityShape,
+    /// Swap part of the withdrawn tokens through the pair to match the new range
+    pub swap_to_rebalance: bool,
+    pub max_slippage_bps: u16,
+}
+
+/// Swap through the pair between closing the old position and opening the new one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RebalanceSwap {
+    pub amount_in: u64,
+    pub swap_for_y: bool,
+    pub minimum_amount_out: u64,
+}
+
+/// Instructions moving a position, in execution order: decrease, close, swap, create and increase
+pub struct RebalancePlan {
+    pub withdrawn_x: u64,
+    pub withdrawn_y: u64,
+    pub swap: Option<RebalanceSwap>,
+    /// Position mints of the new range, they must sign the transactions creating them
+    pub new_position: WidePosition,
+    pub deposit_x: u64,
+    pub deposit_y: u64,
+    pub instructions: Vec<Instruction>,
+}
+
+impl RebalancePolicy {
+    pub fn should_rebalance(&self, position: &Position, active_id: u32) -> bool {
+        !self.only_when_out_of_range
+            || active_id < position.lower_bin_id
+            || active_id > position.upper_bin_id
+    }
+
+    /// Range of the new position relative to the active bin
+    pub fn get_relative_range(&self, position: &Position) -> Result<(i32, i32)> {
+        if !self.keep_width {
+            if self.relative_bin_id_left > self.relative_bin_id_right {
+                return Err(ErrorCode::InvalidBinRange.into());
+            }
+            return Ok((self.relative_bin_id_left, self.relative_bin_id_right));
+        }
+
+        let width = position
+            .upper_bin_id
+            .checked_sub(position.lower_bin_id)
+            .ok_or(ErrorCode::InvalidBinRange)?
+            + 1;
+        let width = i32::try_from(width.min(MAX_BIN_PER_POSITION as u32))?;
+        let relative_bin_id_left = -(width - 1) / 2;
+
+        Ok((relative_bin_id_left, relative_bin_id_left + width - 1))
+    }
+}
+
+/// Plan the move of `position` around the active bin of `amm`, `None` when the policy keeps it.
+///
+/// The rebalance swap targets the same value in every bin of the new range at the current price,
+/// fees and price impact aside, and its minimum amount out is what gets deposited.
+#[allow(clippy::too_many_arguments)]
+pub fn plan_rebalance(
+    amm: &SarosDlmm,
+    position: &Position,
+    bin_array: &BinArrayPair,
+    policy: &RebalancePolicy,
+    user: Pubkey,
+    user_vault_x: Pubkey,
+    user_vault_y: Pubkey,
+    existing_accounts: &HashSet<Pubkey>,
+) -> Result<Option<RebalancePlan>> {
+    if position.pair != amm.key {
+        return Err(anyhow!("Position does not belong to pair {}", amm.key));
+    }
+
+    let active_id = amm.pair.active_id;
+    if !policy.should_rebalance(position, active_id) {
+        return Ok(None);
+    }
+
+    let (withdrawn_x, withdrawn_y) = position.get_amounts(bin_array)?;
+    let mut instructions = get_close_positions_instructions(
+        amm,
+        std::slice::from_ref(position),
+        user,
+        user_vault_x,
+        user_vault_y,
+    )?;
+
+    let (relative_bin_id_left, relative_bin_id_right) = policy.get_relative_range(position)?;
+
+    let mut deposit_x = withdrawn_x;
+    let mut deposit_y = withdrawn_y;
+    let mut swap = None;
+
+    if policy.swap_to_rebalance {
+        let distribution =
+            create_distribution(policy.shape, relative_bin_id_left, relative_bin_id_right)?;
+        let bins_x = distribution.iter().filter(|d| d.distribution_x > 0).count();
+        let bins_y = distribution.iter().filter(|d| d.distribution_y > 0).count();
+
+        if let Some((amount_in, swap_for_y)) =
+            get_rebalance_swap(amm, withdrawn_x, withdrawn_y, bins_x, bins_y)?
+        {
+            let (source_mint, destination_mint, source_token_account, destination_token_account) =
+                if swap_for_y {
+                    (
+                        amm.pair.token_mint_x,
+                        amm.pair.token_mint_y,
+                        user_vault_x,
+                        user_vault_y,
+                    )
+                } else {
+                    (
+                        amm.pair.token_mint_y,
+                        amm.pair.token_mint_x,
+                        user_vault_y,
+                        user_vault_x,
+                    )
+                };
+
+            let quote = amm.quote(&QuoteParams {
+                amount: amount_in,
+                input_mint: source_mint,
+                output_mint: destination_mint,
+                swap_mode: SwapMode::ExactIn,
+            })?;
+            let minimum_amount_out =
+                get_minimum_amount_out(quote.out_amount, policy.max_slippage_bps)?;
+
+            instructions.push(amm.get_swap_instruction(
+                &SwapParams {
+                    swap_mode: SwapMode::ExactIn,
+                    in_amount: amount_in,
+                    out_amount: quote.out_amount,
+                    source_mint,
+                    destination_mint,
+                    source_token_account,
+                    destination_token_account,
+                    token_transfer_authority: user,
+                    quote_mint_to_referrer: None,
+                    jupiter_program_id: &amm.program_id,
+                    missing_dynamic_accounts_as_default: false,
+                },
+                minimum_amount_out,
+            )?);
+
+            if swap_for_y {
+                deposit_x -= amount_in;
+                deposit_y = deposit_y.saturating_add(minimum_amount_out);
+            } else {
+                deposit_y -= amount_in;
+                deposit_x = deposit_x.saturating_add(minimum_amount_out);
+            }
+
+            swap = Some(RebalanceSwap {
+                amount_in,
+                swap_for_y,
+                minimum_amount_out,
+            });
+        }
+    }
+
+    let new_position =
+        WidePosition::from_relative_range(amm, relative_bin_id_left, relative_bin_id_right)?;
+    instructions.extend(new_position.get_create_instructions(amm, user, existing_accounts)?);
+    instructions.extend(new_position.get_increase_instructions(
+        amm,
+        user,
+        user_vault_x,
+        user_vault_y,
+        deposit_x,
+        deposit_y,
+        policy.shape,
+    )?);
+
+    Ok(Some(RebalancePlan {
+        withdrawn_x,
+        withdrawn_y,
+        swap,
+        new_position,
+        deposit_x,
+        deposit_y,
+        instructions,
+    }))
+}
+
+// Amount to sell so the value of X is to the value of Y as `bins_x` is to `bins_y`, valued at the
+// active bin price
+fn get_rebalance_swap(
+    amm: &SarosDlmm,
+    amount_x: u64,
+    amount_y: u64,
+    bins_x: usize,
+    bins_y: usize,
+) -> Result<Option<(u64, bool)>> {
+    if bins_x + bins_y == 0 {
+        return Ok(None);
+    }
+
+    let price = U256::from(
+        get_price_from_id(amm.pair.bin_step, amm.pair.active_id)
+            .ok_or(ErrorCode::AmountOverflow)?,
+    );
+    let value_x = (price * U256::from(amount_x)) >> SCALE_OFFSET;
+    let value = value_x + U256::from(amount_y);
+    let target_value_x = value * U256::from(bins_x) / U256::from(bins_x + bins_y);
+
+    let swap = if value_x > target_value_x {
+        let amount_in = ((value_x - target_value_x) << SCALE_OFFSET) / price;
+        (u64::try_from(amount_in)?.min(amount_x), true)
+    } else {
+        let amount_in = target_value_x - value_x;
+        (u64::try_from(amount_in)?.min(amount_y), false)
+    };
+
+    Ok((swap.0 > 0).then_some(swap))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amms::test_pool::build_test_pool;
+    use saros_sdk::{
+        constants::MIDDLE_BIN_ID,
+        instruction::{
+            build_close_position_instruction_data, build_decrease_position_instruction_data,
+            DecreasePositionParams,
+        },
+    };
+
+    fn setup() -> (SarosDlmm, Position, BinArrayPair) {
+        let pool = build_test_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            10,
+            10_000,
+            1_000_000,
+        );
+
+        // Ten bins of Y fully owned, left behind once the price went up
+        let mut position = Position::default();
+        position.pair = pool.key;
+        position.position_mint = Pubkey::new_unique();
+        position.lower_bin_id = MIDDLE_BIN_ID as u32 - 30;
+        position.upper_bin_id = position.lower_bin_id + 9;
+        for share in position.liquidity_shares[..10].iter_mut() {
+            *share = 1_000_000 << 64;
+        }
+
+        let bin_array = BinArrayPair::merge(pool.bin_array_middle, pool.bin_array_upper).unwrap();
+        (pool, position, bin_array)
+    }
+
+    fn policy() -> RebalancePolicy {
+        RebalancePolicy {
+            only_when_out_of_range: true,
+            keep_width: true,
+            relative_bin_id_left: 0,
+            relative_bin_id_right: 0,
+            shape: LiquidityShape::Spot,
+            swap_to_rebalance: true,
+            max_slippage_bps: 100,
+        }
+    }
+
+    #[test]
+    fn test_plan_rebalance_out_of_range() {
+        let (pool, position, bin_array) = setup();
+        let user = Pubkey::new_unique();
+
+        let plan = plan_rebalance(
+            &pool,
+            &position,
+            &bin_array,
+            &policy(),
+            user,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            &HashSet::from(pool.active_bin_array_key),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!((plan.withdrawn_x, plan.withdrawn_y), (0, 10_000_000));
+
+        // Ten bins around the active bin: four of Y, five of X and the active bin holding both
+        let new_position = &plan.new_position;
+        assert_eq!(new_position.segments.len(), 1);
+        assert_eq!(new_position.lower_bin_id(), pool.pair.active_id - 4);
+        assert_eq!(new_position.upper_bin_id(), pool.pair.active_id + 5);
+
+        let swap = plan.swap.unwrap();
+        assert!(!swap.swap_for_y);
+        assert_eq!(swap.amount_in, 10_000_000 * 6 / 11);
+        assert!(swap.minimum_amount_out > 0);
+        assert_eq!(plan.deposit_y, 10_000_000 - swap.amount_in);
+        assert_eq!(plan.deposit_x, swap.minimum_amount_out);
+
+        let data: Vec<&[u8]> = plan
+            .instructions
+            .iter()
+            .map(|instruction| instruction.data.as_slice())
+            .collect();
+        assert_eq!(
+            data[0],
+            build_decrease_position_instruction_data(DecreasePositionParams {
+                shares: position.liquidity_shares[..10].to_vec(),
+            })
+            .unwrap()
+        );
+        assert_eq!(data[1], build_close_position_instruction_data().unwrap());
+        assert!(plan
+            .instructions
+            .iter()
+            .all(|instruction| instruction.program_id == pool.program_id));
+        // decrease, close, swap, create and increase, the bin arrays already exist
+        assert_eq!(plan.instructions.len(), 5);
+    }
+
+    #[test]
+    fn test_plan_rebalance_in_range() {
+        let (pool, mut position, bin_array) = setup();
+        position.lower_bin_id = pool.pair.active_id - 5;
+        position.upper_bin_id = pool.pair.active_id + 4;
+
+        let plan = |policy: &RebalancePolicy| {
+            plan_rebalance(
+                &pool,
+                &position,
+                &bin_array,
+                policy,
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                &HashSet::new(),
+            )
+            .unwrap()
+        };
+
+        assert!(plan(&policy()).is_none());
+
+        let plan = plan(&RebalancePolicy {
+            only_when_out_of_range: false,
+            keep_width: false,
+            relative_bin_id_left: -2,
+            relative_bin_id_right: 2,
+            swap_to_rebalance: false,
+            ..policy()
+        })
+        .unwrap();
+        assert!(plan.swap.is_none());
+        assert_eq!(plan.new_position.lower_bin_id(), pool.pair.active_id - 2);
+        assert_eq!(plan.new_position.upper_bin_id(), pool.pair.active_id + 2);
+        assert_eq!(
+            (plan.deposit_x, plan.deposit_y),
+            (plan.withdrawn_x, plan.withdrawn_y)
+        );
+    }
+
+    #[test]
+    fn test_plan_rebalance_foreign_position() {
+        let (pool, mut position, bin_array) = setup();
+        position.pair = Pubkey::new_unique();
+
+        assert!(plan_rebalance(
+            &pool,
+            &position,
+            &bin_array,
+            &policy(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            &HashSet::new(),
+        )
+        .is_err());
+    }
+}
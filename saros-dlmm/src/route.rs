@@ -1,6 +1,12 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
 use itertools::Itertools;
-use jupiter_amm_interface::Amm;
-use solana_sdk::pubkey::Pubkey;
+use jupiter_amm_interface::{Amm, QuoteParams, SwapMode, SwapParams};
+use saros_sdk::constants::BASIS_POINT_MAX;
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+
+use crate::SarosDlmm;
 
 fn get_two_permutations<T: PartialEq + Clone>(items: &[T]) -> Vec<(T, T)> {
     items
@@ -17,3 +23,468 @@ pub fn get_token_mints_permutations(amm: &dyn Amm) -> Vec<(Pubkey, Pubkey)> {
         get_two_permutations(&amm.get_reserve_mints())
     }
 }
+
+pub const DEFAULT_MAX_HOPS: usize = 3;
+
+/// One swap of a route
+#[derive(Clone, Debug)]
+pub struct RouteLeg {
+    pub pool: Pubkey,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub in_amount: u64,
+    pub out_amount: u64,
+    pub fee_amount: u64,
+    pub fee_mint: Pubkey,
+}
+
+/// Chain of swaps where the output of each leg is the input of the next one
+#[derive(Clone, Debug)]
+pub struct Route {
+    pub swap_mode: SwapMode,
+    pub in_amount: u64,
+    pub out_amount: u64,
+    pub legs: Vec<RouteLeg>,
+}
+
+// (pool, input mint, output mint)
+type Hop = (Pubkey, Pubkey, Pubkey);
+
+/// Finds the best path between two mints through a set of loaded pools
+pub struct Router {
+    pools: HashMap<Pubkey, SarosDlmm>,
+    // mint -> pools trading it, sorted by key
+    pools_by_mint: HashMap<Pubkey, Vec<Pubkey>>,
+}
+
+impl Router {
+    pub fn new<I>(pools: I) -> Self
+    where
+        I: IntoIterator<Item = SarosDlmm>,
+    {
+        let pools: HashMap<Pubkey, SarosDlmm> =
+            pools.into_iter().map(|pool| (pool.key(), pool)).collect();
+
+        let mut pools_by_mint: HashMap<Pubkey, Vec<Pubkey>> = HashMap::new();
+        for pool in pools.values() {
+            for mint in pool.get_reserve_mints() {
+                pools_by_mint.entry(mint).or_default().push(pool.key());
+            }
+        }
+        for keys in pools_by_mint.values_mut() {
+            keys.sort();
+        }
+
+        Self {
+            pools,
+            pools_by_mint,
+        }
+    }
+
+    pub fn get_pool(&self, key: &Pubkey) -> Option<&SarosDlmm> {
+        self.pools.get(key)
+    }
+
+    /// Every path of at most `max_hops` pools from `input_mint` to `output_mint` that never visits
+    /// a mint twice
+    pub fn find_paths(
+        &self,
+        input_mint: Pubkey,
+        output_mint: Pubkey,
+        max_hops: usize,
+    ) -> Vec<Vec<Hop>> {
+        let mut paths = Vec::new();
+        let mut visited = vec![input_mint];
+        let mut path = Vec::new();
+        self.walk(
+            input_mint,
+            output_mint,
+            max_hops,
+            &mut visited,
+            &mut path,
+            &mut paths,
+        );
+        paths
+    }
+
+    fn walk(
+        &self,
+        mint: Pubkey,
+        output_mint: Pubkey,
+        max_hops: usize,
+        visited: &mut Vec<Pubkey>,
+        path: &mut Vec<Hop>,
+        paths: &mut Vec<Vec<Hop>>,
+    ) {
+        if path.len() == max_hops {
+            return;
+        }
+
+        for pool_key in self.pools_by_mint.get(&mint).into_iter().flatten() {
+            let pool = &self.pools[pool_key];
+            let next_mint = if pool.pair.token_mint_x == mint {
+                pool.pair.token_mint_y
+            } else {
+                pool.pair.token_mint_x
+            };
+            if visited.contains(&next_mint) {
+                continue;
+            }
+
+            path.push((*pool_key, mint, next_mint));
+            if next_mint == output_mint {
+                paths.push(path.clone());
+            } else {
+                visited.push(next_mint);
+                self.walk(next_mint, output_mint, max_hops, visited, path, paths);
+                visited.pop();
+            }
+            path.pop();
+        }
+    }
+
+    /// Quote `amount` along `path`, forward for exact in and backward for exact out
+    pub fn quote_path(&self, path: &[Hop], amount: u64, swap_mode: SwapMode) -> Result<Route> {
+        if path.is_empty() {
+            return Err(anyhow!("Path has no hop"));
+        }
+        for (hop, next_hop) in path.iter().zip(path.iter().skip(1)) {
+            if hop.2 != next_hop.1 {
+                return Err(anyhow!(
+                    "Hop through pool {} outputs {} but the next hop takes {}",
+                    hop.0,
+                    hop.2,
+                    next_hop.1
+                ));
+            }
+        }
+
+        let mut legs = Vec::with_capacity(path.len());
+        let mut amount = amount;
+
+        let hops: Vec<&Hop> = match swap_mode {
+            SwapMode::ExactIn => path.iter().collect(),
+            SwapMode::ExactOut => path.iter().rev().collect(),
+        };
+
+        for (pool_key, input_mint, output_mint) in hops {
+            let pool = self
+                .get_pool(pool_key)
+                .ok_or_else(|| anyhow!("Unknown pool {}", pool_key))?;
+            let quote = pool.quote(&QuoteParams {
+                amount,
+                input_mint: *input_mint,
+                output_mint: *output_mint,
+                swap_mode,
+            })?;
+
+            amount = match swap_mode {
+                SwapMode::ExactIn => quote.out_amount,
+                SwapMode::ExactOut => quote.in_amount,
+            };
+
+            legs.push(RouteLeg {
+                pool: *pool_key,
+                input_mint: *input_mint,
+                output_mint: *output_mint,
+                in_amount: quote.in_amount,
+                out_amount: quote.out_amount,
+                fee_amount: quote.fee_amount,
+                fee_mint: quote.fee_mint,
+            });
+        }
+
+        if swap_mode == SwapMode::ExactOut {
+            legs.reverse();
+        }
+
+        Ok(Route {
+            swap_mode,
+            in_amount: legs[0].in_amount,
+            out_amount: legs[legs.len() - 1].out_amount,
+            legs,
+        })
+    }
+
+    /// Route giving the most output for exact in swaps, or needing the least input for exact out
+    /// swaps. Paths that can not be quoted, e.g. because they lack liquidity, are skipped
+    pub fn find_best_route(
+        &self,
+        input_mint: Pubkey,
+        output_mint: Pubkey,
+        amount: u64,
+        swap_mode: SwapMode,
+        max_hops: usize,
+    ) -> Result<Route> {
+        let mut best: Option<Route> = None;
+
+        for path in self.find_paths(input_mint, output_mint, max_hops) {
+            let Ok(route) = self.quote_path(&path, amount, swap_mode) else {
+                continue;
+            };
+
+            let is_better = match &best {
+                None => true,
+                Some(best) => match swap_mode {
+                    SwapMode::ExactIn => route.out_amount > best.out_amount,
+                    SwapMode::ExactOut => route.in_amount < best.in_amount,
+                },
+            };
+            if is_better {
+                best = Some(route);
+            }
+        }
+
+        best.ok_or_else(|| anyhow!("No route found from {} to {}", input_mint, output_mint))
+    }
+
+    /// Swap instructions for `route`, `token_accounts` maps every mint of the route to the user
+    /// token account holding it.
+    ///
+    /// Legs are re-quoted so that each one only relies on what the previous one is guaranteed to
+    /// deliver after slippage: exact in legs spend the minimum output of the previous leg and exact
+    /// out legs produce the maximum input of the next one. Whatever the intermediate legs deliver
+    /// above that stays in the intermediate token accounts.
+    pub fn get_route_instructions(
+        &self,
+        route: &Route,
+        user: Pubkey,
+        token_accounts: &HashMap<Pubkey, Pubkey>,
+        slippage_bps: u16,
+    ) -> Result<Vec<Instruction>> {
+        let swap_mode = route.swap_mode;
+        let legs: Vec<&RouteLeg> = match swap_mode {
+            SwapMode::ExactIn => route.legs.iter().collect(),
+            SwapMode::ExactOut => route.legs.iter().rev().collect(),
+        };
+
+        let mut amount = match swap_mode {
+            SwapMode::ExactIn => route.in_amount,
+            SwapMode::ExactOut => route.out_amount,
+        };
+        let mut instructions = Vec::with_capacity(legs.len());
+
+        for leg in legs {
+            let pool = self
+                .get_pool(&leg.pool)
+                .ok_or_else(|| anyhow!("Unknown pool {}", leg.pool))?;
+            let quote = pool.quote(&QuoteParams {
+                amount,
+                input_mint: leg.input_mint,
+                output_mint: leg.output_mint,
+                swap_mode,
+            })?;
+
+            let other_amount_threshold = match swap_mode {
+                SwapMode::ExactIn => get_minimum_amount_out(quote.out_amount, slippage_bps)?,
+                SwapMode::ExactOut => get_maximum_amount_in(quote.in_amount, slippage_bps)?,
+            };
+
+            let token_account = |mint: &Pubkey| {
+                token_accounts
+                    .get(mint)
+                    .copied()
+                    .ok_or_else(|| anyhow!("Missing token account for mint {}", mint))
+            };
+
+            instructions.push(pool.get_swap_instruction(
+                &SwapParams {
+                    swap_mode,
+                    in_amount: quote.in_amount,
+                    out_amount: quote.out_amount,
+                    source_mint: leg.input_mint,
+                    destination_mint: leg.output_mint,
+                    source_token_account: token_account(&leg.input_mint)?,
+                    destination_token_account: token_account(&leg.output_mint)?,
+                    token_transfer_authority: user,
+                    quote_mint_to_referrer: None,
+                    jupiter_program_id: &pool.program_id,
+                    missing_dynamic_accounts_as_default: false,
+                },
+                other_amount_threshold,
+            )?);
+
+            amount = other_amount_threshold;
+        }
+
+        if swap_mode == SwapMode::ExactOut {
+            instructions.reverse();
+        }
+
+        Ok(instructions)
+    }
+}
+
+pub fn get_minimum_amount_out(amount_out: u64, slippage_bps: u16) -> Result<u64> {
+    let slippage_bps = u64::from(slippage_bps).min(BASIS_POINT_MAX);
+    Ok(u64::try_from(
+        u128::from(amount_out) * u128::from(BASIS_POINT_MAX - slippage_bps)
+            / u128::from(BASIS_POINT_MAX),
+    )?)
+}
+
+pub fn get_maximum_amount_in(amount_in: u64, slippage_bps: u16) -> Result<u64> {
+    let numerator = u128::from(amount_in) * u128::from(BASIS_POINT_MAX + u64::from(slippage_bps));
+    Ok(u64::try_from(
+        numerator.div_ceil(u128::from(BASIS_POINT_MAX)),
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amms::test_pool::build_test_pool;
+
+    struct Mints {
+        a: Pubkey,
+        b: Pubkey,
+        c: Pubkey,
+    }
+
+    fn build_router() -> (Router, Mints, [Pubkey; 3]) {
+        let mints = Mints {
+            a: Pubkey::new_unique(),
+            b: Pubkey::new_unique(),
+            c: Pubkey::new_unique(),
+        };
+        let keys = [
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ];
+
+        let router = Router::new(vec![
+            build_test_pool(keys[0], mints.a, mints.b, 1, 10_000, 1_000_000_000),
+            build_test_pool(keys[1], mints.b, mints.c, 1, 10_000, 1_000_000_000),
+            // Direct pool is too shallow for the amounts used below
+            build_test_pool(keys[2], mints.a, mints.c, 1, 10_000, 1_000),
+        ]);
+
+        (router, mints, keys)
+    }
+
+    #[test]
+    fn test_find_paths() {
+        let (router, mints, keys) = build_router();
+
+        let paths = router.find_paths(mints.a, mints.c, DEFAULT_MAX_HOPS);
+        assert_eq!(paths.len(), 2);
+        assert!(paths.contains(&vec![(keys[2], mints.a, mints.c)]));
+        assert!(paths.contains(&vec![
+            (keys[0], mints.a, mints.b),
+            (keys[1], mints.b, mints.c)
+        ]));
+
+        assert_eq!(router.find_paths(mints.a, mints.c, 1).len(), 1);
+    }
+
+    #[test]
+    fn test_quote_path_rejects_broken_paths() {
+        let (router, mints, keys) = build_router();
+
+        assert!(router.quote_path(&[], 1_000, SwapMode::ExactIn).is_err());
+        assert!(router
+            .quote_path(
+                &[(keys[0], mints.a, mints.b), (keys[2], mints.a, mints.c)],
+                1_000,
+                SwapMode::ExactIn
+            )
+            .is_err());
+        assert!(router
+            .quote_path(
+                &[(keys[0], mints.a, mints.b), (keys[1], mints.b, mints.c)],
+                1_000,
+                SwapMode::ExactOut
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn test_best_route_exact_in() {
+        let (router, mints, keys) = build_router();
+
+        let route = router
+            .find_best_route(
+                mints.a,
+                mints.c,
+                1_000_000,
+                SwapMode::ExactIn,
+                DEFAULT_MAX_HOPS,
+            )
+            .unwrap();
+
+        assert_eq!(route.legs.len(), 2);
+        assert_eq!(route.legs[0].pool, keys[0]);
+        assert_eq!(route.legs[1].pool, keys[1]);
+        assert_eq!(route.in_amount, 1_000_000);
+        assert_eq!(route.legs[0].out_amount, route.legs[1].in_amount);
+        assert_eq!(route.out_amount, route.legs[1].out_amount);
+        assert!(route.out_amount > 0 && route.out_amount < 1_000_000);
+    }
+
+    #[test]
+    fn test_best_route_exact_out() {
+        let (router, mints, _) = build_router();
+
+        let route = router
+            .find_best_route(
+                mints.a,
+                mints.c,
+                1_000_000,
+                SwapMode::ExactOut,
+                DEFAULT_MAX_HOPS,
+            )
+            .unwrap();
+
+        assert_eq!(route.legs.len(), 2);
+        assert_eq!(route.out_amount, 1_000_000);
+        assert_eq!(route.legs[0].out_amount, route.legs[1].in_amount);
+        assert!(route.in_amount > 1_000_000);
+    }
+
+    #[test]
+    fn test_route_instructions() {
+        let (router, mints, keys) = build_router();
+        let user = Pubkey::new_unique();
+        let token_accounts: HashMap<Pubkey, Pubkey> = [mints.a, mints.b, mints.c]
+            .into_iter()
+            .map(|mint| (mint, Pubkey::new_unique()))
+            .collect();
+
+        for swap_mode in [SwapMode::ExactIn, SwapMode::ExactOut] {
+            let route = router
+                .find_best_route(mints.a, mints.c, 1_000_000, swap_mode, DEFAULT_MAX_HOPS)
+                .unwrap();
+            let instructions = router
+                .get_route_instructions(&route, user, &token_accounts, 50)
+                .unwrap();
+
+            assert_eq!(instructions.len(), 2);
+            assert_eq!(instructions[0].accounts[0].pubkey, keys[0]);
+            assert_eq!(instructions[1].accounts[0].pubkey, keys[1]);
+        }
+
+        let mut missing = token_accounts.clone();
+        missing.remove(&mints.b);
+        let route = router
+            .find_best_route(
+                mints.a,
+                mints.c,
+                1_000_000,
+                SwapMode::ExactIn,
+                DEFAULT_MAX_HOPS,
+            )
+            .unwrap();
+        assert!(router
+            .get_route_instructions(&route, user, &missing, 50)
+            .is_err());
+    }
+
+    #[test]
+    fn test_slippage_bounds() {
+        assert_eq!(get_minimum_amount_out(10_000, 50).unwrap(), 9_950);
+        assert_eq!(get_maximum_amount_in(10_000, 50).unwrap(), 10_050);
+        assert_eq!(get_maximum_amount_in(1, 1).unwrap(), 2);
+        assert_eq!(get_minimum_amount_out(10_000, u16::MAX).unwrap(), 0);
+    }
+}
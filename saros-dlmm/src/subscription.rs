@@ -0,0 +1,654 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{atomic::Ordering, Arc, RwLock},
+};
+
+use anyhow::{anyhow, Context, Result};
+use jupiter_amm_interface::Amm;
+use saros_sdk::{
+    math::fees::get_epoch_transfer_fee,
+    state::{bin_array::BinArray, pair::Pair},
+};
+use solana_sdk::{
+    account::Account,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar::{clock, clock::Clock},
+};
+use tokio::sync::mpsc;
+
+use crate::{account_provider::AccountProvider, SarosDlmm};
+
+/// Account write observed by a websocket or Geyser style feed
+#[derive(Clone, Debug)]
+pub struct AccountUpdate {
+    pub pubkey: Pubkey,
+    pub account: Account,
+    pub slot: u64,
+}
+
+/// Emitted once a pool has applied an update that can change its quotes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoolChanged {
+    pub pool: Pubkey,
+    pub slot: u64,
+}
+
+/// Sent by [`run_pool_subscriptions`] for every pool change and every failure it recovers from
+#[derive(Debug)]
+pub enum SubscriptionEvent {
+    PoolChanged(PoolChanged),
+    /// The update of `pubkey` failed to apply and was skipped
+    UpdateFailed {
+        pubkey: Pubkey,
+        error: anyhow::Error,
+    },
+    /// Fetching the pending accounts failed, they are fetched again after the next update
+    FetchFailed(anyhow::Error),
+}
+
+/// Set of pools kept up to date from account updates.
+///
+/// Every update is routed to the pools reading the account and only that account is decoded: a
+/// bin array write re-decodes one bin array, a pair write re-derives the bin array keys. Clock
+/// updates refresh the shared timestamp without notifying, as they land on every slot.
+/// Updates older than the last write seen for the same account are dropped, so a feed delivering
+/// out of order never rolls a pool back.
+///
+/// A pool moving to bin arrays no write was seen for yet queues them as pending fetches, the feed
+/// only delivers them on their next write so they have to be fetched once with
+/// [`fetch_pending_accounts`].
+#[derive(Default)]
+pub struct PoolSubscriptions {
+    pools: HashMap<Pubkey, SarosDlmm>,
+    // account -> pools reading it
+    routes: HashMap<Pubkey, HashSet<Pubkey>>,
+    // Last write seen for every routed account and its slot, restores the bin arrays that stay in
+    // the window when a pool moves
+    accounts: HashMap<Pubkey, (u64, Account)>,
+    // Accounts a pool moved to without a write seen for them, with the slot of the move
+    pending_fetches: HashMap<Pubkey, u64>,
+}
+
+impl PoolSubscriptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Track `pool`, replacing any pool with the same key
+    pub fn add_pool(&mut self, pool: SarosDlmm) {
+        let key = pool.key;
+        self.remove_pool(&key);
+        self.pools.insert(key, pool);
+        self.add_routes(&key);
+    }
+
+    pub fn remove_pool(&mut self, key: &Pubkey) -> Option<SarosDlmm> {
+        let pool = self.pools.remove(key)?;
+        self.remove_routes(key);
+        self.retain_routed_fetches();
+        Some(pool)
+    }
+
+    pub fn get_pool(&self, key: &Pubkey) -> Option<&SarosDlmm> {
+        self.pools.get(key)
+    }
+
+    pub fn pools(&self) -> impl Iterator<Item = &SarosDlmm> {
+        self.pools.values()
+    }
+
+    /// Accounts the feed has to deliver, changes when a pool moves to other bin arrays
+    pub fn get_subscribed_accounts(&self) -> Vec<Pubkey> {
+        let mut keys: Vec<Pubkey> = self.routes.keys().copied().collect();
+        keys.sort();
+        keys
+    }
+
+    /// Accounts to fetch once and feed back through [`Self::apply`] at the returned slot, drains
+    /// the queue
+    pub fn take_pending_fetches(&mut self) -> Vec<(Pubkey, u64)> {
+        let mut fetches: Vec<(Pubkey, u64)> = self.pending_fetches.drain().collect();
+        fetches.sort();
+        fetches
+    }
+
+    fn add_routes(&mut self, key: &Pubkey) {
+        if let Some(pool) = self.pools.get(key) {
+            for account in pool.get_accounts_to_update() {
+                self.routes.entry(account).or_default().insert(*key);
+            }
+        }
+    }
+
+    fn remove_routes(&mut self, key: &Pubkey) {
+        self.routes.retain(|account, pools| {
+            pools.remove(key);
+            if pools.is_empty() {
+                self.accounts.remove(account);
+            }
+            !pools.is_empty()
+        });
+    }
+
+    fn retain_routed_fetches(&mut self) {
+        self.pending_fetches
+            .retain(|account, _| self.routes.contains_key(account));
+    }
+
+    /// Apply `update` to every pool reading the account, returns the pools whose quotes may have
+    /// changed. Accounts no pool reads are ignored.
+    pub fn apply(&mut self, update: AccountUpdate) -> Result<Vec<PoolChanged>> {
+        let AccountUpdate {
+            pubkey,
+            account,
+            slot,
+        } = update;
+
+        let Some(pool_keys) = self.routes.get(&pubkey) else {
+            return Ok(Vec::new());
+        };
+        let mut pool_keys: Vec<Pubkey> = pool_keys.iter().copied().collect();
+        pool_keys.sort();
+
+        if self
+            .accounts
+            .get(&pubkey)
+            .is_some_and(|(last_slot, _)| slot < *last_slot)
+        {
+            return Ok(Vec::new());
+        }
+
+        if pubkey == clock::ID {
+            let clock: Clock = bincode::deserialize(&account.data)
+                .with_context(|| "Failed to deserialize Clock")?;
+            for pool_key in pool_keys.iter() {
+                if let Some(pool) = self.pools.get(pool_key) {
+                    pool.epoch.store(clock.epoch, Ordering::Relaxed);
+                    pool.timestamp
+                        .store(clock.unix_timestamp, Ordering::Relaxed);
+                }
+            }
+            self.accounts.insert(pubkey, (slot, account));
+            return Ok(Vec::new());
+        }
+
+        let mut changed = Vec::new();
+        for pool_key in pool_keys {
+            if self.apply_to_pool(&pool_key, &pubkey, &account, slot)? {
+                changed.push(PoolChanged {
+                    pool: pool_key,
+                    slot,
+                });
+            }
+        }
+        self.accounts.insert(pubkey, (slot, account));
+        self.pending_fetches.remove(&pubkey);
+
+        Ok(changed)
+    }
+
+    fn apply_to_pool(
+        &mut self,
+        pool_key: &Pubkey,
+        key: &Pubkey,
+        account: &Account,
+        slot: u64,
+    ) -> Result<bool> {
+        let Some(pool) = self.pools.get_mut(pool_key) else {
+            return Ok(false);
+        };
+
+        // The pool may hold a newer state than the feed, e.g. right after being loaded
+        if pool
+            .get_account_slot(key)
+            .is_some_and(|held_slot| slot < held_slot)
+        {
+            return Ok(false);
+        }
+
+        if key == pool_key {
+            let pair = Pair::unpack(&account.data)
+                .with_context(|| format!("Invalid pair account {}", key))?;
+            if pool.apply_pair(pair, slot)? {
+                for bin_array_key in pool.bin_array_key {
+                    if let Some((cached_slot, cached)) = self.accounts.get(&bin_array_key) {
+                        pool.apply_bin_array(
+                            &bin_array_key,
+                            BinArray::unpack(&cached.data)?,
+                            *cached_slot,
+                        )?;
+                    } else {
+                        self.pending_fetches.insert(bin_array_key, slot);
+                    }
+                }
+                self.remove_routes(pool_key);
+                self.add_routes(pool_key);
+                self.retain_routed_fetches();
+            }
+            return Ok(true);
+        }
+
+        if pool.bin_array_key.contains(key) {
+            let bin_array = BinArray::unpack(&account.data)
+                .with_context(|| format!("Invalid bin array account {}", key))?;
+            return pool.apply_bin_array(key, bin_array, slot);
+        }
+
+        let is_mint_x = *key == pool.pair.token_mint_x;
+        if is_mint_x || *key == pool.pair.token_mint_y {
+            let epoch = pool.epoch.load(Ordering::Relaxed);
+            let transfer_fee = get_epoch_transfer_fee(&account.data, &account.owner, epoch)?;
+            if is_mint_x {
+                pool.token_transfer_fee.epoch_transfer_fee_x = transfer_fee;
+            } else {
+                pool.token_transfer_fee.epoch_transfer_fee_y = transfer_fee;
+            }
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+}
+
+/// Fetch the pending accounts of `subscriptions` through `provider` and apply them, returns the
+/// pools whose quotes may have changed. Accounts that do not exist stay uninitialized in the pools.
+pub async fn fetch_pending_accounts<P: AccountProvider + ?Sized>(
+    subscriptions: &RwLock<PoolSubscriptions>,
+    provider: &P,
+) -> Result<Vec<PoolChanged>> {
+    let fetches = subscriptions
+        .write()
+        .map_err(|_| anyhow!("Pool subscriptions lock poisoned"))?
+        .take_pending_fetches();
+    if fetches.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let keys: Vec<Pubkey> = fetches.iter().map(|(key, _)| *key).collect();
+    let accounts = provider.get_multiple_accounts(&keys).await;
+
+    let mut subscriptions = subscriptions
+        .write()
+        .map_err(|_| anyhow!("Pool subscriptions lock poisoned"))?;
+    let accounts = match accounts {
+        Ok(accounts) => accounts,
+        Err(err) => {
+            // Retried on the next call unless a write arrived in the meantime
+            for (key, slot) in fetches {
+                if subscriptions.routes.contains_key(&key)
+                    && !subscriptions.accounts.contains_key(&key)
+                {
+                    subscriptions.pending_fetches.entry(key).or_insert(slot);
+                }
+            }
+            return Err(err);
+        }
+    };
+
+    let mut changed = Vec::new();
+    for ((pubkey, slot), account) in fetches.into_iter().zip(accounts) {
+        let Some(account) = account else {
+            continue;
+        };
+        changed.extend(subscriptions.apply(AccountUpdate {
+            pubkey,
+            account,
+            slot,
+        })?);
+    }
+
+    Ok(changed)
+}
+
+/// Feed `updates` into `subscriptions` until the update stream ends, sending a `PoolChanged` for
+/// every pool that applied one. Bin arrays pools move to are fetched through `provider`.
+/// Updates that fail to apply are skipped and reported as events, the task stops early when nobody
+/// listens to the notifications anymore.
+pub async fn run_pool_subscriptions<P: AccountProvider + ?Sized>(
+    subscriptions: Arc<RwLock<PoolSubscriptions>>,
+    provider: Arc<P>,
+    mut updates: mpsc::Receiver<AccountUpdate>,
+    notifications: mpsc::Sender<SubscriptionEvent>,
+) -> Result<()> {
+    while let Some(update) = updates.recv().await {
+        let pubkey = update.pubkey;
+        let applied = subscriptions
+            .write()
+            .map_err(|_| anyhow!("Pool subscriptions lock poisoned"))?
+            .apply(update);
+
+        let mut events: Vec<SubscriptionEvent> = match applied {
+            Ok(changed) => changed
+                .into_iter()
+                .map(SubscriptionEvent::PoolChanged)
+                .collect(),
+            Err(error) => vec![SubscriptionEvent::UpdateFailed { pubkey, error }],
+        };
+
+        match fetch_pending_accounts(&subscriptions, provider.as_ref()).await {
+            Ok(fetched) => events.extend(fetched.into_iter().map(SubscriptionEvent::PoolChanged)),
+            Err(error) => events.push(SubscriptionEvent::FetchFailed(error)),
+        }
+
+        for event in events {
+            if notifications.send(event).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use jupiter_amm_interface::{AccountMap, QuoteParams, SwapMode};
+    use saros_sdk::state::bin::BIN_ARRAY_SIZE;
+    use solana_sdk::program_pack::IsInitialized;
+
+    use super::*;
+    use crate::amms::test_pool::{build_test_pool, build_test_pool_accounts};
+
+    fn quote_x_for_y(pool: &SarosDlmm, amount: u64) -> u64 {
+        pool.quote(&QuoteParams {
+            amount,
+            input_mint: pool.pair.token_mint_x,
+            output_mint: pool.pair.token_mint_y,
+            swap_mode: SwapMode::ExactIn,
+        })
+        .unwrap()
+        .out_amount
+    }
+
+    fn bin_array_update(
+        pool: &SarosDlmm,
+        index: usize,
+        reserve_y: u64,
+        slot: u64,
+    ) -> AccountUpdate {
+        let mut bin_array = [
+            pool.bin_array_lower,
+            pool.bin_array_middle,
+            pool.bin_array_upper,
+        ][index];
+        for bin in bin_array.bins.iter_mut() {
+            if bin.reserve_y > 0 {
+                bin.reserve_y = reserve_y;
+            }
+        }
+
+        let mut data = vec![0u8; BinArray::LEN];
+        bin_array.pack_into_slice(&mut data);
+        AccountUpdate {
+            pubkey: pool.bin_array_key[index],
+            account: Account {
+                lamports: 1,
+                data,
+                owner: pool.program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+            slot,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bin_array_update_notifies() {
+        let pool = build_test_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            10,
+            10_000,
+            1_000_000,
+        );
+        let pool_key = pool.key;
+        let before = quote_x_for_y(&pool, 2_000_000);
+
+        let mut subscriptions = PoolSubscriptions::new();
+        subscriptions.add_pool(pool.clone());
+        let subscriptions = Arc::new(RwLock::new(subscriptions));
+
+        let (update_sender, update_receiver) = mpsc::channel(8);
+        let (notification_sender, mut notification_receiver) = mpsc::channel(8);
+        let task = tokio::spawn(run_pool_subscriptions(
+            subscriptions.clone(),
+            Arc::new(AccountMap::default()),
+            update_receiver,
+            notification_sender,
+        ));
+
+        // Not read by any pool
+        update_sender
+            .send(AccountUpdate {
+                pubkey: Pubkey::new_unique(),
+                account: Account::default(),
+                slot: 9,
+            })
+            .await
+            .unwrap();
+        // Read by the pool but not a bin array, reported and skipped
+        update_sender
+            .send(AccountUpdate {
+                pubkey: pool.bin_array_key[1],
+                account: Account::default(),
+                slot: 9,
+            })
+            .await
+            .unwrap();
+        // Both swap bin arrays lose half of their Y
+        update_sender
+            .send(bin_array_update(&pool, 1, 500_000, 10))
+            .await
+            .unwrap();
+        update_sender
+            .send(bin_array_update(&pool, 2, 500_000, 11))
+            .await
+            .unwrap();
+        drop(update_sender);
+
+        assert!(matches!(
+            notification_receiver.recv().await,
+            Some(SubscriptionEvent::UpdateFailed { pubkey, .. }) if pubkey == pool.bin_array_key[1]
+        ));
+        for slot in [10, 11] {
+            assert!(matches!(
+                notification_receiver.recv().await,
+                Some(SubscriptionEvent::PoolChanged(changed))
+                    if changed == PoolChanged { pool: pool_key, slot }
+            ));
+        }
+        assert!(notification_receiver.recv().await.is_none());
+        task.await.unwrap().unwrap();
+
+        let subscriptions = subscriptions.read().unwrap();
+        let after = quote_x_for_y(subscriptions.get_pool(&pool_key).unwrap(), 2_000_000);
+        assert!(after < before);
+    }
+
+    #[test]
+    fn test_pair_update_moves_bin_arrays() {
+        let pool = build_test_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            10,
+            10_000,
+            1_000_000,
+        );
+        let accounts = build_test_pool_accounts(&pool, 0);
+
+        let mut subscriptions = PoolSubscriptions::new();
+        subscriptions.add_pool(pool.clone());
+        let subscribed = subscriptions.get_subscribed_accounts();
+        assert!(pool
+            .bin_array_key
+            .iter()
+            .all(|key| subscribed.contains(key)));
+
+        // Seen before the pool needs it
+        let upper_update = bin_array_update(&pool, 2, 1_000_000, 5);
+        subscriptions.apply(upper_update).unwrap();
+
+        let mut pair = pool.pair.clone();
+        pair.active_id += BIN_ARRAY_SIZE;
+        let mut data = vec![0u8; Pair::LEN];
+        pair.pack_into_slice(&mut data);
+        let mut pair_account = accounts[&pool.key].clone();
+        pair_account.data = data;
+
+        let changed = subscriptions
+            .apply(AccountUpdate {
+                pubkey: pool.key,
+                account: pair_account,
+                slot: 6,
+            })
+            .unwrap();
+        assert_eq!(
+            changed,
+            vec![PoolChanged {
+                pool: pool.key,
+                slot: 6
+            }]
+        );
+
+        let moved = subscriptions.get_pool(&pool.key).unwrap();
+        assert_eq!(moved.bin_array_key[1], pool.bin_array_key[2]);
+        assert!(moved.bin_array_middle.is_initialized());
+        assert!(!moved.bin_array_upper.is_initialized());
+
+        let subscribed = subscriptions.get_subscribed_accounts();
+        assert!(moved
+            .bin_array_key
+            .iter()
+            .all(|key| subscribed.contains(key)));
+        assert!(!subscribed.contains(&pool.bin_array_key[0]));
+    }
+
+    #[tokio::test]
+    async fn test_pair_move_fetches_new_bin_arrays() {
+        let pool = build_test_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            10,
+            10_000,
+            1_000_000,
+        );
+        let accounts = build_test_pool_accounts(&pool, 0);
+
+        let mut subscriptions = PoolSubscriptions::new();
+        subscriptions.add_pool(pool.clone());
+        assert!(subscriptions.take_pending_fetches().is_empty());
+
+        let mut pair = pool.pair.clone();
+        pair.active_id += BIN_ARRAY_SIZE;
+        let mut data = vec![0u8; Pair::LEN];
+        pair.pack_into_slice(&mut data);
+        let mut pair_account = accounts[&pool.key].clone();
+        pair_account.data = data;
+        subscriptions
+            .apply(AccountUpdate {
+                pubkey: pool.key,
+                account: pair_account,
+                slot: 6,
+            })
+            .unwrap();
+
+        // No write was seen for any of the bin arrays the pool moved to
+        let moved = subscriptions.get_pool(&pool.key).unwrap().clone();
+        assert!(!moved.bin_array_middle.is_initialized());
+        let mut expected: Vec<(Pubkey, u64)> =
+            moved.bin_array_key.iter().map(|key| (*key, 6)).collect();
+        expected.sort();
+        let mut pending: Vec<(Pubkey, u64)> = subscriptions
+            .pending_fetches
+            .iter()
+            .map(|(key, slot)| (*key, *slot))
+            .collect();
+        pending.sort();
+        assert_eq!(pending, expected);
+
+        let subscriptions = RwLock::new(subscriptions);
+        // Only the middle bin array exists
+        let mut provider = AccountMap::default();
+        provider.insert(
+            moved.bin_array_key[1],
+            bin_array_update(&pool, 2, 1_000_000, 0).account,
+        );
+        let changed = fetch_pending_accounts(&subscriptions, &provider)
+            .await
+            .unwrap();
+        assert_eq!(
+            changed,
+            vec![PoolChanged {
+                pool: pool.key,
+                slot: 6
+            }]
+        );
+
+        let mut subscriptions = subscriptions.into_inner().unwrap();
+        let fetched = subscriptions.get_pool(&pool.key).unwrap();
+        assert!(fetched.bin_array_middle.is_initialized());
+        assert!(!fetched.bin_array_upper.is_initialized());
+        assert_eq!(fetched.get_account_slot(&moved.bin_array_key[1]), Some(6));
+        assert!(subscriptions.take_pending_fetches().is_empty());
+    }
+
+    #[test]
+    fn test_stale_updates_and_slot_spread() {
+        let pool = build_test_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            10,
+            10_000,
+            1_000_000,
+        );
+        let accounts = build_test_pool_accounts(&pool, 0);
+
+        let mut subscriptions = PoolSubscriptions::new();
+        subscriptions.add_pool(pool.clone());
+
+        let fresh = bin_array_update(&pool, 1, 500_000, 100);
+        let stale = bin_array_update(&pool, 1, 1, 90);
+        assert_eq!(subscriptions.apply(fresh).unwrap().len(), 1);
+        assert!(subscriptions.apply(stale).unwrap().is_empty());
+
+        let held = subscriptions.get_pool(&pool.key).unwrap();
+        assert_eq!(held.get_account_slot(&pool.bin_array_key[1]), Some(100));
+        assert_eq!(held.bin_array_middle.bins[255].reserve_y, 500_000);
+
+        // The pair moves far ahead of the upper bin array the swap reads
+        let pair_update = |slot| AccountUpdate {
+            pubkey: pool.key,
+            account: accounts[&pool.key].clone(),
+            slot,
+        };
+        subscriptions
+            .apply(bin_array_update(&pool, 2, 1_000_000, 100))
+            .unwrap();
+        subscriptions.apply(pair_update(110)).unwrap();
+        quote_x_for_y(subscriptions.get_pool(&pool.key).unwrap(), 1_000);
+
+        subscriptions.apply(pair_update(200)).unwrap();
+        let held = subscriptions.get_pool(&pool.key).unwrap();
+        assert!(held
+            .quote(&QuoteParams {
+                amount: 1_000,
+                input_mint: held.pair.token_mint_x,
+                output_mint: held.pair.token_mint_y,
+                swap_mode: SwapMode::ExactIn,
+            })
+            .is_err());
+
+        subscriptions
+            .apply(bin_array_update(&pool, 2, 1_000_000, 195))
+            .unwrap();
+        subscriptions
+            .apply(bin_array_update(&pool, 1, 1_000_000, 195))
+            .unwrap();
+        quote_x_for_y(subscriptions.get_pool(&pool.key).unwrap(), 1_000);
+    }
+}
@@ -0,0 +1,644 @@
+use std::collections::HashSet;
+
+use anchor_lang::AccountDeserialize;
+use anyhow::{anyhow, Result};
+use jupiter_amm_interface::{Amm, Quote, QuoteParams, SwapMode, SwapParams};
+use rewarder_hook::rewarder_hook::accounts::Hook;
+use saros_sdk::{
+    constants::BASIS_POINT_MAX,
+    instruction::{
+        create_distribution, get_claim_rewards_instruction, ClaimRewardsParams,
+        DecreasePositionParams, IncreasePositionParams, LiquidityShape,
+    },
+    state::position::Position,
+};
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+
+use crate::{
+    account_provider::AccountProvider,
+    amms::position_manager::{get_modifier_position_params, SarosPositionManagement},
+    route::{get_maximum_amount_in, get_minimum_amount_out},
+    wide_position::get_close_positions_instructions,
+    SarosDlmm,
+};
+
+/// Quote of a swap with the instructions executing it
+pub struct SwapTransaction {
+    pub quote: Quote,
+    pub other_amount_threshold: u64,
+    pub instructions: Vec<Instruction>,
+}
+
+/// Associated token accounts of `user` for both tokens of `amm`
+pub fn get_user_vaults(amm: &SarosDlmm, user: Pubkey) -> (Pubkey, Pubkey) {
+    (
+        get_associated_token_address_with_program_id(
+            &user,
+            &amm.pair.token_mint_x,
+            &amm.token_program[0],
+        ),
+        get_associated_token_address_with_program_id(
+            &user,
+            &amm.pair.token_mint_y,
+            &amm.token_program[1],
+        ),
+    )
+}
+
+/// Idempotently create the token accounts of `user` for both tokens of `amm`
+pub fn get_create_user_vaults_instructions(amm: &SarosDlmm, user: Pubkey) -> Vec<Instruction> {
+    [amm.pair.token_mint_x, amm.pair.token_mint_y]
+        .iter()
+        .zip(amm.token_program.iter())
+        .map(|(mint, token_program)| {
+            create_associated_token_account_idempotent(&user, &user, mint, token_program)
+        })
+        .collect()
+}
+
+/// Quote a swap of `amount` of `input_mint` and build it with a `slippage_bps` tolerance, creating
+/// the output token account of `user` first
+pub fn get_swap_transaction(
+    amm: &SarosDlmm,
+    user: Pubkey,
+    input_mint: Pubkey,
+    amount: u64,
+    swap_mode: SwapMode,
+    slippage_bps: u16,
+) -> Result<SwapTransaction> {
+    let (user_vault_x, user_vault_y) = get_user_vaults(amm, user);
+    let (output_mint, source_token_account, destination_token_account, output_program) =
+        if input_mint == amm.pair.token_mint_x {
+            (
+                amm.pair.token_mint_y,
+                user_vault_x,
+                user_vault_y,
+                amm.token_program[1],
+            )
+        } else if input_mint == amm.pair.token_mint_y {
+            (
+                amm.pair.token_mint_x,
+                user_vault_y,
+                user_vault_x,
+                amm.token_program[0],
+            )
+        } else {
+            return Err(anyhow!("Pair {} does not trade {}", amm.key, input_mint));
+        };
+
+    let quote = amm.quote(&QuoteParams {
+        amount,
+        input_mint,
+        output_mint,
+        swap_mode,
+    })?;
+    let other_amount_threshold = match swap_mode {
+        SwapMode::ExactIn => get_minimum_amount_out(quote.out_amount, slippage_bps)?,
+        SwapMode::ExactOut => get_maximum_amount_in(quote.in_amount, slippage_bps)?,
+    };
+
+    let instructions = vec![
+        create_associated_token_account_idempotent(&user, &user, &output_mint, &output_program),
+        amm.get_swap_instruction(
+            &SwapParams {
+                swap_mode,
+                in_amount: quote.in_amount,
+                out_amount: quote.out_amount,
+                source_mint: input_mint,
+                destination_mint: output_mint,
+                source_token_account,
+                destination_token_account,
+                token_transfer_authority: user,
+                quote_mint_to_referrer: None,
+                jupiter_program_id: &amm.program_id,
+                missing_dynamic_accounts_as_default: false,
+            },
+            other_amount_threshold,
+        )?,
+    ];
+
+    Ok(SwapTransaction {
+        quote,
+        other_amount_threshold,
+        instructions,
+    })
+}
+
+/// Subset of `keys` that exist on chain
+pub async fn get_existing_accounts<P>(provider: &P, keys: &[Pubkey]) -> Result<HashSet<Pubkey>>
+where
+    P: AccountProvider + ?Sized,
+{
+    Ok(keys
+        .iter()
+        .zip(provider.get_multiple_accounts(keys).await?)
+        .filter(|(_, account)| account.is_some())
+        .map(|(key, _)| *key)
+        .collect())
+}
+
+/// Deposit `amount_x` and `amount_y` over the whole range of `position` following `shape`
+pub fn get_add_liquidity_instruction(
+    amm: &SarosDlmm,
+    position: &Position,
+    user: Pubkey,
+    shape: LiquidityShape,
+    amount_x: u64,
+    amount_y: u64,
+) -> Result<Instruction> {
+    let (user_vault_x, user_vault_y) = get_user_vaults(amm, user);
+
+    let active_id = i64::from(amm.pair.active_id);
+    let liquidity_distribution = create_distribution(
+        shape,
+        i32::try_from(i64::from(position.lower_bin_id) - active_id)?,
+        i32::try_from(i64::from(position.upper_bin_id) - active_id)?,
+    )?;
+
+    amm.get_increase_position_instruction(
+        get_modifier_position_params(
+            amm,
+            user,
+            position.position_mint,
+            position.lower_bin_id,
+            user_vault_x,
+            user_vault_y,
+        ),
+        IncreasePositionParams {
+            amount_x,
+            amount_y,
+            liquidity_distribution,
+        },
+    )
+}
+
+/// `bps` basis points of every liquidity share of `position`, rounded down
+pub fn get_withdrawn_shares(position: &Position, bps: u16) -> Result<Vec<u128>> {
+    if bps == 0 || u64::from(bps) > BASIS_POINT_MAX {
+        return Err(anyhow!("bps must be between 1 and {}", BASIS_POINT_MAX));
+    }
+
+    let bps = u128::from(bps);
+    let basis_point_max = u128::from(BASIS_POINT_MAX);
+    Ok(position
+        .get_liquidity_shares()?
+        .iter()
+        // Split to avoid overflowing shares close to u128::MAX
+        .map(|share| {
+            share / basis_point_max * bps + share % basis_point_max * bps / basis_point_max
+        })
+        .collect())
+}
+
+/// Withdraw `bps` basis points of the liquidity of `position`, creating the token accounts of
+/// `user` first
+pub fn get_remove_liquidity_instructions(
+    amm: &SarosDlmm,
+    position: &Position,
+    user: Pubkey,
+    bps: u16,
+) -> Result<Vec<Instruction>> {
+    let shares = get_withdrawn_shares(position, bps)?;
+    if shares.iter().all(|share| *share == 0) {
+        return Err(anyhow!(
+            "Position of mint {} holds no liquidity",
+            position.position_mint
+        ));
+    }
+
+    let (user_vault_x, user_vault_y) = get_user_vaults(amm, user);
+    let mut instructions = get_create_user_vaults_instructions(amm, user);
+    instructions.push(amm.get_decrease_position_instruction(
+        get_modifier_position_params(
+            amm,
+            user,
+            position.position_mint,
+            position.lower_bin_id,
+            user_vault_x,
+            user_vault_y,
+        ),
+        DecreasePositionParams { shares },
+    )?);
+    Ok(instructions)
+}
+
+/// Withdraw everything from `position` and close it, creating the token accounts of `user` first
+pub fn get_close_position_instructions(
+    amm: &SarosDlmm,
+    position: &Position,
+    user: Pubkey,
+) -> Result<Vec<Instruction>> {
+    let (user_vault_x, user_vault_y) = get_user_vaults(amm, user);
+    let mut instructions = get_create_user_vaults_instructions(amm, user);
+    instructions.extend(get_close_positions_instructions(
+        amm,
+        std::slice::from_ref(position),
+        user,
+        user_vault_x,
+        user_vault_y,
+    )?);
+    Ok(instructions)
+}
+
+/// Claim the hook rewards of `position`, creating the reward token account of `user` first
+pub async fn get_claim_rewards_instructions<P>(
+    provider: &P,
+    amm: &SarosDlmm,
+    position: &Position,
+    user: Pubkey,
+) -> Result<Vec<Instruction>>
+where
+    P: AccountProvider + ?Sized,
+{
+    let hook_key = amm
+        .get_hook()
+        .ok_or_else(|| anyhow!("Pair {} has no hook", position.pair))?;
+
+    let hook_account = provider
+        .get_account(&hook_key)
+        .await?
+        .ok_or_else(|| anyhow!("Account does not exist: {}", hook_key))?;
+    let hook = Hook::try_deserialize(&mut hook_account.data.as_slice())?;
+
+    // The reward token program is whichever program owns the reward mint
+    let reward_token_program = provider
+        .get_account(&hook.reward_token_mint)
+        .await?
+        .ok_or_else(|| anyhow!("Account does not exist: {}", hook.reward_token_mint))?
+        .owner;
+
+    Ok(vec![
+        create_associated_token_account_idempotent(
+            &user,
+            &user,
+            &hook.reward_token_mint,
+            &reward_token_program,
+        ),
+        get_claim_rewards_instruction(ClaimRewardsParams {
+            hook: hook_key,
+            pair: position.pair,
+            active_id: amm.pair.active_id,
+            position_mint: position.position_mint,
+            position_token_account: get_associated_token_address_with_program_id(
+                &user,
+                &position.position_mint,
+                &spl_token_2022::ID,
+            ),
+            position_lower_bin_id: position.lower_bin_id,
+            reward_token_mint: hook.reward_token_mint,
+            reward_token_program,
+            hook_reserve: hook.hook_reserve,
+            user_reserve: get_associated_token_address_with_program_id(
+                &user,
+                &hook.reward_token_mint,
+                &reward_token_program,
+            ),
+            user,
+        }),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::AccountSerialize;
+    use saros_sdk::{
+        constants::MIDDLE_BIN_ID,
+        instruction::{
+            build_close_position_instruction_data, build_decrease_position_instruction_data,
+            build_increase_position_instruction_data, get_initialize_bin_array_instruction,
+        },
+        state::bin::BIN_ARRAY_SIZE,
+        utils::helper::get_pair_bin_array,
+    };
+    use solana_sdk::account::Account;
+
+    use super::*;
+    use crate::{
+        amms::test_pool::{build_test_pool, build_test_pool_accounts},
+        wide_position::WidePosition,
+    };
+
+    fn setup() -> (SarosDlmm, Position) {
+        let pool = build_test_pool(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            10,
+            10_000,
+            1_000_000,
+        );
+
+        let mut position = Position::default();
+        position.pair = pool.key;
+        position.position_mint = Pubkey::new_unique();
+        position.lower_bin_id = MIDDLE_BIN_ID as u32 - 5;
+        position.upper_bin_id = MIDDLE_BIN_ID as u32 + 4;
+        for share in position.liquidity_shares[..10].iter_mut() {
+            *share = 1_000_000 << 64;
+        }
+
+        (pool, position)
+    }
+
+    #[test]
+    fn test_get_swap_transaction() {
+        let (pool, _) = setup();
+        let user = Pubkey::new_unique();
+        let (user_vault_x, user_vault_y) = get_user_vaults(&pool, user);
+
+        let swap = get_swap_transaction(
+            &pool,
+            user,
+            pool.pair.token_mint_x,
+            1_000_000,
+            SwapMode::ExactIn,
+            100,
+        )
+        .unwrap();
+        assert_eq!(swap.quote.in_amount, 1_000_000);
+        assert_eq!(
+            swap.other_amount_threshold,
+            get_minimum_amount_out(swap.quote.out_amount, 100).unwrap()
+        );
+        assert_eq!(swap.instructions.len(), 2);
+        assert_eq!(
+            swap.instructions[0],
+            create_associated_token_account_idempotent(
+                &user,
+                &user,
+                &pool.pair.token_mint_y,
+                &pool.token_program[1]
+            )
+        );
+        assert_eq!(swap.instructions[1].program_id, pool.program_id);
+        let accounts: Vec<Pubkey> = swap.instructions[1]
+            .accounts
+            .iter()
+            .map(|meta| meta.pubkey)
+            .collect();
+        let source = accounts.iter().position(|key| *key == user_vault_x);
+        let destination = accounts.iter().position(|key| *key == user_vault_y);
+        assert!(source.unwrap() < destination.unwrap());
+
+        let swap = get_swap_transaction(
+            &pool,
+            user,
+            pool.pair.token_mint_y,
+            1_000_000,
+            SwapMode::ExactOut,
+            100,
+        )
+        .unwrap();
+        assert_eq!(swap.quote.out_amount, 1_000_000);
+        assert_eq!(
+            swap.other_amount_threshold,
+            get_maximum_amount_in(swap.quote.in_amount, 100).unwrap()
+        );
+
+        assert!(get_swap_transaction(
+            &pool,
+            user,
+            Pubkey::new_unique(),
+            1_000_000,
+            SwapMode::ExactIn,
+            100
+        )
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_existing_accounts() {
+        let (pool, _) = setup();
+        let accounts = build_test_pool_accounts(&pool, 0);
+
+        // Spans the three bin arrays of the test pool, the upper positions reach the one past them
+        let bin_array_index = pool.pair.bin_array_index();
+        let wide_position = WidePosition::new(
+            (bin_array_index - 1) * BIN_ARRAY_SIZE,
+            (bin_array_index + 2) * BIN_ARRAY_SIZE - 1,
+        )
+        .unwrap();
+        let keys = wide_position.get_bin_array_keys(&pool);
+        assert_eq!(
+            keys,
+            (bin_array_index - 1..=bin_array_index + 2)
+                .map(|index| get_pair_bin_array(index, &pool.key, &pool.program_id).0)
+                .collect::<Vec<_>>()
+        );
+
+        let existing = get_existing_accounts(&accounts, &keys).await.unwrap();
+        assert_eq!(existing, HashSet::from(pool.bin_array_key));
+
+        // Only the bin array missing from the pool gets initialized
+        let user = Pubkey::new_unique();
+        let instructions = wide_position
+            .get_create_instructions(&pool, user, &existing)
+            .unwrap();
+        let initialize_bin_array =
+            get_initialize_bin_array_instruction(pool.key, bin_array_index + 2, user, keys[3]);
+        assert_eq!(
+            instructions
+                .iter()
+                .filter(|ix| ix.data == initialize_bin_array.data)
+                .collect::<Vec<_>>(),
+            vec![&initialize_bin_array]
+        );
+    }
+
+    #[test]
+    fn test_get_add_liquidity_instruction() {
+        let (pool, position) = setup();
+        let user = Pubkey::new_unique();
+
+        let instruction = get_add_liquidity_instruction(
+            &pool,
+            &position,
+            user,
+            LiquidityShape::Spot,
+            1_000,
+            2_000,
+        )
+        .unwrap();
+
+        assert_eq!(
+            instruction.data,
+            build_increase_position_instruction_data(IncreasePositionParams {
+                amount_x: 1_000,
+                amount_y: 2_000,
+                liquidity_distribution: create_distribution(LiquidityShape::Spot, -5, 4).unwrap(),
+            })
+            .unwrap()
+        );
+        let (user_vault_x, user_vault_y) = get_user_vaults(&pool, user);
+        assert!(instruction
+            .accounts
+            .iter()
+            .any(|meta| meta.pubkey == user_vault_x));
+        assert!(instruction
+            .accounts
+            .iter()
+            .any(|meta| meta.pubkey == user_vault_y));
+    }
+
+    #[test]
+    fn test_get_withdrawn_shares() {
+        let (_, mut position) = setup();
+        position.liquidity_shares[0] = u128::MAX;
+        position.liquidity_shares[1] = 9_999;
+
+        let shares = get_withdrawn_shares(&position, 10_000).unwrap();
+        assert_eq!(shares, position.liquidity_shares[..10].to_vec());
+
+        let shares = get_withdrawn_shares(&position, 5_000).unwrap();
+        assert_eq!(shares.len(), 10);
+        assert_eq!(shares[0], u128::MAX / 2);
+        assert_eq!(shares[1], 4_999);
+        assert_eq!(shares[2], 1_000_000 << 63);
+
+        assert!(get_withdrawn_shares(&position, 0).is_err());
+        assert!(get_withdrawn_shares(&position, 10_001).is_err());
+    }
+
+    #[test]
+    fn test_malformed_position_range() {
+        let (pool, mut position) = setup();
+        let user = Pubkey::new_unique();
+
+        for (lower_bin_id, upper_bin_id) in [(10, 9), (0, 64), (0, u32::MAX)] {
+            position.lower_bin_id = lower_bin_id;
+            position.upper_bin_id = upper_bin_id;
+            assert!(get_withdrawn_shares(&position, 10_000).is_err());
+            assert!(get_close_position_instructions(&pool, &position, user).is_err());
+        }
+
+        position.upper_bin_id = 63;
+        assert_eq!(get_withdrawn_shares(&position, 10_000).unwrap().len(), 64);
+    }
+
+    #[test]
+    fn test_get_remove_liquidity_instructions() {
+        let (pool, mut position) = setup();
+        let user = Pubkey::new_unique();
+
+        let instructions =
+            get_remove_liquidity_instructions(&pool, &position, user, 2_500).unwrap();
+        assert_eq!(instructions.len(), 3);
+        assert_eq!(
+            instructions[..2],
+            get_create_user_vaults_instructions(&pool, user)[..]
+        );
+        assert_eq!(
+            instructions[2].data,
+            build_decrease_position_instruction_data(DecreasePositionParams {
+                shares: vec![250_000 << 64; 10],
+            })
+            .unwrap()
+        );
+
+        position.liquidity_shares = [1; 64];
+        assert!(get_remove_liquidity_instructions(&pool, &position, user, 2_500).is_err());
+    }
+
+    #[test]
+    fn test_get_close_position_instructions() {
+        let (pool, mut position) = setup();
+        let user = Pubkey::new_unique();
+
+        let instructions = get_close_position_instructions(&pool, &position, user).unwrap();
+        let data: Vec<Vec<u8>> = instructions[2..].iter().map(|ix| ix.data.clone()).collect();
+        assert_eq!(
+            data,
+            vec![
+                build_decrease_position_instruction_data(DecreasePositionParams {
+                    shares: position.liquidity_shares[..10].to_vec(),
+                })
+                .unwrap(),
+                build_close_position_instruction_data().unwrap(),
+            ]
+        );
+
+        // Empty positions are closed right away
+        position.liquidity_shares = [0; 64];
+        let instructions = get_close_position_instructions(&pool, &position, user).unwrap();
+        assert_eq!(instructions.len(), 3);
+        assert_eq!(
+            instructions[2].data,
+            build_close_position_instruction_data().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_claim_rewards_instructions() {
+        let (mut pool, position) = setup();
+        let user = Pubkey::new_unique();
+        let mut accounts = build_test_pool_accounts(&pool, 0);
+
+        assert!(
+            get_claim_rewards_instructions(&accounts, &pool, &position, user)
+                .await
+                .is_err()
+        );
+
+        let hook_key = Pubkey::new_unique();
+        pool.pair.hook = Some(hook_key);
+        let hook = Hook {
+            bump: [0],
+            authority: Pubkey::new_unique(),
+            pair: pool.key,
+            reward_token_mint: Pubkey::new_unique(),
+            hook_reserve: Pubkey::new_unique(),
+            rewards_per_second: 1,
+            end_time: 0,
+            last_update: 0,
+            delta_bin_a: -10,
+            delta_bin_b: 10,
+            total_unclaimed_rewards: 0,
+        };
+        let mut hook_data = Vec::new();
+        hook.try_serialize(&mut hook_data).unwrap();
+        for (key, owner, data) in [
+            (hook_key, rewarder_hook::ID, hook_data),
+            (hook.reward_token_mint, spl_token_2022::ID, vec![]),
+        ] {
+            accounts.insert(
+                key,
+                Account {
+                    lamports: 1,
+                    data,
+                    owner,
+                    executable: false,
+                    rent_epoch: 0,
+                },
+            );
+        }
+
+        let instructions = get_claim_rewards_instructions(&accounts, &pool, &position, user)
+            .await
+            .unwrap();
+        let user_reserve = get_associated_token_address_with_program_id(
+            &user,
+            &hook.reward_token_mint,
+            &spl_token_2022::ID,
+        );
+        assert_eq!(
+            instructions[0],
+            create_associated_token_account_idempotent(
+                &user,
+                &user,
+                &hook.reward_token_mint,
+                &spl_token_2022::ID
+            )
+        );
+        assert_eq!(instructions[1].program_id, rewarder_hook::ID);
+        for key in [hook_key, hook.hook_reserve, user_reserve] {
+            assert!(instructions[1]
+                .accounts
+                .iter()
+                .any(|meta| meta.pubkey == key));
+        }
+    }
+}
@@ -0,0 +1,568 @@
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Result};
+use jupiter_amm_interface::Amm;
+use saros_sdk::{
+    constants::{BASIS_POINT_MAX, MAX_BIN_PER_POSITION},
+    errors::ErrorCode,
+    instruction::{
+        create_distribution, get_initialize_bin_array_instruction,
+        get_initialize_hook_bin_array_instruction, get_initialize_hook_position_instruction,
+        normalize_weights, CreatePositionParams, DecreasePositionParams, IncreasePositionParams,
+        LiquidityDistribution, LiquidityShape,
+    },
+    state::{bin::BIN_ARRAY_SIZE, position::Position},
+    utils::helper::{find_position, get_hook_bin_array, get_pair_bin_array},
+};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    instruction::Instruction,
+    message::Message,
+    packet::PACKET_DATA_SIZE,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+use crate::{
+    amms::position_manager::{get_modifier_position_params, SarosPositionManagement},
+    SarosDlmm,
+};
+
+pub const WIDE_POSITION_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+/// One of the positions backing a wide position
+pub struct WidePositionSegment {
+    pub position_mint: Keypair,
+    pub lower_bin_id: u32,
+    pub upper_bin_id: u32,
+}
+
+/// A liquidity range wider than `MAX_BIN_PER_POSITION` bins, backed by adjacent positions
+pub struct WidePosition {
+    pub segments: Vec<WidePositionSegment>,
+}
+
+/// Instructions that fit in a single transaction, with the accounts that must sign it besides the
+/// payer
+#[derive(Clone, Debug)]
+pub struct TransactionBatch {
+    pub instructions: Vec<Instruction>,
+    pub signers: Vec<Pubkey>,
+}
+
+/// Split `[lower_bin_id, upper_bin_id]` into consecutive ranges of at most `MAX_BIN_PER_POSITION`
+/// bins
+pub fn split_bin_range(lower_bin_id: u32, upper_bin_id: u32) -> Result<Vec<(u32, u32)>> {
+    if lower_bin_id > upper_bin_id {
+        return Err(ErrorCode::InvalidBinRange.into());
+    }
+
+    let mut ranges = Vec::new();
+    let mut start = lower_bin_id;
+
+    loop {
+        let end = start
+            .saturating_add(MAX_BIN_PER_POSITION as u32 - 1)
+            .min(upper_bin_id);
+        ranges.push((start, end));
+
+        if end == upper_bin_id {
+            return Ok(ranges);
+        }
+        start = end + 1;
+    }
+}
+
+impl WidePosition {
+    /// Split `[lower_bin_id, upper_bin_id]` into positions, each with a fresh position mint
+    pub fn new(lower_bin_id: u32, upper_bin_id: u32) -> Result<Self> {
+        let segments = split_bin_range(lower_bin_id, upper_bin_id)?
+            .into_iter()
+            .map(|(lower_bin_id, upper_bin_id)| WidePositionSegment {
+                position_mint: Keypair::new(),
+                lower_bin_id,
+                upper_bin_id,
+            })
+            .collect();
+
+        Ok(Self { segments })
+    }
+
+    /// Build a wide position around the active bin of `amm`
+    pub fn from_relative_range(
+        amm: &SarosDlmm,
+        relative_bin_id_left: i32,
+        relative_bin_id_right: i32,
+    ) -> Result<Self> {
+        let active_id = i64::from(amm.pair.active_id);
+        let lower_bin_id = u32::try_from(active_id + i64::from(relative_bin_id_left))?;
+        let upper_bin_id = u32::try_from(active_id + i64::from(relative_bin_id_right))?;
+
+        Self::new(lower_bin_id, upper_bin_id)
+    }
+
+    pub fn lower_bin_id(&self) -> u32 {
+        self.segments[0].lower_bin_id
+    }
+
+    pub fn upper_bin_id(&self) -> u32 {
+        self.segments[self.segments.len() - 1].upper_bin_id
+    }
+
+    pub fn position_mints(&self) -> Vec<Pubkey> {
+        self.segments
+            .iter()
+            .map(|segment| segment.position_mint.pubkey())
+            .collect()
+    }
+
+    /// Bin arrays (and hook bin arrays) the positions of the range may need initialized
+    pub fn get_bin_array_keys(&self, amm: &SarosDlmm) -> Vec<Pubkey> {
+        let mut keys = Vec::new();
+        for index in
+            (self.lower_bin_id() / BIN_ARRAY_SIZE)..=(self.upper_bin_id() / BIN_ARRAY_SIZE + 1)
+        {
+            keys.push(get_pair_bin_array(index, &amm.key(), &amm.program_id()).0);
+            if let Some(hook) = amm.get_hook() {
+                keys.push(get_hook_bin_array(index, hook).0);
+            }
+        }
+        keys
+    }
+
+    /// Position mint keypairs required to sign `batch`
+    pub fn get_signers(&self, batch: &TransactionBatch) -> Vec<&Keypair> {
+        self.segments
+            .iter()
+            .map(|segment| &segment.position_mint)
+            .filter(|keypair| batch.signers.contains(&keypair.pubkey()))
+            .collect()
+    }
+
+    /// Create every position of the range, initializing the bin arrays (and hook accounts) that are
+    /// not part of `existing_accounts`.
+    ///
+    /// Positions are created relative to the active bin of `amm`, if the active bin moves before the
+    /// instructions land the whole range is shifted accordingly.
+    pub fn get_create_instructions(
+        &self,
+        amm: &SarosDlmm,
+        user: Pubkey,
+        existing_accounts: &HashSet<Pubkey>,
+    ) -> Result<Vec<Instruction>> {
+        let active_id = i64::from(amm.pair.active_id);
+        let hook = amm.get_hook();
+
+        let mut initialized = existing_accounts.clone();
+        let mut instructions = Vec::new();
+
+        for segment in self.segments.iter() {
+            let bin_array_index = segment.lower_bin_id / BIN_ARRAY_SIZE;
+
+            let (bin_array_lower, bin_array_upper) =
+                get_pair_bin_array(bin_array_index, &amm.key(), &amm.program_id());
+            for (index, bin_array) in [
+                (bin_array_index, bin_array_lower),
+                (bin_array_index + 1, bin_array_upper),
+            ] {
+                if initialized.insert(bin_array) {
+                    instructions.push(get_initialize_bin_array_instruction(
+                        amm.key(),
+                        index,
+                        user,
+                        bin_array,
+                    ));
+                }
+            }
+
+            if let Some(hook) = hook {
+                let (hook_bin_array_lower, hook_bin_array_upper) =
+                    get_hook_bin_array(bin_array_index, hook);
+                for (index, hook_bin_array) in [
+                    (bin_array_index, hook_bin_array_lower),
+                    (bin_array_index + 1, hook_bin_array_upper),
+                ] {
+                    if initialized.insert(hook_bin_array) {
+                        instructions.push(get_initialize_hook_bin_array_instruction(
+                            hook,
+                            index,
+                            user,
+                            hook_bin_array,
+                        ));
+                    }
+                }
+            }
+
+            let position_mint = segment.position_mint.pubkey();
+            instructions.push(amm.get_create_position_instruction(CreatePositionParams {
+                relative_bin_id_left: i32::try_from(i64::from(segment.lower_bin_id) - active_id)?,
+                relative_bin_id_right: i32::try_from(i64::from(segment.upper_bin_id) - active_id)?,
+                user,
+                source_position: get_associated_token_address_with_program_id(
+                    &user,
+                    &position_mint,
+                    &spl_token_2022::ID,
+                ),
+                position_mint,
+            })?);
+
+            if let Some(hook) = hook {
+                instructions.push(get_initialize_hook_position_instruction(
+                    hook,
+                    find_position(position_mint),
+                    user,
+                ));
+            }
+        }
+
+        Ok(instructions)
+    }
+
+    /// Deposit `amount_x` and `amount_y` over the whole range following `shape`, as if it was a
+    /// single position
+    #[allow(clippy::too_many_arguments)]
+    pub fn get_increase_instructions(
+        &self,
+        amm: &SarosDlmm,
+        user: Pubkey,
+        user_vault_x: Pubkey,
+        user_vault_y: Pubkey,
+        amount_x: u64,
+        amount_y: u64,
+        shape: LiquidityShape,
+    ) -> Result<Vec<Instruction>> {
+        let active_id = i64::from(amm.pair.active_id);
+        let relative_bin_id_left = i32::try_from(i64::from(self.lower_bin_id()) - active_id)?;
+        let relative_bin_id_right = i32::try_from(i64::from(self.upper_bin_id()) - active_id)?;
+
+        let distribution = create_distribution(shape, relative_bin_id_left, relative_bin_id_right)?;
+        let segment_params = split_distribution(
+            &distribution,
+            relative_bin_id_left,
+            &self.segments,
+            amount_x,
+            amount_y,
+        )?;
+
+        let mut instructions = Vec::new();
+        for (segment, increase_position_params) in self.segments.iter().zip(segment_params) {
+            if increase_position_params.amount_x == 0 && increase_position_params.amount_y == 0 {
+                continue;
+            }
+
+            let modifier_position_params = get_modifier_position_params(
+                amm,
+                user,
+                segment.position_mint.pubkey(),
+                segment.lower_bin_id,
+                user_vault_x,
+                user_vault_y,
+            );
+            instructions.push(amm.get_increase_position_instruction(
+                modifier_position_params,
+                increase_position_params,
+            )?);
+        }
+
+        Ok(instructions)
+    }
+}
+
+// Slice a distribution covering the whole range into one increase per segment, each side of each
+// slice normalized back to `BASIS_POINT_MAX`
+fn split_distribution(
+    distribution: &[LiquidityDistribution],
+    relative_bin_id_left: i32,
+    segments: &[WidePositionSegment],
+    amount_x: u64,
+    amount_y: u64,
+) -> Result<Vec<IncreasePositionParams>> {
+    let mut params = Vec::with_capacity(segments.len());
+    let mut remaining_x = amount_x;
+    let mut remaining_y = amount_y;
+
+    let first_bin_id = segments[0].lower_bin_id;
+    let last_x = segments.iter().rposition(|segment| {
+        slice(distribution, first_bin_id, segment)
+            .iter()
+            .any(|d| d.distribution_x > 0)
+    });
+    let last_y = segments.iter().rposition(|segment| {
+        slice(distribution, first_bin_id, segment)
+            .iter()
+            .any(|d| d.distribution_y > 0)
+    });
+
+    for (i, segment) in segments.iter().enumerate() {
+        let bins = slice(distribution, first_bin_id, segment);
+        let weights_x: Vec<u64> = bins.iter().map(|d| u64::from(d.distribution_x)).collect();
+        let weights_y: Vec<u64> = bins.iter().map(|d| u64::from(d.distribution_y)).collect();
+
+        // The last funded segment takes the rounding leftovers so nothing is lost
+        let segment_x = if Some(i) == last_x {
+            remaining_x
+        } else {
+            share_of(amount_x, weights_x.iter().sum())?
+        };
+        let segment_y = if Some(i) == last_y {
+            remaining_y
+        } else {
+            share_of(amount_y, weights_y.iter().sum())?
+        };
+        remaining_x -= segment_x;
+        remaining_y -= segment_y;
+
+        let distribution_x = normalize_side(&weights_x)?;
+        let distribution_y = normalize_side(&weights_y)?;
+
+        let offset = i32::try_from(segment.lower_bin_id - first_bin_id)?;
+        let liquidity_distribution = distribution_x
+            .into_iter()
+            .zip(distribution_y)
+            .enumerate()
+            .map(
+                |(j, (distribution_x, distribution_y))| LiquidityDistribution {
+                    relative_bin_id: relative_bin_id_left + offset + j as i32,
+                    distribution_x,
+                    distribution_y,
+                },
+            )
+            .collect();
+
+        params.push(IncreasePositionParams {
+            amount_x: segment_x,
+            amount_y: segment_y,
+            liquidity_distribution,
+        });
+    }
+
+    Ok(params)
+}
+
+fn slice<'a>(
+    distribution: &'a [LiquidityDistribution],
+    first_bin_id: u32,
+    segment: &WidePositionSegment,
+) -> &'a [LiquidityDistribution] {
+    let start = (segment.lower_bin_id - first_bin_id) as usize;
+    let end = (segment.upper_bin_id - first_bin_id) as usize;
+    &distribution[start..=end]
+}
+
+fn share_of(amount: u64, weight: u64) -> Result<u64> {
+    Ok(u64::try_from(
+        u128::from(amount) * u128::from(weight) / u128::from(BASIS_POINT_MAX),
+    )?)
+}
+
+fn normalize_side(weights: &[u64]) -> Result<Vec<u16>> {
+    if weights.iter().all(|weight| *weight == 0) {
+        return Ok(vec![0; weights.len()]);
+    }
+    normalize_weights(weights)
+}
+
+/// Withdraw everything from `positions` and close them
+pub fn get_close_positions_instructions(
+    amm: &SarosDlmm,
+    positions: &[Position],
+    user: Pubkey,
+    user_vault_x: Pubkey,
+    user_vault_y: Pubkey,
+) -> Result<Vec<Instruction>> {
+    let mut instructions = Vec::new();
+
+    for position in positions.iter() {
+        let modifier_position_params = get_modifier_position_params(
+            amm,
+            user,
+            position.position_mint,
+            position.lower_bin_id,
+            user_vault_x,
+            user_vault_y,
+        );
+
+        let shares = position.get_liquidity_shares()?.to_vec();
+
+        if shares.iter().any(|share| *share > 0) {
+            instructions.push(amm.get_decrease_position_instruction(
+                modifier_position_params.clone(),
+                DecreasePositionParams { shares },
+            )?);
+        }
+
+        instructions.push(amm.get_close_position_instruction(modifier_position_params)?);
+    }
+
+    Ok(instructions)
+}
+
+/// Pack `instructions` in order into as few transactions as possible, each one starting with a
+/// compute unit limit instruction
+pub fn build_transaction_batches(
+    instructions: Vec<Instruction>,
+    payer: &Pubkey,
+) -> Result<Vec<TransactionBatch>> {
+    let mut batches = Vec::new();
+    let mut current: Vec<Instruction> = Vec::new();
+
+    for instruction in instructions {
+        current.push(instruction);
+
+        if get_transaction_size(&current, payer) > PACKET_DATA_SIZE {
+            let instruction = current.pop().unwrap();
+            if current.is_empty() {
+                return Err(anyhow!(
+                    "Instruction for program {} does not fit in a transaction",
+                    instruction.program_id
+                ));
+            }
+            batches.push(into_batch(std::mem::take(&mut current), payer));
+            current.push(instruction);
+        }
+    }
+
+    if !current.is_empty() {
+        batches.push(into_batch(current, payer));
+    }
+
+    Ok(batches)
+}
+
+fn with_compute_budget(instructions: &[Instruction]) -> Vec<Instruction> {
+    let mut all = vec![ComputeBudgetInstruction::set_compute_unit_limit(
+        WIDE_POSITION_COMPUTE_UNIT_LIMIT,
+    )];
+    all.extend_from_slice(instructions);
+    all
+}
+
+/// Serialized size of a legacy transaction holding `instructions`
+pub fn get_transaction_size(instructions: &[Instruction], payer: &Pubkey) -> usize {
+    let message = Message::new(&with_compute_budget(instructions), Some(payer));
+    let signatures = usize::from(message.header.num_required_signatures);
+
+    // Signature count is a compact-u16, a single byte below 128 signatures
+    1 + signatures * 64 + message.serialize().len()
+}
+
+fn into_batch(instructions: Vec<Instruction>, payer: &Pubkey) -> TransactionBatch {
+    let mut signers = Vec::new();
+    for meta in instructions.iter().flat_map(|ix| ix.accounts.iter()) {
+        if meta.is_signer && meta.pubkey != *payer && !signers.contains(&meta.pubkey) {
+            signers.push(meta.pubkey);
+        }
+    }
+
+    TransactionBatch {
+        instructions: with_compute_budget(&instructions),
+        signers,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_bin_range() {
+        assert_eq!(split_bin_range(10, 10).unwrap(), vec![(10, 10)]);
+        assert_eq!(split_bin_range(0, 63).unwrap(), vec![(0, 63)]);
+        assert_eq!(
+            split_bin_range(0, 150).unwrap(),
+            vec![(0, 63), (64, 127), (128, 150)]
+        );
+        assert!(split_bin_range(5, 4).is_err());
+    }
+
+    #[test]
+    fn test_split_distribution_keeps_totals() {
+        let active_id = 1_000u32;
+        let wide = WidePosition::new(active_id - 100, active_id + 100).unwrap();
+        let distribution = create_distribution(LiquidityShape::Spot, -100, 100).unwrap();
+
+        let params = split_distribution(
+            &distribution,
+            -100,
+            &wide.segments,
+            1_000_000_007,
+            999_999_999,
+        )
+        .unwrap();
+
+        assert_eq!(params.len(), 4);
+        assert_eq!(
+            params.iter().map(|p| p.amount_x).sum::<u64>(),
+            1_000_000_007
+        );
+        assert_eq!(params.iter().map(|p| p.amount_y).sum::<u64>(), 999_999_999);
+
+        for (segment, param) in wide.segments.iter().zip(params.iter()) {
+            let distribution = &param.liquidity_distribution;
+            assert_eq!(
+                distribution.len() as u32,
+                segment.upper_bin_id - segment.lower_bin_id + 1
+            );
+            assert_eq!(
+                distribution[0].relative_bin_id,
+                segment.lower_bin_id as i32 - active_id as i32
+            );
+
+            let sum_x: u64 = distribution
+                .iter()
+                .map(|d| u64::from(d.distribution_x))
+                .sum();
+            let sum_y: u64 = distribution
+                .iter()
+                .map(|d| u64::from(d.distribution_y))
+                .sum();
+            assert_eq!(
+                sum_x,
+                if param.amount_x > 0 {
+                    BASIS_POINT_MAX
+                } else {
+                    0
+                }
+            );
+            assert_eq!(
+                sum_y,
+                if param.amount_y > 0 {
+                    BASIS_POINT_MAX
+                } else {
+                    0
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_transaction_batches() {
+        let payer = Pubkey::new_unique();
+        let instructions: Vec<Instruction> = (0..40)
+            .map(|_| {
+                let signer = Pubkey::new_unique();
+                Instruction {
+                    program_id: liquidity_book::ID,
+                    accounts: vec![
+                        anchor_lang::prelude::AccountMeta::new(signer, true),
+                        anchor_lang::prelude::AccountMeta::new(payer, true),
+                        anchor_lang::prelude::AccountMeta::new(Pubkey::new_unique(), false),
+                    ],
+                    data: vec![0; 16],
+                }
+            })
+            .collect();
+
+        let batches = build_transaction_batches(instructions.clone(), &payer).unwrap();
+
+        assert!(batches.len() > 1);
+        let mut flattened = Vec::new();
+        for batch in batches.iter() {
+            assert!(get_transaction_size(&batch.instructions[1..], &payer) <= PACKET_DATA_SIZE);
+            assert_eq!(batch.signers.len(), batch.instructions.len() - 1);
+            flattened.extend_from_slice(&batch.instructions[1..]);
+        }
+        assert_eq!(flattened, instructions);
+    }
+}
@@ -0,0 +1,84 @@
+use std::{fmt::Debug, fs, path::Path};
+
+use saros_dlmm_sdk::account_provider::load_fixture_accounts;
+use saros_sdk::state::{
+    bin_array::{BinArray, BIN_ARRAY_DISCRIMINATOR},
+    bin_step_config::{BinStepConfig, BIN_STEP_CONFIG_DISCRIMINATOR},
+    hook_position::{HookPosition, HOOK_POSITION_DISCRIMINATOR},
+    liquidity_book_config::{LiquidityBookConfig, LIQUIDITY_BOOK_CONFIG_DISCRIMINATOR},
+    pair::{Pair, PAIR_DISCRIMINATOR},
+    position::{Position, POSITION_DISCRIMINATOR},
+    quote_asset_badge::{QuoteAssetBadge, QUOTE_ASSET_BADGE_DISCRIMINATOR},
+};
+use solana_sdk::program_pack::Pack;
+
+fn assert_round_trip<T: Pack + PartialEq + Debug>(data: &[u8]) {
+    let unpacked = T::unpack_from_slice(data).unwrap();
+
+    let mut packed = data.to_vec();
+    unpacked.pack_into_slice(&mut packed);
+    assert_eq!(packed, data);
+    assert_eq!(T::unpack_from_slice(&packed).unwrap(), unpacked);
+}
+
+/// Round trip every state account of `directory`, returns how many were checked
+fn assert_directory_round_trip(directory: &Path) -> usize {
+    let accounts = load_fixture_accounts(directory).unwrap();
+    let mut checked = 0;
+
+    for account in accounts.values() {
+        let data = account.data.as_slice();
+        if data.len() < 8 {
+            continue;
+        }
+
+        let discriminator: [u8; 8] = data[..8].try_into().unwrap();
+        match discriminator {
+            PAIR_DISCRIMINATOR if data.len() == Pair::LEN => assert_round_trip::<Pair>(data),
+            BIN_ARRAY_DISCRIMINATOR if data.len() == BinArray::LEN => {
+                assert_round_trip::<BinArray>(data)
+            }
+            POSITION_DISCRIMINATOR if data.len() == Position::LEN => {
+                assert_round_trip::<Position>(data)
+            }
+            HOOK_POSITION_DISCRIMINATOR if data.len() == HookPosition::LEN => {
+                assert_round_trip::<HookPosition>(data)
+            }
+            BIN_STEP_CONFIG_DISCRIMINATOR if data.len() == BinStepConfig::LEN => {
+                assert_round_trip::<BinStepConfig>(data)
+            }
+            LIQUIDITY_BOOK_CONFIG_DISCRIMINATOR if data.len() == LiquidityBookConfig::LEN => {
+                assert_round_trip::<LiquidityBookConfig>(data)
+            }
+            QUOTE_ASSET_BADGE_DISCRIMINATOR if data.len() == QuoteAssetBadge::LEN => {
+                assert_round_trip::<QuoteAssetBadge>(data)
+            }
+            _ => continue,
+        }
+        checked += 1;
+    }
+
+    checked
+}
+
+/// The committed pair, bin array and position pack back to the exact bytes they were read from.
+/// They are built by hand rather than taken from mainnet, with non-zero `_space` in the bin array
+/// and the position and non-zero bytes behind the unset hook of the pair
+#[test]
+fn test_pack_fixture_round_trip() {
+    assert_eq!(
+        assert_directory_round_trip(Path::new("tests/fixtures/pack")),
+        3
+    );
+}
+
+/// Same check over the pool snapshots, which are taken locally and not committed
+#[test]
+#[ignore = "requires pool snapshots in tests/fixtures/accounts"]
+fn test_fixture_accounts_round_trip() {
+    let directories = fs::read_dir(Path::new("tests/fixtures/accounts")).unwrap();
+
+    for directory in directories.flatten() {
+        assert_directory_round_trip(&directory.path());
+    }
+}
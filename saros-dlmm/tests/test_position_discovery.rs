@@ -0,0 +1,134 @@
+use jupiter_amm_interface::AccountMap;
+use saros_dlmm_sdk::position_discovery::find_positions_by_owner;
+use saros_sdk::{state::position::Position, utils::helper::find_position};
+use solana_sdk::{account::Account, program_pack::Pack, pubkey::Pubkey};
+use spl_token_2022::state::{Account as TokenAccount, AccountState};
+
+fn token_account(mint: Pubkey, owner: Pubkey, amount: u64, program_id: Pubkey) -> Account {
+    let mut data = vec![0u8; TokenAccount::LEN];
+    TokenAccount {
+        mint,
+        owner,
+        amount,
+        state: AccountState::Initialized,
+        ..Default::default()
+    }
+    .pack_into_slice(&mut data);
+
+    Account {
+        lamports: 1,
+        data,
+        owner: program_id,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+fn position_account(pair: Pubkey, position_mint: Pubkey, lower_bin_id: u32) -> Account {
+    let mut position = Position::default();
+    position.pair = pair;
+    position.position_mint = position_mint;
+    position.lower_bin_id = lower_bin_id;
+    position.upper_bin_id = lower_bin_id + 9;
+    position.liquidity_shares[0] = 1_000;
+
+    let mut data = vec![0u8; Position::LEN];
+    position.pack_into_slice(&mut data);
+
+    Account {
+        lamports: 1,
+        data,
+        owner: liquidity_book::ID,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// Add a position NFT held by `owner` together with its position account
+fn add_position(
+    accounts: &mut AccountMap,
+    owner: Pubkey,
+    pair: Pubkey,
+    lower_bin_id: u32,
+) -> Pubkey {
+    let position_mint = Pubkey::new_unique();
+    accounts.insert(
+        Pubkey::new_unique(),
+        token_account(position_mint, owner, 1, spl_token_2022::ID),
+    );
+    accounts.insert(
+        find_position(position_mint),
+        position_account(pair, position_mint, lower_bin_id),
+    );
+    position_mint
+}
+
+#[tokio::test]
+async fn test_find_positions_by_owner() {
+    let owner = Pubkey::new_unique();
+    let other_owner = Pubkey::new_unique();
+    let pair_a = Pubkey::new_unique();
+    let pair_b = Pubkey::new_unique();
+
+    let mut accounts = AccountMap::default();
+
+    let mint_a_high = add_position(&mut accounts, owner, pair_a, 8_388_700);
+    let mint_a_low = add_position(&mut accounts, owner, pair_a, 8_388_600);
+    let mint_b = add_position(&mut accounts, owner, pair_b, 8_388_608);
+    add_position(&mut accounts, other_owner, pair_a, 8_388_608);
+
+    // Token-2022 NFT without a position behind it
+    accounts.insert(
+        Pubkey::new_unique(),
+        token_account(Pubkey::new_unique(), owner, 1, spl_token_2022::ID),
+    );
+    // Fungible Token-2022 balance
+    accounts.insert(
+        Pubkey::new_unique(),
+        token_account(Pubkey::new_unique(), owner, 1_000, spl_token_2022::ID),
+    );
+    // Legacy token account for a position mint, positions are only minted with Token-2022
+    let legacy_mint = Pubkey::new_unique();
+    accounts.insert(
+        Pubkey::new_unique(),
+        token_account(legacy_mint, owner, 1, spl_token::ID),
+    );
+    accounts.insert(
+        find_position(legacy_mint),
+        position_account(pair_b, legacy_mint, 8_388_608),
+    );
+    // Position account that does not belong to the program
+    let spoofed_mint = Pubkey::new_unique();
+    accounts.insert(
+        Pubkey::new_unique(),
+        token_account(spoofed_mint, owner, 1, spl_token_2022::ID),
+    );
+    let mut spoofed = position_account(pair_b, spoofed_mint, 8_388_608);
+    spoofed.owner = Pubkey::new_unique();
+    accounts.insert(find_position(spoofed_mint), spoofed);
+    // Program account at the position address holding another state
+    let mislabeled_mint = Pubkey::new_unique();
+    accounts.insert(
+        Pubkey::new_unique(),
+        token_account(mislabeled_mint, owner, 1, spl_token_2022::ID),
+    );
+    let mut mislabeled = position_account(pair_b, mislabeled_mint, 8_388_608);
+    mislabeled.data[..8].copy_from_slice(&[0; 8]);
+    accounts.insert(find_position(mislabeled_mint), mislabeled);
+
+    let positions = find_positions_by_owner(&accounts, &owner).await.unwrap();
+
+    assert_eq!(positions.len(), 2);
+
+    let pair_a_mints: Vec<Pubkey> = positions[&pair_a]
+        .iter()
+        .map(|owned| owned.position.position_mint)
+        .collect();
+    assert_eq!(pair_a_mints, vec![mint_a_low, mint_a_high]);
+
+    let pair_b_positions = &positions[&pair_b];
+    assert_eq!(pair_b_positions.len(), 1);
+    assert_eq!(pair_b_positions[0].position.position_mint, mint_b);
+    assert_eq!(pair_b_positions[0].position_key, find_position(mint_b));
+    assert_eq!(pair_b_positions[0].position.liquidity_shares[0], 1_000);
+}
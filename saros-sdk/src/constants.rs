@@ -1,3 +1,5 @@
+use solana_sdk::{pubkey, pubkey::Pubkey};
+
 pub const MAX_PROTOCOL_SHARE: u16 = 2_500;
 pub const BASIS_POINT_MAX: u64 = 10_000;
 pub const VARIABLE_FEE_PRECISION: u128 = 100_000_000_000;
@@ -7,3 +9,4 @@ pub const MAX_ACTIVE_ID: u32 = 16_777_215; // 2^24 - 1
 pub const MIDDLE_BIN_ID: i32 = 8_388_608; // 2^23
 pub const MAX_BIN_CROSSING: u32 = 30; // Maximum number of bins that can be crossed in a swap
 pub const MAX_BIN_PER_POSITION: u64 = 64;
+pub const MEMO_PROGRAM_ID: Pubkey = pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
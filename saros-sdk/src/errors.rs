@@ -50,6 +50,15 @@ pub enum ErrorCode {
 
     #[error("Swap crosses too many bins – quote aborted")]
     SwapCrossesTooManyBins,
+
+    #[error("Invalid bin range")]
+    InvalidBinRange,
+
+    #[error("Invalid liquidity distribution")]
+    InvalidLiquidityDistribution,
+
+    #[error("Invalid time range")]
+    InvalidTimeRange,
 }
 
 impl From<TryFromIntError> for ErrorCode {
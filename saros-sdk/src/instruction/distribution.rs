@@ -0,0 +1,226 @@
+use anyhow::Result;
+
+use crate::{constants::BASIS_POINT_MAX, errors::ErrorCode};
+
+use super::LiquidityDistribution;
+
+/// Shape of the liquidity placed around the active bin.
+///
+/// Bins left of the active bin only receive token Y, bins right of it only receive token X and the
+/// active bin receives both.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LiquidityShape {
+    /// Same weight on every bin
+    Spot,
+    /// Gaussian weights centred on the active bin, `sigma` is expressed in bins
+    Curve { sigma: f64 },
+    /// Weight grows linearly with the distance to the active bin
+    BidAsk,
+}
+
+// Scale applied to floating point weights before they are normalized
+const CURVE_WEIGHT_SCALE: f64 = 1_000_000_000.0;
+
+impl LiquidityShape {
+    pub fn weight(&self, relative_bin_id: i32) -> u64 {
+        let distance = u64::from(relative_bin_id.unsigned_abs());
+
+        match self {
+            LiquidityShape::Spot => 1,
+            LiquidityShape::Curve { sigma } => {
+                let distance = distance as f64;
+                let weight = (-(distance * distance) / (2.0 * sigma * sigma)).exp();
+                // Keep far away bins funded, a zero weight would leave holes in the range
+                ((weight * CURVE_WEIGHT_SCALE) as u64).max(1)
+            }
+            LiquidityShape::BidAsk => distance + 1,
+        }
+    }
+}
+
+/// Create a liquidity distribution over `[relative_bin_id_left, relative_bin_id_right]` using one of
+/// the predefined shapes
+pub fn create_distribution(
+    shape: LiquidityShape,
+    relative_bin_id_left: i32,
+    relative_bin_id_right: i32,
+) -> Result<Vec<LiquidityDistribution>> {
+    if let LiquidityShape::Curve { sigma } = shape {
+        if !sigma.is_finite() || sigma <= 0.0 {
+            return Err(ErrorCode::InvalidLiquidityDistribution.into());
+        }
+    }
+
+    create_distribution_with_weights(relative_bin_id_left, relative_bin_id_right, |id| {
+        shape.weight(id)
+    })
+}
+
+/// Create a liquidity distribution over `[relative_bin_id_left, relative_bin_id_right]` from an
+/// arbitrary weight function.
+///
+/// Weights are normalized independently for each token, so the X distribution sums to exactly
+/// `BASIS_POINT_MAX` when the range contains the active bin or bins above it, and the Y
+/// distribution sums to exactly `BASIS_POINT_MAX` when it contains the active bin or bins below it.
+pub fn create_distribution_with_weights<F>(
+    relative_bin_id_left: i32,
+    relative_bin_id_right: i32,
+    weight: F,
+) -> Result<Vec<LiquidityDistribution>>
+where
+    F: Fn(i32) -> u64,
+{
+    if relative_bin_id_left > relative_bin_id_right {
+        return Err(ErrorCode::InvalidBinRange.into());
+    }
+
+    let relative_bin_ids: Vec<i32> = (relative_bin_id_left..=relative_bin_id_right).collect();
+    let weights: Vec<u64> = relative_bin_ids.iter().map(|id| weight(*id)).collect();
+
+    let weights_x: Vec<u64> = relative_bin_ids
+        .iter()
+        .zip(&weights)
+        .map(|(id, weight)| if *id >= 0 { *weight } else { 0 })
+        .collect();
+    let weights_y: Vec<u64> = relative_bin_ids
+        .iter()
+        .zip(&weights)
+        .map(|(id, weight)| if *id <= 0 { *weight } else { 0 })
+        .collect();
+
+    let distribution_x = if relative_bin_id_right >= 0 {
+        normalize_weights(&weights_x)?
+    } else {
+        vec![0; relative_bin_ids.len()]
+    };
+    let distribution_y = if relative_bin_id_left <= 0 {
+        normalize_weights(&weights_y)?
+    } else {
+        vec![0; relative_bin_ids.len()]
+    };
+
+    Ok(relative_bin_ids
+        .into_iter()
+        .zip(distribution_x.into_iter().zip(distribution_y))
+        .map(
+            |(relative_bin_id, (distribution_x, distribution_y))| LiquidityDistribution {
+                relative_bin_id,
+                distribution_x,
+                distribution_y,
+            },
+        )
+        .collect())
+}
+
+/// Scale weights so they sum to exactly `BASIS_POINT_MAX`, rounding with the largest remainder method
+pub fn normalize_weights(weights: &[u64]) -> Result<Vec<u16>> {
+    let total_weight: u128 = weights.iter().map(|weight| u128::from(*weight)).sum();
+
+    if total_weight == 0 {
+        return Err(ErrorCode::InvalidLiquidityDistribution.into());
+    }
+
+    let mut distribution = Vec::with_capacity(weights.len());
+    let mut remainders = Vec::with_capacity(weights.len());
+
+    for (i, weight) in weights.iter().enumerate() {
+        let scaled = u128::from(*weight) * u128::from(BASIS_POINT_MAX);
+        distribution.push(u16::try_from(scaled / total_weight)?);
+        remainders.push((scaled % total_weight, i));
+    }
+
+    let allocated: u64 = distribution.iter().map(|value| u64::from(*value)).sum();
+    let missing = usize::try_from(BASIS_POINT_MAX - allocated)?;
+
+    // Hand out the rounding leftovers to the largest remainders, ties go to the lowest index
+    remainders.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    for (_, i) in remainders.into_iter().take(missing) {
+        distribution[i] += 1;
+    }
+
+    Ok(distribution)
+}
+
+#[cfg(test)]
+mod fuzz_tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn sum_x(distribution: &[LiquidityDistribution]) -> u64 {
+        distribution
+            .iter()
+            .map(|d| u64::from(d.distribution_x))
+            .sum()
+    }
+
+    fn sum_y(distribution: &[LiquidityDistribution]) -> u64 {
+        distribution
+            .iter()
+            .map(|d| u64::from(d.distribution_y))
+            .sum()
+    }
+
+    proptest! {
+        #[test]
+        fn test_distribution_sums(
+            left in -64i32..64,
+            width in 0i32..64,
+            shape_index in 0u8..3,
+            sigma in 0.5f64..32.0,
+        ) {
+            let right = left + width;
+            let shape = match shape_index {
+                0 => LiquidityShape::Spot,
+                1 => LiquidityShape::Curve { sigma },
+                _ => LiquidityShape::BidAsk,
+            };
+
+            let distribution = create_distribution(shape, left, right).unwrap();
+
+            assert_eq!(distribution.len(), (width + 1) as usize);
+            assert_eq!(sum_x(&distribution), if right >= 0 { BASIS_POINT_MAX } else { 0 });
+            assert_eq!(sum_y(&distribution), if left <= 0 { BASIS_POINT_MAX } else { 0 });
+
+            for d in distribution.iter() {
+                if d.relative_bin_id < 0 {
+                    assert_eq!(d.distribution_x, 0);
+                }
+                if d.relative_bin_id > 0 {
+                    assert_eq!(d.distribution_y, 0);
+                }
+            }
+        }
+
+        #[test]
+        fn test_normalize_weights(weights in proptest::collection::vec(0u64..u64::MAX, 1..128)) {
+            prop_assume!(weights.iter().any(|w| *w > 0));
+            let distribution = normalize_weights(&weights).unwrap();
+            let total: u64 = distribution.iter().map(|d| u64::from(*d)).sum();
+            assert_eq!(total, BASIS_POINT_MAX);
+        }
+    }
+
+    #[test]
+    fn test_bid_ask_grows_away_from_active_bin() {
+        let distribution = create_distribution(LiquidityShape::BidAsk, -3, 3).unwrap();
+
+        assert!(distribution[0].distribution_y > distribution[2].distribution_y);
+        assert!(distribution[6].distribution_x > distribution[4].distribution_x);
+    }
+
+    #[test]
+    fn test_curve_peaks_at_active_bin() {
+        let distribution =
+            create_distribution(LiquidityShape::Curve { sigma: 2.0 }, -4, 4).unwrap();
+
+        assert!(distribution[4].distribution_y > distribution[0].distribution_y);
+        assert!(distribution[4].distribution_x > distribution[8].distribution_x);
+    }
+
+    #[test]
+    fn test_invalid_range() {
+        assert!(create_distribution(LiquidityShape::Spot, 3, -3).is_err());
+        assert!(create_distribution(LiquidityShape::Curve { sigma: 0.0 }, -3, 3).is_err());
+        assert!(create_distribution_with_weights(-3, 3, |_| 0).is_err());
+    }
+}
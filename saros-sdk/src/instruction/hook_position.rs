@@ -1,8 +1,17 @@
 use anchor_lang::{prelude::AccountMeta, system_program, InstructionData};
-use rewarder_hook::rewarder_hook::client::args::InitializePosition as InitializePositionArgs;
+use rewarder_hook::rewarder_hook::client::args::{
+    Claim as ClaimArgs, InitializePosition as InitializePositionArgs,
+};
 use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
 
-use crate::utils::helper::{find_event_authority, find_hook_position};
+use crate::{
+    constants::MEMO_PROGRAM_ID,
+    state::bin::BIN_ARRAY_SIZE,
+    utils::helper::{
+        find_event_authority, find_hook_bin_array_at_position, find_hook_position, find_position,
+        get_hook_bin_array, get_pair_bin_array,
+    },
+};
 
 pub fn get_initialize_hook_position_instruction(
     hook: Pubkey,
@@ -29,3 +38,147 @@ pub fn get_initialize_hook_position_instruction(
         data: InitializePositionArgs {}.data(),
     }
 }
+
+#[derive(Clone)]
+pub struct ClaimRewardsParams {
+    pub hook: Pubkey,
+    pub pair: Pubkey,
+    pub active_id: u32,
+    pub position_mint: Pubkey,
+    pub position_token_account: Pubkey,
+    pub position_lower_bin_id: u32,
+    pub reward_token_mint: Pubkey,
+    pub reward_token_program: Pubkey,
+    pub hook_reserve: Pubkey,
+    /// Token account of `user` receiving the rewards
+    pub user_reserve: Pubkey,
+    pub user: Pubkey,
+}
+
+/// Claim the rewards accrued by a position from the rewarder hook of its pair
+pub fn get_claim_rewards_instruction(claim_rewards_params: ClaimRewardsParams) -> Instruction {
+    let ClaimRewardsParams {
+        hook,
+        pair,
+        active_id,
+        position_mint,
+        position_token_account,
+        position_lower_bin_id,
+        reward_token_mint,
+        reward_token_program,
+        hook_reserve,
+        user_reserve,
+        user,
+    } = claim_rewards_params;
+
+    let position = find_position(position_mint);
+    let hook_position = find_hook_position(position, hook);
+
+    let active_bin_array_index = active_id / BIN_ARRAY_SIZE;
+    let (active_bin_array_lower, active_bin_array_upper) =
+        get_pair_bin_array(active_bin_array_index, &pair, &liquidity_book::ID);
+    let (active_hook_bin_array_lower, active_hook_bin_array_upper) =
+        get_hook_bin_array(active_bin_array_index, hook);
+    let (_, [position_hook_bin_array_lower, position_hook_bin_array_upper]) =
+        find_hook_bin_array_at_position(position_lower_bin_id / BIN_ARRAY_SIZE, hook);
+
+    let event_authority = find_event_authority(rewarder_hook::ID);
+
+    let accounts = vec![
+        AccountMeta::new(hook, false),
+        AccountMeta::new_readonly(pair, false),
+        AccountMeta::new_readonly(position, false),
+        AccountMeta::new_readonly(position_mint, false),
+        AccountMeta::new_readonly(position_token_account, false),
+        AccountMeta::new(active_bin_array_lower, false),
+        AccountMeta::new(active_bin_array_upper, false),
+        AccountMeta::new(active_hook_bin_array_lower, false),
+        AccountMeta::new(active_hook_bin_array_upper, false),
+        AccountMeta::new(hook_position, false),
+        AccountMeta::new(position_hook_bin_array_lower, false),
+        AccountMeta::new(position_hook_bin_array_upper, false),
+        AccountMeta::new_readonly(reward_token_mint, false),
+        AccountMeta::new(hook_reserve, false),
+        AccountMeta::new(user_reserve, false),
+        AccountMeta::new_readonly(user, true),
+        AccountMeta::new_readonly(reward_token_program, false),
+        AccountMeta::new_readonly(spl_token_2022::ID, false),
+        AccountMeta::new_readonly(MEMO_PROGRAM_ID, false),
+        AccountMeta::new_readonly(event_authority, false),
+        AccountMeta::new_readonly(rewarder_hook::ID, false),
+    ];
+
+    Instruction {
+        program_id: rewarder_hook::ID,
+        accounts,
+        data: ClaimArgs {}.data(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::ToAccountMetas;
+    use rewarder_hook::rewarder_hook::client::accounts::Claim;
+
+    use super::*;
+    use crate::constants::MIDDLE_BIN_ID;
+
+    #[test]
+    fn test_claim_rewards_accounts_follow_idl() {
+        let hook = Pubkey::new_unique();
+        let pair = Pubkey::new_unique();
+        let active_id = MIDDLE_BIN_ID as u32;
+        // The position sits two bin arrays below the active one
+        let position_lower_bin_id = active_id - 2 * BIN_ARRAY_SIZE;
+        let params = ClaimRewardsParams {
+            hook,
+            pair,
+            active_id,
+            position_mint: Pubkey::new_unique(),
+            position_token_account: Pubkey::new_unique(),
+            position_lower_bin_id,
+            reward_token_mint: Pubkey::new_unique(),
+            reward_token_program: spl_token::ID,
+            hook_reserve: Pubkey::new_unique(),
+            user_reserve: Pubkey::new_unique(),
+            user: Pubkey::new_unique(),
+        };
+
+        let position = find_position(params.position_mint);
+        let active_index = active_id / BIN_ARRAY_SIZE;
+        let active_bin_array = get_pair_bin_array(active_index, &pair, &liquidity_book::ID);
+        let active_hook_bin_array = get_hook_bin_array(active_index, hook);
+        let position_index = position_lower_bin_id / BIN_ARRAY_SIZE;
+        let position_hook_bin_array = get_hook_bin_array(position_index, hook);
+
+        let expected = Claim {
+            hook,
+            pair,
+            position,
+            position_mint: params.position_mint,
+            position_token_account: params.position_token_account,
+            active_bin_array_lower: active_bin_array.0,
+            active_bin_array_upper: active_bin_array.1,
+            active_bin_hook_bin_array_lower: active_hook_bin_array.0,
+            active_bin_hook_bin_array_upper: active_hook_bin_array.1,
+            hook_position: find_hook_position(position, hook),
+            position_hook_bin_array_lower: position_hook_bin_array.0,
+            position_hook_bin_array_upper: position_hook_bin_array.1,
+            reward_token_mint: params.reward_token_mint,
+            hook_reserve: params.hook_reserve,
+            user_reserve: params.user_reserve,
+            user: params.user,
+            reward_token_program: params.reward_token_program,
+            position_token_program: spl_token_2022::ID,
+            memo_program: MEMO_PROGRAM_ID,
+            event_authority: find_event_authority(rewarder_hook::ID),
+            program: rewarder_hook::ID,
+        }
+        .to_account_metas(None);
+
+        let instruction = get_claim_rewards_instruction(params);
+        assert_eq!(instruction.program_id, rewarder_hook::ID);
+        assert_eq!(instruction.accounts, expected);
+        assert_eq!(instruction.data, ClaimArgs {}.data());
+    }
+}
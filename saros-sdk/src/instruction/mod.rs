@@ -1,9 +1,11 @@
 mod bin_array;
+mod distribution;
 mod hook_position;
 mod position;
 mod swap;
 
 pub use bin_array::*;
+pub use distribution::*;
 pub use hook_position::*;
 pub use position::*;
 pub use swap::*;
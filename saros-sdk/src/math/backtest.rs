@@ -0,0 +1,566 @@
+use anyhow::{anyhow, Result};
+use liquidity_book::liquidity_book::events::BinSwapEvent;
+use ruint::aliases::U256;
+
+use crate::{
+    errors::ErrorCode,
+    instruction::IncreasePositionParams,
+    math::{
+        bin_math::{get_id_from_price, get_liquidity, get_price_from_id},
+        fee_earnings::{get_share_of, BinFees},
+        liquidity_manager::{plan_deposit, simulate_increase_position, DepositPlanParams},
+        swap_manager::apply_swap_steps,
+        u64x64_math::SCALE_OFFSET,
+        utils::get_protocol_fee,
+    },
+    state::{bin_array::BinArrayPair, pair::Pair, position::Position},
+};
+
+/// An exact in swap to replay
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BacktestSwap {
+    pub timestamp: u64,
+    /// Amount in, fees included
+    pub amount_in: u64,
+    pub swap_for_y: bool,
+}
+
+impl BacktestSwap {
+    /// Swap through a single bin, `BinSwapEvent` does not carry the block time
+    pub fn from_bin_swap_event(event: &BinSwapEvent, timestamp: u64) -> Self {
+        Self {
+            timestamp,
+            amount_in: event.amount_in,
+            swap_for_y: event.swap_for_y,
+        }
+    }
+}
+
+/// One row of a price and volume series
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PriceVolume {
+    pub timestamp: u64,
+    /// Price of one unit of token X in units of token Y, decimals excluded
+    pub price: f64,
+    /// Volume traded in units of token X
+    pub volume: u64,
+}
+
+/// Parse `timestamp,price,volume` rows, a leading header row is skipped
+pub fn parse_price_volume_csv(csv: &str) -> Result<Vec<PriceVolume>> {
+    let mut rows = Vec::new();
+
+    for (line_number, line) in csv.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let columns: Vec<&str> = line.split(',').map(str::trim).collect();
+        if columns.len() != 3 {
+            return Err(anyhow!("Line {}: expected 3 columns", line_number + 1));
+        }
+
+        let row = (
+            columns[0].parse::<u64>(),
+            columns[1].parse::<f64>(),
+            columns[2].parse::<u64>(),
+        );
+        match row {
+            (Ok(timestamp), Ok(price), Ok(volume)) => {
+                if !price.is_finite() || price <= 0f64 {
+                    return Err(anyhow!("Line {}: invalid price", line_number + 1));
+                }
+                rows.push(PriceVolume {
+                    timestamp,
+                    price,
+                    volume,
+                });
+            }
+            _ if rows.is_empty() && line_number == 0 => continue,
+            _ => return Err(anyhow!("Line {}: invalid row", line_number + 1)),
+        }
+    }
+
+    Ok(rows)
+}
+
+/// Bin whose price is the closest to `price`, a price of one unit of token X in units of token Y
+pub fn get_target_id(bin_step: u8, price: f64) -> Result<u32> {
+    let price = price * (1u128 << SCALE_OFFSET) as f64;
+    if !price.is_finite() || price < 1f64 || price >= u128::MAX as f64 {
+        return Err(anyhow!("Invalid price"));
+    }
+    let price = price as u128;
+
+    let below = get_id_from_price(bin_step, price, false);
+    let above = get_id_from_price(bin_step, price, true);
+    match (below, above) {
+        (Some(below), Some(above)) if below != above => {
+            let price_below =
+                get_price_from_id(bin_step, below).ok_or(ErrorCode::AmountOverflow)?;
+            let price_above =
+                get_price_from_id(bin_step, above).ok_or(ErrorCode::AmountOverflow)?;
+
+            // Closest in ratio: price / price_below against price_above / price
+            if U256::from(price) * U256::from(price)
+                < U256::from(price_below) * U256::from(price_above)
+            {
+                Ok(below)
+            } else {
+                Ok(above)
+            }
+        }
+        (Some(id), _) | (None, Some(id)) => Ok(id),
+        (None, None) => Err(ErrorCode::ActiveIdOverflow.into()),
+    }
+}
+
+/// A liquidity position followed through the replay
+#[derive(Clone, Debug)]
+pub struct BacktestPosition {
+    pub position: Position,
+    pub deposited_x: u64,
+    pub deposited_y: u64,
+    /// Swap fees earned by the position, protocol fees excluded
+    pub fees: BinFees,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PositionReport {
+    pub deposited_x: u64,
+    pub deposited_y: u64,
+    /// Amounts withdrawable at the end of the replay, fees included
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub fees: BinFees,
+    /// Value of the deposit had it been held, in token Y at the final active price
+    pub hold_value_y: u64,
+    /// Value of the position without its fees, in token Y at the final active price
+    pub value_y: u64,
+    /// `value_y / hold_value_y - 1`, zero or negative when the price moved
+    pub impermanent_loss: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BacktestReport {
+    pub positions: Vec<PositionReport>,
+    pub swaps_replayed: usize,
+    /// Amounts in the loaded bins could not absorb
+    pub unfilled_x: u64,
+    pub unfilled_y: u64,
+    pub final_active_id: u32,
+}
+
+/// Replays swaps against an in-memory pair and its bins while following liquidity positions.
+/// Swaps walk the bins like `get_swap_result` but keep the reserves they leave behind.
+pub struct Backtester {
+    pub pair: Pair,
+    pub bin_array: BinArrayPair,
+    positions: Vec<BacktestPosition>,
+    swaps_replayed: usize,
+    unfilled_x: u64,
+    unfilled_y: u64,
+}
+
+impl Backtester {
+    pub fn new(pair: Pair, bin_array: BinArrayPair) -> Self {
+        Self {
+            pair,
+            bin_array,
+            positions: Vec::new(),
+            swaps_replayed: 0,
+            unfilled_x: 0,
+            unfilled_y: 0,
+        }
+    }
+
+    pub fn positions(&self) -> &[BacktestPosition] {
+        &self.positions
+    }
+
+    /// Open a position around the active bin with a distribution from `instruction::position`,
+    /// returns its index
+    pub fn add_position(
+        &mut self,
+        increase_position_params: IncreasePositionParams,
+        block_timestamp: u64,
+    ) -> Result<usize> {
+        let relative_bin_ids = increase_position_params
+            .liquidity_distribution
+            .iter()
+            .map(|distribution| distribution.relative_bin_id);
+        let (Some(left), Some(right)) = (relative_bin_ids.clone().min(), relative_bin_ids.max())
+        else {
+            return Err(ErrorCode::InvalidLiquidityDistribution.into());
+        };
+
+        let mut position = Position::default();
+        position.pair = self.bin_array.bin_array_lower.pair;
+        position.lower_bin_id = get_relative_bin_id(self.pair.active_id, left)?;
+        position.upper_bin_id = get_relative_bin_id(self.pair.active_id, right)?;
+
+        let change = simulate_increase_position(
+            &mut self.pair,
+            &mut self.bin_array,
+            &mut position,
+            increase_position_params,
+            block_timestamp,
+        )?;
+
+        self.positions.push(BacktestPosition {
+            position,
+            deposited_x: change.total_amount_x,
+            deposited_y: change.total_amount_y,
+            fees: BinFees::default(),
+        });
+        Ok(self.positions.len() - 1)
+    }
+
+    /// Open a shaped position around the active bin, returns its index
+    pub fn add_shaped_position(
+        &mut self,
+        params: DepositPlanParams,
+        block_timestamp: u64,
+    ) -> Result<usize> {
+        let active_bin = *self.bin_array.get_bin(self.pair.active_id)?;
+        let plan = plan_deposit(&self.pair, &active_bin, params, block_timestamp)?;
+
+        self.add_position(plan.increase_position_params, block_timestamp)
+    }
+
+    /// Replay one exact in swap, crediting the fees of every bin crossed to the positions in it
+    pub fn replay_swap(&mut self, swap: &BacktestSwap) -> Result<()> {
+        self.replay_swap_until(swap, None)
+    }
+
+    /// `replay_swap` stopping in the bin `stop_id`, the amount left is counted as unfilled
+    fn replay_swap_until(&mut self, swap: &BacktestSwap, stop_id: Option<u32>) -> Result<()> {
+        let steps = apply_swap_steps(
+            &mut self.pair,
+            &mut self.bin_array,
+            swap.amount_in,
+            swap.swap_for_y,
+            stop_id,
+            swap.timestamp,
+        )?;
+        let protocol_share = self.pair.get_protocol_share();
+
+        let mut amount_in_left = swap.amount_in;
+        for step in steps {
+            amount_in_left = amount_in_left.saturating_sub(step.amount_in);
+
+            let lp_fee = step
+                .fee_amount
+                .checked_sub(get_protocol_fee(step.fee_amount, protocol_share)?)
+                .ok_or(ErrorCode::AmountUnderflow)?;
+            if lp_fee == 0 {
+                continue;
+            }
+
+            let total_supply = self.bin_array.get_bin(step.bin_id)?.total_supply;
+            for backtest_position in self.positions.iter_mut() {
+                let position = &backtest_position.position;
+                if step.bin_id < position.lower_bin_id || step.bin_id > position.upper_bin_id {
+                    continue;
+                }
+
+                let shares =
+                    position.liquidity_shares[(step.bin_id - position.lower_bin_id) as usize];
+                let fee = get_share_of(lp_fee, shares, total_supply)?;
+                if swap.swap_for_y {
+                    backtest_position.fees.add(fee, 0)?;
+                } else {
+                    backtest_position.fees.add(0, fee)?;
+                }
+            }
+        }
+
+        let unfilled = if swap.swap_for_y {
+            &mut self.unfilled_x
+        } else {
+            &mut self.unfilled_y
+        };
+        *unfilled = unfilled
+            .checked_add(amount_in_left)
+            .ok_or(ErrorCode::AmountOverflow)?;
+
+        self.swaps_replayed += 1;
+        Ok(())
+    }
+
+    pub fn replay_swaps(&mut self, swaps: &[BacktestSwap]) -> Result<()> {
+        for swap in swaps {
+            self.replay_swap(swap)?;
+        }
+        Ok(())
+    }
+
+    /// Replay the volume of a row as a swap towards the bin of its price, stopping there with the
+    /// rest of the volume unfilled. When the price stays in the active bin the volume is split in
+    /// a sell and a buy of half each.
+    pub fn replay_price_volume(&mut self, row: &PriceVolume) -> Result<()> {
+        let target_id = get_target_id(self.pair.bin_step, row.price)?;
+        let amount_y = row.volume as f64 * row.price;
+        if !amount_y.is_finite() || amount_y > u64::MAX as f64 {
+            return Err(ErrorCode::AmountOverflow.into());
+        }
+        let amount_y = amount_y as u64;
+
+        let swaps = match target_id.cmp(&self.pair.active_id) {
+            std::cmp::Ordering::Less => vec![(row.volume, true)],
+            std::cmp::Ordering::Greater => vec![(amount_y, false)],
+            std::cmp::Ordering::Equal => vec![(row.volume / 2, true), (amount_y / 2, false)],
+        };
+
+        for (amount_in, swap_for_y) in swaps {
+            if amount_in == 0 {
+                continue;
+            }
+            self.replay_swap_until(
+                &BacktestSwap {
+                    timestamp: row.timestamp,
+                    amount_in,
+                    swap_for_y,
+                },
+                Some(target_id),
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn replay_price_volumes(&mut self, rows: &[PriceVolume]) -> Result<()> {
+        for row in rows {
+            self.replay_price_volume(row)?;
+        }
+        Ok(())
+    }
+
+    /// Balances, fees and impermanent loss of every position at the current state
+    pub fn report(&self) -> Result<BacktestReport> {
+        let price = get_price_from_id(self.pair.bin_step, self.pair.active_id)
+            .ok_or(ErrorCode::AmountOverflow)?;
+
+        let positions = self
+            .positions
+            .iter()
+            .map(|backtest_position| self.get_position_report(backtest_position, price))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(BacktestReport {
+            positions,
+            swaps_replayed: self.swaps_replayed,
+            unfilled_x: self.unfilled_x,
+            unfilled_y: self.unfilled_y,
+            final_active_id: self.pair.active_id,
+        })
+    }
+
+    fn get_position_report(
+        &self,
+        backtest_position: &BacktestPosition,
+        price: u128,
+    ) -> Result<PositionReport> {
+        let (amount_x, amount_y) = backtest_position.position.get_amounts(&self.bin_array)?;
+
+        let fees = backtest_position.fees;
+        let hold_value_y = get_value_y(
+            backtest_position.deposited_x,
+            backtest_position.deposited_y,
+            price,
+        )?;
+        let value_y = get_value_y(
+            amount_x.saturating_sub(fees.fees_x),
+            amount_y.saturating_sub(fees.fees_y),
+            price,
+        )?;
+        let impermanent_loss = if hold_value_y == 0 {
+            0f64
+        } else {
+            value_y as f64 / hold_value_y as f64 - 1f64
+        };
+
+        Ok(PositionReport {
+            deposited_x: backtest_position.deposited_x,
+            deposited_y: backtest_position.deposited_y,
+            amount_x,
+            amount_y,
+            fees,
+            hold_value_y,
+            value_y,
+            impermanent_loss,
+        })
+    }
+}
+
+fn get_relative_bin_id(active_id: u32, relative_bin_id: i32) -> Result<u32> {
+    Ok(active_id
+        .checked_add_signed(relative_bin_id)
+        .ok_or(ErrorCode::ActiveIdOverflow)?)
+}
+
+fn get_value_y(amount_x: u64, amount_y: u64, price: u128) -> Result<u64> {
+    let liquidity = get_liquidity(amount_x, amount_y, price).ok_or(ErrorCode::AmountOverflow)?;
+
+    Ok(u64::try_from(liquidity >> SCALE_OFFSET).map_err(|_| ErrorCode::U64ConversionOverflow)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        constants::MIDDLE_BIN_ID,
+        instruction::{create_uniform_distribution, LiquidityShape},
+        math::test_pool::build_test_pool,
+    };
+
+    const ACTIVE_ID: u32 = MIDDLE_BIN_ID as u32;
+    const RESERVE: u64 = 1_000_000;
+
+    fn setup() -> Backtester {
+        let (mut pair, bin_array) = build_test_pool(10, 10_000, RESERVE);
+        pair.static_fee_parameters.protocol_share = 2_000;
+        Backtester::new(pair, bin_array)
+    }
+
+    #[test]
+    fn test_round_trip_earns_fees() {
+        let mut backtester = setup();
+        let index = backtester
+            .add_position(
+                IncreasePositionParams {
+                    amount_x: RESERVE * 2,
+                    amount_y: RESERVE * 2,
+                    liquidity_distribution: create_uniform_distribution(1),
+                },
+                0,
+            )
+            .unwrap();
+        assert_eq!(
+            backtester.positions()[index].position.lower_bin_id,
+            ACTIVE_ID - 1
+        );
+
+        backtester
+            .replay_swaps(&[
+                BacktestSwap {
+                    timestamp: 10,
+                    amount_in: 100_000,
+                    swap_for_y: true,
+                },
+                BacktestSwap {
+                    timestamp: 20,
+                    amount_in: 100_000,
+                    swap_for_y: false,
+                },
+            ])
+            .unwrap();
+
+        let report = backtester.report().unwrap();
+        assert_eq!(report.swaps_replayed, 2);
+        assert_eq!((report.unfilled_x, report.unfilled_y), (0, 0));
+        assert_eq!(report.final_active_id, ACTIVE_ID);
+
+        let position = &report.positions[0];
+        assert!(position.fees.fees_x > 0 && position.fees.fees_y > 0);
+        assert!(
+            position.amount_x + position.amount_y > position.deposited_x + position.deposited_y
+        );
+        assert!(position.impermanent_loss.abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_price_move_causes_impermanent_loss() {
+        let mut backtester = setup();
+        backtester
+            .add_shaped_position(
+                DepositPlanParams {
+                    amount_x: RESERVE * 5,
+                    amount_y: RESERVE * 5,
+                    shape: LiquidityShape::Spot,
+                    relative_bin_id_left: -5,
+                    relative_bin_id_right: 5,
+                },
+                0,
+            )
+            .unwrap();
+
+        // Push the price up by about ten bins
+        let price = 1.001f64.powi(10);
+        backtester
+            .replay_price_volumes(&[PriceVolume {
+                timestamp: 10,
+                price,
+                volume: 20_000_000,
+            }])
+            .unwrap();
+
+        let report = backtester.report().unwrap();
+        assert_eq!(report.final_active_id, get_target_id(10, price).unwrap());
+        assert!(report.unfilled_y > 0);
+
+        let position = &report.positions[0];
+        assert!(position.amount_x < position.deposited_x);
+        assert!(position.amount_y > position.deposited_y);
+        assert!(position.fees.fees_y > 0);
+        assert_eq!(position.fees.fees_x, 0);
+        assert!(position.value_y < position.hold_value_y);
+        assert!(position.impermanent_loss < 0f64);
+    }
+
+    #[test]
+    fn test_unfilled_amount() {
+        let mut backtester = setup();
+        backtester
+            .replay_swap(&BacktestSwap {
+                timestamp: 0,
+                amount_in: u64::MAX / 4,
+                swap_for_y: true,
+            })
+            .unwrap();
+
+        let report = backtester.report().unwrap();
+        assert!(report.unfilled_x > 0);
+        assert_eq!(report.unfilled_y, 0);
+    }
+
+    #[test]
+    fn test_parse_price_volume_csv() {
+        let rows =
+            parse_price_volume_csv("timestamp,price,volume\n10, 1.5, 100\n\n20,0.5,200\n").unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                PriceVolume {
+                    timestamp: 10,
+                    price: 1.5,
+                    volume: 100,
+                },
+                PriceVolume {
+                    timestamp: 20,
+                    price: 0.5,
+                    volume: 200,
+                },
+            ]
+        );
+
+        assert!(parse_price_volume_csv("10,1.5,100\nbad,row,here").is_err());
+        assert!(parse_price_volume_csv("10,-1,100").is_err());
+        assert!(parse_price_volume_csv("10,1").is_err());
+    }
+
+    #[test]
+    fn test_get_target_id() {
+        assert_eq!(get_target_id(10, 1f64).unwrap(), ACTIVE_ID);
+        assert_eq!(get_target_id(10, 1.001f64.powi(-3)).unwrap(), ACTIVE_ID - 3);
+        assert_eq!(
+            get_target_id(10, 1.001f64.powf(7.4)).unwrap(),
+            ACTIVE_ID + 7
+        );
+        assert_eq!(
+            get_target_id(10, 1.001f64.powf(7.6)).unwrap(),
+            ACTIVE_ID + 8
+        );
+        assert!(get_target_id(10, 0f64).is_err());
+    }
+}
@@ -1,6 +1,8 @@
+use ruint::aliases::U256;
+
 use crate::constants::MIDDLE_BIN_ID;
 
-use super::u64x64_math::{get_base, pow};
+use super::u64x64_math::{get_base, pow, MAX_EXPONENTIAL, SCALE_OFFSET};
 
 pub fn get_price_from_id(bin_step: u8, id: u32) -> Option<u128> {
     let base = get_base(bin_step)?;
@@ -8,3 +10,71 @@ pub fn get_price_from_id(bin_step: u8, id: u32) -> Option<u128> {
 
     pow(base, exponent)
 }
+
+/// Liquidity of a bin, expressed in token Y scaled by 64 bits: price * x + (y << 64)
+pub fn get_liquidity(amount_x: u64, amount_y: u64, price: u128) -> Option<u128> {
+    let liquidity_x = U256::from(price).checked_mul(U256::from(amount_x))?;
+    let liquidity_y = U256::from(amount_y).checked_shl(SCALE_OFFSET.into())?;
+
+    liquidity_x.checked_add(liquidity_y)?.try_into().ok()
+}
+
+/// Bin of a Q64.64 `price`: the lowest bin priced at or above it when `round_up`, the highest bin
+/// priced at or below it otherwise
+pub fn get_id_from_price(bin_step: u8, price: u128, round_up: bool) -> Option<u32> {
+    let middle_bin_id = MIDDLE_BIN_ID as u32;
+    let max_exponent = MAX_EXPONENTIAL - 1;
+
+    if price == 0 {
+        return None;
+    }
+
+    // Prices that do not fit in Q64.64 are above every price when the exponent is positive
+    let is_at_or_above = |id: u32| match get_price_from_id(bin_step, id) {
+        Some(bin_price) => bin_price >= price,
+        None => id > middle_bin_id,
+    };
+
+    // First bin priced at or above `price`
+    let mut low = middle_bin_id - max_exponent;
+    let mut high = middle_bin_id + max_exponent + 1;
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if is_at_or_above(mid) {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+
+    let id = if round_up || get_price_from_id(bin_step, low) == Some(price) {
+        low
+    } else {
+        low.checked_sub(1)?
+    };
+
+    // Bins past the edges of the Q64.64 range can not be traded
+    get_price_from_id(bin_step, id)
+        .filter(|bin_price| *bin_price > 0)
+        .map(|_| id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_id_from_price() {
+        let middle_bin_id = MIDDLE_BIN_ID as u32;
+
+        for id in [middle_bin_id - 1_000, middle_bin_id, middle_bin_id + 37] {
+            let price = get_price_from_id(10, id).unwrap();
+            assert_eq!(get_id_from_price(10, price, true), Some(id));
+            assert_eq!(get_id_from_price(10, price, false), Some(id));
+            assert_eq!(get_id_from_price(10, price + 1, true), Some(id + 1));
+            assert_eq!(get_id_from_price(10, price + 1, false), Some(id));
+        }
+
+        assert_eq!(get_id_from_price(10, 0, false), None);
+    }
+}
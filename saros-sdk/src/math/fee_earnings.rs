@@ -0,0 +1,373 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use liquidity_book::liquidity_book::events::{BinSwapEvent, CompositionFeesEvent};
+use ruint::aliases::U256;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    errors::ErrorCode,
+    math::{
+        bin_math::{get_liquidity, get_price_from_id},
+        utils::{get_fee_amount, get_protocol_fee},
+    },
+    state::{bin_array::BinArrayPair, pair::Pair, position::Position},
+};
+
+pub const SECONDS_PER_YEAR: u64 = 365 * 24 * 60 * 60;
+
+/// Fees left to liquidity providers, protocol fees excluded
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BinFees {
+    pub fees_x: u64,
+    pub fees_y: u64,
+}
+
+impl BinFees {
+    pub(crate) fn add(&mut self, fees_x: u64, fees_y: u64) -> Result<()> {
+        self.fees_x = self
+            .fees_x
+            .checked_add(fees_x)
+            .ok_or(ErrorCode::AmountOverflow)?;
+        self.fees_y = self
+            .fees_y
+            .checked_add(fees_y)
+            .ok_or(ErrorCode::AmountOverflow)?;
+        Ok(())
+    }
+}
+
+/// Swap volume that went through a single bin
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BinVolume {
+    pub bin_id: u32,
+    /// Amount in, fees included
+    pub amount_in: u64,
+    pub swap_for_y: bool,
+}
+
+/// Fees collected by every bin of a pair over a window
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BinFeeHistory {
+    pub pair: Pubkey,
+    pub bins: BTreeMap<u32, BinFees>,
+}
+
+impl BinFeeHistory {
+    pub fn new(pair: Pubkey) -> Self {
+        Self {
+            pair,
+            bins: BTreeMap::new(),
+        }
+    }
+
+    /// History built from decoded swap and composition fee events, events of other pairs are
+    /// ignored
+    pub fn from_events(
+        pair: Pubkey,
+        swap_events: &[BinSwapEvent],
+        composition_fees_events: &[CompositionFeesEvent],
+    ) -> Result<Self> {
+        let mut history = Self::new(pair);
+        for event in swap_events {
+            history.add_swap_event(event)?;
+        }
+        for event in composition_fees_events {
+            history.add_composition_fees_event(event)?;
+        }
+        Ok(history)
+    }
+
+    /// History built from a volume series, charging the current fee of `pair` on every swap
+    pub fn from_volumes(pair_key: Pubkey, pair: &Pair, volumes: &[BinVolume]) -> Result<Self> {
+        let fee = pair.get_total_fee()?;
+        let protocol_share = pair.get_protocol_share();
+
+        let mut history = Self::new(pair_key);
+        for volume in volumes {
+            history.add_volume(volume, fee, protocol_share)?;
+        }
+        Ok(history)
+    }
+
+    /// Fees of a swap through one bin, paid in the input token
+    pub fn add_swap_event(&mut self, event: &BinSwapEvent) -> Result<()> {
+        if event.pair != self.pair {
+            return Ok(());
+        }
+
+        let lp_fee = event
+            .fee
+            .checked_sub(event.protocol_fee)
+            .ok_or(ErrorCode::AmountUnderflow)?;
+        self.add(event.bin_id, lp_fee, event.swap_for_y)
+    }
+
+    /// Composition fees charged on a deposit into the active bin
+    pub fn add_composition_fees_event(&mut self, event: &CompositionFeesEvent) -> Result<()> {
+        if event.pair != self.pair {
+            return Ok(());
+        }
+
+        let fees_x = event
+            .composition_fees_x
+            .checked_sub(event.protocol_fees_x)
+            .ok_or(ErrorCode::AmountUnderflow)?;
+        let fees_y = event
+            .composition_fees_y
+            .checked_sub(event.protocol_fees_y)
+            .ok_or(ErrorCode::AmountUnderflow)?;
+        self.bins
+            .entry(event.active_id)
+            .or_default()
+            .add(fees_x, fees_y)
+    }
+
+    /// Fees of `volume` charged at the `fee` rate, in `PRECISION` units
+    pub fn add_volume(&mut self, volume: &BinVolume, fee: u64, protocol_share: u64) -> Result<()> {
+        let fee_amount = get_fee_amount(volume.amount_in, fee)?;
+        let lp_fee = fee_amount
+            .checked_sub(get_protocol_fee(fee_amount, protocol_share)?)
+            .ok_or(ErrorCode::AmountUnderflow)?;
+        self.add(volume.bin_id, lp_fee, volume.swap_for_y)
+    }
+
+    fn add(&mut self, bin_id: u32, lp_fee: u64, swap_for_y: bool) -> Result<()> {
+        let bin_fees = self.bins.entry(bin_id).or_default();
+        if swap_for_y {
+            bin_fees.add(lp_fee, 0)
+        } else {
+            bin_fees.add(0, lp_fee)
+        }
+    }
+
+    pub fn get_bin_fees(&self, bin_id: u32) -> BinFees {
+        self.bins.get(&bin_id).copied().unwrap_or_default()
+    }
+}
+
+// share of `amount` owned by `shares` out of `total_supply`, rounded down
+pub(crate) fn get_share_of(amount: u64, shares: u128, total_supply: u128) -> Result<u64> {
+    if total_supply == 0 {
+        return Ok(0);
+    }
+
+    let amount = U256::from(amount) * U256::from(shares) / U256::from(total_supply);
+    Ok(u64::try_from(amount).map_err(|_| ErrorCode::U64ConversionOverflow)?)
+}
+
+/// Fees `position` earned over the history, pro rata to its current share of every bin
+pub fn get_position_fees(
+    position: &Position,
+    bin_array: &BinArrayPair,
+    history: &BinFeeHistory,
+) -> Result<BinFees> {
+    let mut position_fees = BinFees::default();
+
+    for (bin_id, shares) in (position.lower_bin_id..=position.upper_bin_id)
+        .zip(position.liquidity_shares.iter().copied())
+    {
+        if shares == 0 {
+            continue;
+        }
+
+        let bin_fees = history.get_bin_fees(bin_id);
+        if bin_fees == BinFees::default() {
+            continue;
+        }
+
+        let total_supply = bin_array.get_bin(bin_id)?.total_supply;
+        position_fees.add(
+            get_share_of(bin_fees.fees_x, shares, total_supply)?,
+            get_share_of(bin_fees.fees_y, shares, total_supply)?,
+        )?;
+    }
+
+    Ok(position_fees)
+}
+
+/// Annualised fee APR of liquidity provided over `lower_bin_id..=upper_bin_id`, from the fees the
+/// range collected during `window_seconds` and its current reserves, both valued in token Y at
+/// each bin's price. A small deposit spread like the existing liquidity earns the same rate.
+pub fn estimate_range_apr(
+    pair: &Pair,
+    bin_array: &BinArrayPair,
+    history: &BinFeeHistory,
+    lower_bin_id: u32,
+    upper_bin_id: u32,
+    window_seconds: u64,
+) -> Result<f64> {
+    if window_seconds == 0 {
+        return Err(ErrorCode::InvalidTimeRange.into());
+    }
+    if lower_bin_id > upper_bin_id {
+        return Err(ErrorCode::InvalidBinRange.into());
+    }
+
+    let mut liquidity_value = 0f64;
+    let mut fee_value = 0f64;
+
+    for bin_id in lower_bin_id..=upper_bin_id {
+        let bin = bin_array.get_bin(bin_id)?;
+        let bin_fees = history.get_bin_fees(bin_id);
+        let price = get_price_from_id(pair.bin_step, bin_id).ok_or(ErrorCode::AmountOverflow)?;
+
+        liquidity_value += get_liquidity(bin.reserve_x, bin.reserve_y, price)
+            .ok_or(ErrorCode::AmountOverflow)? as f64;
+        fee_value += get_liquidity(bin_fees.fees_x, bin_fees.fees_y, price)
+            .ok_or(ErrorCode::AmountOverflow)? as f64;
+    }
+
+    if liquidity_value == 0f64 {
+        return Ok(0f64);
+    }
+
+    Ok(fee_value / liquidity_value * SECONDS_PER_YEAR as f64 / window_seconds as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        constants::{MIDDLE_BIN_ID, PRECISION},
+        math::test_pool::build_test_pool,
+    };
+
+    const ACTIVE_ID: u32 = MIDDLE_BIN_ID as u32;
+    const RESERVE: u64 = 1_000_000;
+
+    fn setup() -> (Pair, BinArrayPair) {
+        let (mut pair, bin_array) = build_test_pool(10, 10_000, RESERVE);
+        pair.static_fee_parameters.protocol_share = 2_000;
+        (pair, bin_array)
+    }
+
+    fn swap_event(pair: Pubkey, bin_id: u32, swap_for_y: bool, fee: u64) -> BinSwapEvent {
+        BinSwapEvent {
+            pair,
+            swap_for_y,
+            protocol_fee: fee / 5,
+            bin_id,
+            amount_in: fee * 100,
+            amount_out: fee * 100,
+            volatility_accumulator: 0,
+            fee,
+        }
+    }
+
+    #[test]
+    fn test_history_from_events() {
+        let pair = Pubkey::new_unique();
+        let history = BinFeeHistory::from_events(
+            pair,
+            &[
+                swap_event(pair, ACTIVE_ID, true, 1_000),
+                swap_event(pair, ACTIVE_ID, false, 500),
+                swap_event(pair, ACTIVE_ID - 1, true, 100),
+                swap_event(Pubkey::new_unique(), ACTIVE_ID, true, 1_000_000),
+            ],
+            &[CompositionFeesEvent {
+                pair,
+                active_id: ACTIVE_ID,
+                composition_fees_x: 50,
+                composition_fees_y: 10,
+                protocol_fees_x: 10,
+                protocol_fees_y: 2,
+            }],
+        )
+        .unwrap();
+
+        assert_eq!(
+            history.get_bin_fees(ACTIVE_ID),
+            BinFees {
+                fees_x: 800 + 40,
+                fees_y: 400 + 8,
+            }
+        );
+        assert_eq!(
+            history.get_bin_fees(ACTIVE_ID - 1),
+            BinFees {
+                fees_x: 80,
+                fees_y: 0
+            }
+        );
+        assert_eq!(history.get_bin_fees(ACTIVE_ID + 1), BinFees::default());
+    }
+
+    #[test]
+    fn test_history_from_volumes() {
+        let (pair, _) = setup();
+        let fee = pair.get_total_fee().unwrap();
+
+        let history = BinFeeHistory::from_volumes(
+            Pubkey::new_unique(),
+            &pair,
+            &[
+                BinVolume {
+                    bin_id: ACTIVE_ID,
+                    amount_in: 1_000_000,
+                    swap_for_y: true,
+                },
+                BinVolume {
+                    bin_id: ACTIVE_ID,
+                    amount_in: 1_000_000,
+                    swap_for_y: true,
+                },
+            ],
+        )
+        .unwrap();
+
+        let fee_amount = 1_000_000 * fee / PRECISION;
+        let lp_fee = fee_amount - fee_amount / 5;
+        assert_eq!(history.get_bin_fees(ACTIVE_ID).fees_x, 2 * lp_fee);
+        assert_eq!(history.get_bin_fees(ACTIVE_ID).fees_y, 0);
+    }
+
+    #[test]
+    fn test_position_fees_and_apr() {
+        let (pair, bin_array) = setup();
+        let pair_key = Pubkey::new_unique();
+
+        let mut history = BinFeeHistory::new(pair_key);
+        for bin_id in ACTIVE_ID - 2..=ACTIVE_ID + 2 {
+            history
+                .add_swap_event(&swap_event(pair_key, bin_id, bin_id > ACTIVE_ID, 1_250))
+                .unwrap();
+        }
+
+        // Half of the active bin and a quarter of the bin above
+        let mut position = Position::default();
+        position.lower_bin_id = ACTIVE_ID;
+        position.upper_bin_id = ACTIVE_ID + 1;
+        position.liquidity_shares[0] = 500 << 64;
+        position.liquidity_shares[1] = 250 << 64;
+
+        let fees = get_position_fees(&position, &bin_array, &history).unwrap();
+        assert_eq!(
+            fees,
+            BinFees {
+                fees_x: 250,
+                fees_y: 500,
+            }
+        );
+
+        // At the active bin price X and Y are worth the same, the active bin collected 1 of fees
+        // for 2000 of reserves over a day
+        let apr =
+            estimate_range_apr(&pair, &bin_array, &history, ACTIVE_ID, ACTIVE_ID, 86_400).unwrap();
+        assert!((apr - 365.0 / 2_000.0).abs() < 1e-9);
+
+        let wide_apr = estimate_range_apr(
+            &pair,
+            &bin_array,
+            &history,
+            ACTIVE_ID - 10,
+            ACTIVE_ID + 10,
+            86_400,
+        )
+        .unwrap();
+        assert!(wide_apr < apr);
+
+        assert!(estimate_range_apr(&pair, &bin_array, &history, ACTIVE_ID, ACTIVE_ID, 0).is_err());
+    }
+}
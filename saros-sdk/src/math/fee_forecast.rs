@@ -0,0 +1,220 @@
+use anyhow::Result;
+
+use crate::{constants::MAX_ACTIVE_ID, errors::ErrorCode, state::pair::Pair};
+
+/// Fee rate at a point in time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeePoint {
+    pub timestamp: u64,
+    /// Total fee in `PRECISION` units
+    pub fee: u64,
+}
+
+/// Fee rate of every bin a swap landing at `block_timestamp` would cross, from the active bin up to
+/// `bins_moved` bins away in the direction of the swap.
+///
+/// Assumes no other swap happens on the pair before `block_timestamp`, so the volatility decays
+/// from the state stored in `pair` exactly as the program would decay it.
+pub fn get_bin_fees_at(
+    pair: &Pair,
+    block_timestamp: u64,
+    bins_moved: u32,
+    swap_for_y: bool,
+) -> Result<Vec<u64>> {
+    // Bounds the allocation below by the bins the active id can actually reach
+    if swap_for_y && bins_moved > pair.active_id {
+        return Err(ErrorCode::ActiveIdUnderflow.into());
+    }
+    if !swap_for_y && bins_moved > MAX_ACTIVE_ID.saturating_sub(pair.active_id) {
+        return Err(ErrorCode::ActiveIdOverflow.into());
+    }
+
+    let mut pair = pair.clone();
+    pair.update_references(block_timestamp)?;
+
+    let mut fees = Vec::with_capacity(bins_moved as usize + 1);
+    for i in 0..=bins_moved {
+        if i > 0 {
+            pair.move_active_id(swap_for_y)?;
+        }
+        pair.update_volatility_accumulator()?;
+        fees.push(pair.get_total_fee()?);
+    }
+
+    Ok(fees)
+}
+
+/// Fee rate charged in the bin `bins_moved` bins away from the active bin by a swap landing at
+/// `block_timestamp`
+pub fn get_fee_at(
+    pair: &Pair,
+    block_timestamp: u64,
+    bins_moved: u32,
+    swap_for_y: bool,
+) -> Result<u64> {
+    let fees = get_bin_fees_at(pair, block_timestamp, bins_moved, swap_for_y)?;
+    Ok(fees[fees.len() - 1])
+}
+
+/// Fee rate `bins_moved` bins away sampled every `interval` seconds from `start` to `end`, showing
+/// how the variable fee decays while nobody swaps
+pub fn get_fee_curve(
+    pair: &Pair,
+    start: u64,
+    end: u64,
+    interval: u64,
+    bins_moved: u32,
+    swap_for_y: bool,
+) -> Result<Vec<FeePoint>> {
+    if interval == 0 || start > end {
+        return Err(ErrorCode::InvalidTimeRange.into());
+    }
+
+    let mut curve = Vec::new();
+    let mut timestamp = start;
+    loop {
+        curve.push(FeePoint {
+            timestamp,
+            fee: get_fee_at(pair, timestamp, bins_moved, swap_for_y)?,
+        });
+
+        match timestamp.checked_add(interval) {
+            Some(next) if next <= end => timestamp = next,
+            _ => break,
+        }
+    }
+
+    Ok(curve)
+}
+
+/// Time from which the volatility reference is fully reset if nobody swaps, the fee then only
+/// depends on how far the swap moves
+pub fn get_volatility_reset_timestamp(pair: &Pair) -> u64 {
+    pair.dynamic_fee_parameters
+        .time_last_updated
+        .saturating_add(pair.static_fee_parameters.decay_period.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::MIDDLE_BIN_ID;
+    use solana_sdk::program_pack::Pack;
+
+    const LAST_UPDATE: u64 = 1_000;
+
+    fn volatile_pair() -> Pair {
+        let mut pair = Pair::unpack_from_slice(&[0u8; Pair::LEN]).unwrap();
+        pair.bin_step = 10;
+        pair.active_id = MIDDLE_BIN_ID as u32;
+        pair.static_fee_parameters.base_factor = 10_000;
+        pair.static_fee_parameters.filter_period = 30;
+        pair.static_fee_parameters.decay_period = 600;
+        pair.static_fee_parameters.reduction_factor = 5_000;
+        pair.static_fee_parameters.variable_fee_control = 40_000;
+        pair.static_fee_parameters.max_volatility_accumulator = 350_000;
+        pair.dynamic_fee_parameters.time_last_updated = LAST_UPDATE;
+        pair.dynamic_fee_parameters.id_reference = pair.active_id + 5;
+        pair.dynamic_fee_parameters.volatility_accumulator = 100_000;
+        pair.dynamic_fee_parameters.volatility_reference = 40_000;
+        pair
+    }
+
+    #[test]
+    fn test_fee_grows_with_bins_moved() {
+        let pair = volatile_pair();
+        let fees = get_bin_fees_at(&pair, LAST_UPDATE + 10, 10, true).unwrap();
+
+        assert_eq!(fees.len(), 11);
+        assert!(fees.windows(2).all(|window| window[0] <= window[1]));
+        assert_eq!(
+            get_fee_at(&pair, LAST_UPDATE + 10, 10, true).unwrap(),
+            fees[10]
+        );
+
+        // Only a copy of the pair is moved
+        assert_eq!(pair.active_id, MIDDLE_BIN_ID as u32);
+    }
+
+    #[test]
+    fn test_bins_moved_out_of_range() {
+        let pair = volatile_pair();
+        assert!(get_bin_fees_at(&pair, LAST_UPDATE, u32::MAX, true).is_err());
+        assert!(get_bin_fees_at(&pair, LAST_UPDATE, u32::MAX, false).is_err());
+        assert!(get_fee_at(&pair, LAST_UPDATE, MIDDLE_BIN_ID as u32 + 1, true).is_err());
+        assert!(get_fee_at(&pair, LAST_UPDATE, MIDDLE_BIN_ID as u32, false).is_err());
+
+        let fees = get_bin_fees_at(&pair, LAST_UPDATE, MIDDLE_BIN_ID as u32 - 1, false).unwrap();
+        assert_eq!(fees.len(), MIDDLE_BIN_ID as usize);
+    }
+
+    #[test]
+    fn test_fee_curve_decays_to_base_fee() {
+        let pair = volatile_pair();
+        let reset = get_volatility_reset_timestamp(&pair);
+        assert_eq!(reset, LAST_UPDATE + 600);
+
+        let curve = get_fee_curve(&pair, LAST_UPDATE, reset + 60, 15, 0, true).unwrap();
+        assert_eq!(curve.first().unwrap().timestamp, LAST_UPDATE);
+        assert_eq!(curve.last().unwrap().timestamp, reset + 60);
+
+        // Inside the filter period the references are kept, then reduced, then reset
+        let within_filter = curve[0].fee;
+        let after_filter = get_fee_at(&pair, LAST_UPDATE + 30, 0, true).unwrap();
+        let after_decay = get_fee_at(&pair, reset, 0, true).unwrap();
+        assert!(within_filter > after_filter);
+        assert!(after_filter > after_decay);
+
+        let mut base_pair = pair.clone();
+        base_pair.static_fee_parameters.variable_fee_control = 0;
+        assert_eq!(after_decay, base_pair.get_total_fee().unwrap());
+        assert_eq!(curve.last().unwrap().fee, after_decay);
+    }
+
+    #[test]
+    fn test_fee_curve_invalid_range() {
+        let pair = volatile_pair();
+        assert!(get_fee_curve(&pair, 10, 20, 0, 0, true).is_err());
+        assert!(get_fee_curve(&pair, 20, 10, 1, 0, true).is_err());
+        assert_eq!(get_fee_curve(&pair, 10, 10, 1, 0, true).unwrap().len(), 1);
+    }
+}
+
+#[cfg(test)]
+mod fuzz_tests {
+    use super::*;
+    use crate::constants::MIDDLE_BIN_ID;
+    use proptest::prelude::*;
+    use solana_sdk::program_pack::Pack;
+
+    proptest! {
+        #[test]
+        fn test_fee_never_increases_while_idle(
+            filter_period in 0u16..600,
+            decay_extra in 0u16..3_000,
+            reduction_factor in 0u16..=10_000,
+            volatility_accumulator in 0u32..350_000,
+            offset in 0u32..20,
+            bins_moved in 0u32..30,
+            elapsed in 0u64..10_000,
+        ) {
+            let mut pair = Pair::unpack_from_slice(&[0u8; Pair::LEN]).unwrap();
+            pair.bin_step = 20;
+            pair.active_id = MIDDLE_BIN_ID as u32;
+            pair.static_fee_parameters.base_factor = 5_000;
+            pair.static_fee_parameters.filter_period = filter_period;
+            pair.static_fee_parameters.decay_period = filter_period + decay_extra;
+            pair.static_fee_parameters.reduction_factor = reduction_factor;
+            pair.static_fee_parameters.variable_fee_control = 40_000;
+            pair.static_fee_parameters.max_volatility_accumulator = 350_000;
+            pair.dynamic_fee_parameters.time_last_updated = 1_000;
+            pair.dynamic_fee_parameters.id_reference = pair.active_id + offset;
+            pair.dynamic_fee_parameters.volatility_accumulator = volatility_accumulator;
+            pair.dynamic_fee_parameters.volatility_reference = volatility_accumulator;
+
+            let now = get_fee_at(&pair, 1_000, bins_moved, true).unwrap();
+            let later = get_fee_at(&pair, 1_000 + elapsed, bins_moved, true).unwrap();
+            prop_assert!(later <= now);
+        }
+    }
+}
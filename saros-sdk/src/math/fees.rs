@@ -56,6 +56,25 @@ impl TokenTransferFee {
     }
 }
 
+/// Transfer fee of a single mint at `epoch`, `None` for spl-token mints and mints without the
+/// transfer fee extension
+pub fn get_epoch_transfer_fee(
+    mint_data: &[u8],
+    mint_owner: &Pubkey,
+    epoch: u64,
+) -> Result<Option<TransferFee>> {
+    if mint_owner == &spl_token::ID {
+        return Ok(None);
+    }
+
+    let token_mint_unpacked =
+        StateWithExtensions::<spl_token_2022::state::Mint>::unpack(mint_data)?;
+    Ok(token_mint_unpacked
+        .get_extension::<extension::transfer_fee::TransferFeeConfig>()
+        .ok()
+        .map(|transfer_fee_config| *transfer_fee_config.get_epoch_fee(epoch)))
+}
+
 pub fn compute_transfer_fee(
     epoch_transfer_fee_token_mint: Option<TransferFee>,
     amount: u64,
@@ -0,0 +1,492 @@
+use anyhow::Result;
+
+use crate::{
+    constants::BASIS_POINT_MAX,
+    errors::ErrorCode,
+    instruction::{
+        create_distribution, DecreasePositionParams, IncreasePositionParams, LiquidityDistribution,
+        LiquidityShape,
+    },
+    state::{bin::Bin, bin_array::BinArrayPair, pair::Pair, position::Position},
+};
+
+pub struct DepositPlanParams {
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub shape: LiquidityShape,
+    pub relative_bin_id_left: i32,
+    pub relative_bin_id_right: i32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BinDeposit {
+    pub bin_id: u32,
+    pub amount_x: u64,
+    pub amount_y: u64,
+}
+
+/// Expected outcome of an `increase_position` call
+#[derive(Clone, Debug)]
+pub struct DepositPlan {
+    /// Parameters to pass to `build_increase_position_instruction_data`
+    pub increase_position_params: IncreasePositionParams,
+    /// Amounts taken by each bin, composition fees included
+    pub bin_deposits: Vec<BinDeposit>,
+    pub consumed_x: u64,
+    pub consumed_y: u64,
+    pub refunded_x: u64,
+    pub refunded_y: u64,
+    /// Part of the consumed amounts charged as composition fee on the active bin
+    pub composition_fee_x: u64,
+    pub composition_fee_y: u64,
+}
+
+/// Plan a deposit of up to `amount_x` and `amount_y` shaped by `shape` over the requested range
+pub fn plan_deposit(
+    pair: &Pair,
+    active_bin: &Bin,
+    DepositPlanParams {
+        amount_x,
+        amount_y,
+        shape,
+        relative_bin_id_left,
+        relative_bin_id_right,
+    }: DepositPlanParams,
+    block_timestamp: u64,
+) -> Result<DepositPlan> {
+    let liquidity_distribution =
+        create_distribution(shape, relative_bin_id_left, relative_bin_id_right)?;
+
+    plan_deposit_with_distribution(
+        pair,
+        active_bin,
+        amount_x,
+        amount_y,
+        liquidity_distribution,
+        block_timestamp,
+    )
+}
+
+/// Plan a one-sided deposit: token X goes to the `number_of_bins` bins above the active bin and
+/// token Y to the `number_of_bins` bins below it, so no composition fee is ever charged
+pub fn plan_single_sided_deposit(
+    pair: &Pair,
+    amount: u64,
+    deposit_x: bool,
+    shape: LiquidityShape,
+    number_of_bins: u32,
+) -> Result<DepositPlan> {
+    let number_of_bins = i32::try_from(number_of_bins)?;
+
+    if number_of_bins == 0 {
+        return Err(ErrorCode::InvalidBinRange.into());
+    }
+
+    let (amount_x, amount_y, relative_bin_id_left, relative_bin_id_right) = if deposit_x {
+        (amount, 0, 1, number_of_bins)
+    } else {
+        (0, amount, -number_of_bins, -1)
+    };
+
+    let liquidity_distribution =
+        create_distribution(shape, relative_bin_id_left, relative_bin_id_right)?;
+
+    // The active bin is out of range, its content does not matter
+    plan_deposit_with_distribution(
+        pair,
+        &Bin::default(),
+        amount_x,
+        amount_y,
+        liquidity_distribution,
+        pair.dynamic_fee_parameters.time_last_updated,
+    )
+}
+
+/// Compute how much of `amount_x` and `amount_y` a distribution consumes at the current reserves
+/// of the active bin
+pub fn plan_deposit_with_distribution(
+    pair: &Pair,
+    active_bin: &Bin,
+    amount_x: u64,
+    amount_y: u64,
+    liquidity_distribution: Vec<LiquidityDistribution>,
+    block_timestamp: u64,
+) -> Result<DepositPlan> {
+    let bin_amounts =
+        get_distribution_amounts(pair.active_id, amount_x, amount_y, &liquidity_distribution)?;
+
+    let mut bin_deposits = Vec::with_capacity(bin_amounts.len());
+    let mut composition_fee_x = 0u64;
+    let mut composition_fee_y = 0u64;
+
+    for bin_deposit in bin_amounts {
+        if bin_deposit.bin_id != pair.active_id {
+            bin_deposits.push(bin_deposit);
+            continue;
+        }
+
+        // Fees on the active bin depend on the volatility at the time of the deposit
+        let mut pair = pair.clone();
+        pair.update_references(block_timestamp)?;
+        pair.update_volatility_accumulator()?;
+
+        let mut active_bin = *active_bin;
+        let deposit = active_bin.deposit(
+            &pair,
+            bin_deposit.bin_id,
+            bin_deposit.amount_x,
+            bin_deposit.amount_y,
+        )?;
+
+        composition_fee_x = deposit.composition_fee_x;
+        composition_fee_y = deposit.composition_fee_y;
+
+        bin_deposits.push(BinDeposit {
+            bin_id: bin_deposit.bin_id,
+            amount_x: deposit.amount_x,
+            amount_y: deposit.amount_y,
+        });
+    }
+
+    let consumed_x = bin_deposits
+        .iter()
+        .try_fold(0u64, |total, bin| total.checked_add(bin.amount_x))
+        .ok_or(ErrorCode::AmountOverflow)?;
+    let consumed_y = bin_deposits
+        .iter()
+        .try_fold(0u64, |total, bin| total.checked_add(bin.amount_y))
+        .ok_or(ErrorCode::AmountOverflow)?;
+
+    Ok(DepositPlan {
+        increase_position_params: IncreasePositionParams {
+            amount_x,
+            amount_y,
+            liquidity_distribution,
+        },
+        bin_deposits,
+        consumed_x,
+        consumed_y,
+        refunded_x: amount_x
+            .checked_sub(consumed_x)
+            .ok_or(ErrorCode::AmountUnderflow)?,
+        refunded_y: amount_y
+            .checked_sub(consumed_y)
+            .ok_or(ErrorCode::AmountUnderflow)?,
+        composition_fee_x,
+        composition_fee_y,
+    })
+}
+
+/// Split `amount_x` and `amount_y` across bins the way `increase_position` does, rejecting
+/// distributions that put token X below or token Y above the active bin
+pub fn get_distribution_amounts(
+    active_id: u32,
+    amount_x: u64,
+    amount_y: u64,
+    liquidity_distribution: &[LiquidityDistribution],
+) -> Result<Vec<BinDeposit>> {
+    let total_distribution_x: u64 = liquidity_distribution
+        .iter()
+        .map(|d| u64::from(d.distribution_x))
+        .sum();
+    let total_distribution_y: u64 = liquidity_distribution
+        .iter()
+        .map(|d| u64::from(d.distribution_y))
+        .sum();
+
+    if total_distribution_x > BASIS_POINT_MAX || total_distribution_y > BASIS_POINT_MAX {
+        return Err(ErrorCode::InvalidLiquidityDistribution.into());
+    }
+
+    liquidity_distribution
+        .iter()
+        .map(|distribution| {
+            if (distribution.relative_bin_id < 0 && distribution.distribution_x > 0)
+                || (distribution.relative_bin_id > 0 && distribution.distribution_y > 0)
+            {
+                return Err(ErrorCode::InvalidLiquidityDistribution.into());
+            }
+
+            let bin_id =
+                u32::try_from(i64::from(active_id) + i64::from(distribution.relative_bin_id))
+                    .map_err(|_| ErrorCode::InvalidBinRange)?;
+
+            Ok(BinDeposit {
+                bin_id,
+                amount_x: get_bin_amount(amount_x, distribution.distribution_x)?,
+                amount_y: get_bin_amount(amount_y, distribution.distribution_y)?,
+            })
+        })
+        .collect()
+}
+
+/// Liquidity added to or removed from a position, per bin and in total
+#[derive(Clone, Debug, Default)]
+pub struct PositionLiquidityChange {
+    pub bin_ids: Vec<u32>,
+    pub amounts_x: Vec<u64>,
+    pub amounts_y: Vec<u64>,
+    /// Shares minted on increase, burned on decrease
+    pub liquidity_shares: Vec<u128>,
+    pub total_amount_x: u64,
+    pub total_amount_y: u64,
+    pub composition_fee_x: u64,
+    pub composition_fee_y: u64,
+    pub protocol_fee_x: u64,
+    pub protocol_fee_y: u64,
+}
+
+impl PositionLiquidityChange {
+    fn push(&mut self, bin_id: u32, amount_x: u64, amount_y: u64, shares: u128) -> Result<()> {
+        self.bin_ids.push(bin_id);
+        self.amounts_x.push(amount_x);
+        self.amounts_y.push(amount_y);
+        self.liquidity_shares.push(shares);
+
+        self.total_amount_x = self
+            .total_amount_x
+            .checked_add(amount_x)
+            .ok_or(ErrorCode::AmountOverflow)?;
+        self.total_amount_y = self
+            .total_amount_y
+            .checked_add(amount_y)
+            .ok_or(ErrorCode::AmountOverflow)?;
+
+        Ok(())
+    }
+}
+
+/// Simulate `increase_position` against in-memory state: bins receive the deposit, the position is
+/// credited the minted shares and the pair accrues the protocol part of composition fees
+pub fn simulate_increase_position(
+    pair: &mut Pair,
+    bin_array: &mut BinArrayPair,
+    position: &mut Position,
+    IncreasePositionParams {
+        amount_x,
+        amount_y,
+        liquidity_distribution,
+    }: IncreasePositionParams,
+    block_timestamp: u64,
+) -> Result<PositionLiquidityChange> {
+    pair.update_references(block_timestamp)?;
+    pair.update_volatility_accumulator()?;
+
+    let bin_amounts =
+        get_distribution_amounts(pair.active_id, amount_x, amount_y, &liquidity_distribution)?;
+
+    let mut change = PositionLiquidityChange::default();
+
+    for BinDeposit {
+        bin_id,
+        amount_x,
+        amount_y,
+    } in bin_amounts
+    {
+        if amount_x == 0 && amount_y == 0 {
+            continue;
+        }
+
+        let deposit = bin_array
+            .get_bin_mut(bin_id)?
+            .deposit(pair, bin_id, amount_x, amount_y)?;
+
+        let share = position.get_share_mut(bin_id)?;
+        *share = share
+            .checked_add(deposit.shares)
+            .ok_or(ErrorCode::AmountOverflow)?;
+
+        pair.protocol_fees_x = pair
+            .protocol_fees_x
+            .checked_add(deposit.protocol_fee_x)
+            .ok_or(ErrorCode::AmountOverflow)?;
+        pair.protocol_fees_y = pair
+            .protocol_fees_y
+            .checked_add(deposit.protocol_fee_y)
+            .ok_or(ErrorCode::AmountOverflow)?;
+
+        change.composition_fee_x = change
+            .composition_fee_x
+            .checked_add(deposit.composition_fee_x)
+            .ok_or(ErrorCode::AmountOverflow)?;
+        change.composition_fee_y = change
+            .composition_fee_y
+            .checked_add(deposit.composition_fee_y)
+            .ok_or(ErrorCode::AmountOverflow)?;
+        change.protocol_fee_x = change
+            .protocol_fee_x
+            .checked_add(deposit.protocol_fee_x)
+            .ok_or(ErrorCode::AmountOverflow)?;
+        change.protocol_fee_y = change
+            .protocol_fee_y
+            .checked_add(deposit.protocol_fee_y)
+            .ok_or(ErrorCode::AmountOverflow)?;
+        change.push(bin_id, deposit.amount_x, deposit.amount_y, deposit.shares)?;
+    }
+
+    Ok(change)
+}
+
+/// Simulate `decrease_position` against in-memory state. `shares[i]` is burned from the bin
+/// `position.lower_bin_id + i`.
+pub fn simulate_decrease_position(
+    bin_array: &mut BinArrayPair,
+    position: &mut Position,
+    DecreasePositionParams { shares }: DecreasePositionParams,
+) -> Result<PositionLiquidityChange> {
+    if shares.len() > position.get_liquidity_shares()?.len() {
+        return Err(ErrorCode::InvalidBinRange.into());
+    }
+
+    let mut change = PositionLiquidityChange::default();
+
+    for (bin_id, shares) in (position.lower_bin_id..).zip(shares) {
+        if shares == 0 {
+            continue;
+        }
+
+        let share = position.get_share_mut(bin_id)?;
+        *share = share
+            .checked_sub(shares)
+            .ok_or(ErrorCode::AmountUnderflow)?;
+
+        let (amount_x, amount_y) = bin_array.get_bin_mut(bin_id)?.withdraw(shares)?;
+
+        change.push(bin_id, amount_x, amount_y, shares)?;
+    }
+
+    Ok(change)
+}
+
+fn get_bin_amount(amount: u64, distribution: u16) -> Result<u64> {
+    let bin_amount = u128::from(amount) * u128::from(distribution) / u128::from(BASIS_POINT_MAX);
+
+    Ok(u64::try_from(bin_amount).map_err(|_| ErrorCode::U64ConversionOverflow)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::bin_array::BinArray;
+    use solana_sdk::{program_pack::Pack, pubkey::Pubkey};
+
+    const ACTIVE_ID: u32 = 8_388_608;
+
+    fn pair() -> Pair {
+        let mut pair = Pair::unpack_from_slice(&[0u8; Pair::LEN]).unwrap();
+        pair.bin_step = 10;
+        pair.active_id = ACTIVE_ID;
+        pair.static_fee_parameters.base_factor = 10_000;
+        pair.static_fee_parameters.filter_period = 30;
+        pair.static_fee_parameters.decay_period = 600;
+        pair.static_fee_parameters.protocol_share = 2_000;
+        pair
+    }
+
+    #[test]
+    fn test_single_sided_deposit() {
+        let plan =
+            plan_single_sided_deposit(&pair(), 1_000_000, true, LiquidityShape::Spot, 10).unwrap();
+
+        assert_eq!(plan.bin_deposits.len(), 10);
+        assert!(plan.bin_deposits.iter().all(|bin| bin.bin_id > ACTIVE_ID));
+        assert!(plan.bin_deposits.iter().all(|bin| bin.amount_y == 0));
+        assert_eq!(plan.consumed_x + plan.refunded_x, 1_000_000);
+        assert_eq!((plan.consumed_y, plan.refunded_y), (0, 0));
+        assert_eq!((plan.composition_fee_x, plan.composition_fee_y), (0, 0));
+
+        let plan = plan_single_sided_deposit(&pair(), 1_000_000, false, LiquidityShape::BidAsk, 10)
+            .unwrap();
+
+        assert!(plan.bin_deposits.iter().all(|bin| bin.bin_id < ACTIVE_ID));
+        assert_eq!(plan.consumed_y + plan.refunded_y, 1_000_000);
+    }
+
+    #[test]
+    fn test_composition_fee_on_active_bin() {
+        let active_bin = Bin {
+            total_supply: 1_000_000u128 << 64,
+            reserve_x: 1_000_000,
+            reserve_y: 0,
+        };
+
+        let plan = plan_deposit(
+            &pair(),
+            &active_bin,
+            DepositPlanParams {
+                amount_x: 0,
+                amount_y: 1_000_000,
+                shape: LiquidityShape::Spot,
+                relative_bin_id_left: -2,
+                relative_bin_id_right: 0,
+            },
+            0,
+        )
+        .unwrap();
+
+        assert_eq!(plan.composition_fee_x, 0);
+        assert!(plan.composition_fee_y > 0);
+        assert!(plan.composition_fee_y < plan.consumed_y);
+    }
+
+    #[test]
+    fn test_simulate_position_round_trip() {
+        let mut pair = pair();
+        let index = pair.bin_array_index();
+
+        let mut bin_array_lower = BinArray::default();
+        bin_array_lower.initialize(Pubkey::new_unique(), index);
+        let mut bin_array_upper = BinArray::default();
+        bin_array_upper.initialize(bin_array_lower.pair, index + 1);
+        let mut bin_array = BinArrayPair::merge(bin_array_lower, bin_array_upper).unwrap();
+
+        let mut position = Position::default();
+        position.lower_bin_id = ACTIVE_ID - 5;
+        position.upper_bin_id = ACTIVE_ID + 5;
+
+        let increase = simulate_increase_position(
+            &mut pair,
+            &mut bin_array,
+            &mut position,
+            IncreasePositionParams {
+                amount_x: 1_000_000,
+                amount_y: 1_000_000,
+                liquidity_distribution: create_distribution(LiquidityShape::Spot, -5, 5).unwrap(),
+            },
+            0,
+        )
+        .unwrap();
+
+        assert_eq!(increase.bin_ids.len(), 11);
+        assert_eq!(increase.total_amount_x, 1_000_000);
+        assert_eq!(increase.total_amount_y, 1_000_000);
+        assert_eq!(
+            bin_array.get_bin(ACTIVE_ID).unwrap().total_supply,
+            position.liquidity_shares[5]
+        );
+
+        let shares = position.liquidity_shares[..11].to_vec();
+        let decrease = simulate_decrease_position(
+            &mut bin_array,
+            &mut position,
+            DecreasePositionParams { shares },
+        )
+        .unwrap();
+
+        assert_eq!(decrease.total_amount_x, 1_000_000);
+        assert_eq!(decrease.total_amount_y, 1_000_000);
+        assert!(position.liquidity_shares.iter().all(|share| *share == 0));
+        assert_eq!(bin_array.get_bin(ACTIVE_ID).unwrap().total_supply, 0);
+    }
+
+    #[test]
+    fn test_reject_misplaced_distribution() {
+        let distribution = vec![LiquidityDistribution {
+            relative_bin_id: -1,
+            distribution_x: 5_000,
+            distribution_y: 0,
+        }];
+
+        assert!(get_distribution_amounts(ACTIVE_ID, 100, 100, &distribution).is_err());
+    }
+}
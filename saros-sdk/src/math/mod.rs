@@ -1,5 +1,11 @@
+pub mod backtest;
+pub mod fee_earnings;
+pub mod fee_forecast;
 pub mod fees;
+pub mod liquidity_manager;
 pub mod swap_manager;
+#[cfg(test)]
+pub(crate) mod test_pool;
 
 pub mod bin_math;
 pub mod u128x128_math;
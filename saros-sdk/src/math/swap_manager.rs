@@ -10,6 +10,16 @@ pub enum SwapType {
     ExactOut,
 }
 
+/// Liquidity taken from a single bin by an exact in swap
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SwapStep {
+    pub bin_id: u32,
+    /// Amount in consumed by the bin, fees included
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee_amount: u64,
+}
+
 pub fn get_swap_result(
     pair: &mut Pair,
     bin_array: BinArrayPair,
@@ -131,3 +141,178 @@ pub fn get_swap_result(
         }
     }
 }
+
+/// Break an exact in swap of up to `amount` into the bins it crosses, in crossing order.
+///
+/// Unlike `get_swap_result` the walk does not fail when it runs out of bins: it stops after
+/// `MAX_BIN_CROSSING` bins or at the edge of `bin_array`, so the steps also describe how much the
+/// pair can absorb. Each step's `amount_out / amount_in` is the marginal rate of that bin.
+pub fn get_swap_steps(
+    pair: &mut Pair,
+    bin_array: BinArrayPair,
+    amount: u64,
+    swap_for_y: bool,
+    block_timestamp: u64,
+) -> Result<Vec<SwapStep>> {
+    let mut bin_array = bin_array.clone();
+    apply_swap_steps(
+        pair,
+        &mut bin_array,
+        amount,
+        swap_for_y,
+        None,
+        block_timestamp,
+    )
+}
+
+/// Same walk as `get_swap_steps`, applied to the reserves of `bin_array`. With a `stop_id` the
+/// walk also stops once it has swapped in that bin, leaving it active.
+pub fn apply_swap_steps(
+    pair: &mut Pair,
+    bin_array: &mut BinArrayPair,
+    amount: u64,
+    swap_for_y: bool,
+    stop_id: Option<u32>,
+    block_timestamp: u64,
+) -> Result<Vec<SwapStep>> {
+    pair.update_references(block_timestamp)?;
+
+    let mut steps = Vec::new();
+    let mut amount_in_left = amount;
+    let mut total_bin_used: u32 = 0;
+
+    while amount_in_left > 0 && total_bin_used < MAX_BIN_CROSSING {
+        pair.update_volatility_accumulator()?;
+
+        let Ok(bin) = bin_array.get_bin_mut(pair.active_id) else {
+            break;
+        };
+
+        let fee = pair.get_total_fee()?;
+
+        let (amount_in_with_fees, amount_out_of_bin, fee_amount, _) = bin.swap_exact_in(
+            pair.bin_step,
+            pair.active_id,
+            amount_in_left,
+            fee,
+            pair.get_protocol_share(),
+            swap_for_y,
+        )?;
+
+        if amount_in_with_fees > 0 {
+            steps.push(SwapStep {
+                bin_id: pair.active_id,
+                amount_in: amount_in_with_fees,
+                amount_out: amount_out_of_bin,
+                fee_amount,
+            });
+        }
+
+        amount_in_left = amount_in_left
+            .checked_sub(amount_in_with_fees)
+            .ok_or(ErrorCode::AmountUnderflow)?;
+
+        if amount_in_left == 0 || stop_id == Some(pair.active_id) {
+            break;
+        }
+        pair.move_active_id(swap_for_y)?;
+
+        total_bin_used += 1;
+    }
+
+    Ok(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::test_pool::build_test_pool;
+
+    const RESERVE: u64 = 1_000_000;
+
+    fn setup() -> (Pair, BinArrayPair) {
+        let (mut pair, bin_array) = build_test_pool(10, 10_000, RESERVE);
+        pair.static_fee_parameters.variable_fee_control = 100_000;
+        pair.static_fee_parameters.max_volatility_accumulator = 350_000;
+        (pair, bin_array)
+    }
+
+    #[test]
+    fn test_swap_steps_match_swap_result() {
+        for swap_for_y in [true, false] {
+            let amount = 5 * RESERVE + 12_345;
+
+            let (mut pair, bin_array) = setup();
+            let (amount_out, fee_amount) = get_swap_result(
+                &mut pair,
+                bin_array.clone(),
+                amount,
+                swap_for_y,
+                SwapType::ExactIn,
+                0,
+            )
+            .unwrap();
+
+            let (mut pair, bin_array) = setup();
+            let steps = get_swap_steps(&mut pair, bin_array, amount, swap_for_y, 0).unwrap();
+
+            assert!(steps.len() > 1);
+            assert_eq!(steps.iter().map(|s| s.amount_in).sum::<u64>(), amount);
+            assert_eq!(steps.iter().map(|s| s.amount_out).sum::<u64>(), amount_out);
+            assert_eq!(steps.iter().map(|s| s.fee_amount).sum::<u64>(), fee_amount);
+
+            // Marginal rate only gets worse as the swap walks away from the active bin
+            for window in steps.windows(2) {
+                let (a, b) = (window[0], window[1]);
+                assert!(
+                    u128::from(a.amount_out) * u128::from(b.amount_in)
+                        >= u128::from(b.amount_out) * u128::from(a.amount_in)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_swap_steps_stop_at_capacity() {
+        let (mut pair, bin_array) = setup();
+        let steps = get_swap_steps(&mut pair, bin_array, u64::MAX / 2, true, 0).unwrap();
+
+        assert_eq!(steps.len(), MAX_BIN_CROSSING as usize);
+        assert!(steps.iter().all(|s| s.amount_out == RESERVE));
+    }
+
+    #[test]
+    fn test_swap_with_lagging_clock() {
+        let setup_volatile = || {
+            let (mut pair, bin_array) = setup();
+            pair.static_fee_parameters.filter_period = 30;
+            pair.static_fee_parameters.decay_period = 600;
+            pair.static_fee_parameters.reduction_factor = 5_000;
+            pair.dynamic_fee_parameters.time_last_updated = 1_000;
+            pair.dynamic_fee_parameters.volatility_accumulator = 20_000;
+            pair.dynamic_fee_parameters.volatility_reference = 20_000;
+            (pair, bin_array)
+        };
+
+        let swap_at = |block_timestamp| {
+            let (mut pair, bin_array) = setup_volatile();
+            let result = get_swap_result(
+                &mut pair,
+                bin_array,
+                3 * RESERVE,
+                true,
+                SwapType::ExactIn,
+                block_timestamp,
+            )
+            .unwrap();
+            (result, pair.dynamic_fee_parameters.time_last_updated)
+        };
+
+        let (at_last_update, _) = swap_at(1_000);
+        let (lagging, time_last_updated) = swap_at(400);
+
+        assert_eq!(lagging, at_last_update);
+        assert_eq!(time_last_updated, 1_000);
+        assert_ne!(swap_at(2_000).0, at_last_update);
+    }
+}
@@ -0,0 +1,41 @@
+use solana_sdk::{program_pack::Pack, pubkey::Pubkey};
+
+use crate::{
+    constants::MIDDLE_BIN_ID,
+    state::{
+        bin::BIN_ARRAY_SIZE,
+        bin_array::{BinArray, BinArrayPair},
+        pair::Pair,
+    },
+};
+
+/// Pair centred on `MIDDLE_BIN_ID` and the two bin arrays around it, with `reserve` of Y in every
+/// bin up to the active bin, `reserve` of X in every bin from it and the same supply in every bin
+pub fn build_test_pool(bin_step: u8, base_factor: u16, reserve: u64) -> (Pair, BinArrayPair) {
+    let mut pair = Pair::unpack_from_slice(&[0u8; Pair::LEN]).unwrap();
+    pair.bin_step = bin_step;
+    pair.active_id = MIDDLE_BIN_ID as u32;
+    pair.static_fee_parameters.base_factor = base_factor;
+
+    let index = pair.bin_array_index();
+    let pair_key = Pubkey::new_unique();
+    let mut bin_arrays = [BinArray::default(); 2];
+    for (i, bin_array) in bin_arrays.iter_mut().enumerate() {
+        bin_array.initialize(pair_key, index + i as u32);
+        for (j, bin) in bin_array.bins.iter_mut().enumerate() {
+            let bin_id = (index + i as u32) * BIN_ARRAY_SIZE + j as u32;
+            if bin_id <= pair.active_id {
+                bin.reserve_y = reserve;
+            }
+            if bin_id >= pair.active_id {
+                bin.reserve_x = reserve;
+            }
+            bin.total_supply = 1_000 << 64;
+        }
+    }
+
+    (
+        pair,
+        BinArrayPair::merge(bin_arrays[0], bin_arrays[1]).unwrap(),
+    )
+}
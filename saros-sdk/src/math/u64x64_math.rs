@@ -12,7 +12,7 @@ pub const SCALE_OFFSET: u8 = 64;
 // If we convert 443636 to binary form, it will be 1101100010011110100 (19 bits).
 // Which, the 19 bits are the bits the binary exponential will loop through.
 // The 20th bit will be 0x80000,  which the exponential already > the maximum number of bin Q64.64 can support
-const MAX_EXPONENTIAL: u32 = 0x80000; // 1048576
+pub const MAX_EXPONENTIAL: u32 = 0x80000; // 1048576
 
 // 1.0000... representation of 64x64
 pub const ONE: u128 = 1u128 << SCALE_OFFSET;
@@ -9,22 +9,42 @@ use solana_sdk::{
     program_pack::{IsInitialized, Pack, Sealed},
 };
 
-use crate::math::bin_math::get_price_from_id;
+use crate::math::bin_math::{get_liquidity, get_price_from_id};
 use crate::math::u64x64_math::SCALE_OFFSET;
-use crate::math::u128x128_math::{Rounding, mul_shr, shl_div};
+use crate::math::u128x128_math::{Rounding, mul_div, mul_shr, shl_div};
 use crate::math::utils::get_fee_for_amount;
+use crate::state::pair::Pair;
 
 pub const BIN_ARRAY_SIZE: u32 = 256;
 pub const BIN_ARRAY_SIZE_USIZE: usize = 256;
 
-#[derive(Default, Clone, Copy)]
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bin {
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::utils::serde_helper::u128_string")
+    )]
     pub total_supply: u128,
 
     pub reserve_x: u64,
     pub reserve_y: u64,
 }
 
+/// Outcome of a deposit into a single bin
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BinDepositResult {
+    pub shares: u128,
+    /// Amounts taken from the user, composition fees included
+    pub amount_x: u64,
+    pub amount_y: u64,
+    pub composition_fee_x: u64,
+    pub composition_fee_y: u64,
+    /// Part of the composition fees sent to the protocol instead of the bin
+    pub protocol_fee_x: u64,
+    pub protocol_fee_y: u64,
+}
+
 /// IsInitialized is required to use `Pack::pack` and `Pack::unpack`
 impl IsInitialized for Bin {
     fn is_initialized(&self) -> bool {
@@ -261,4 +281,238 @@ impl Bin {
 
         Ok((amount_in, amount_out, fee_amount, protocol_fee_amount))
     }
+
+    /// Shares minted for a deposit of `amount_x` and `amount_y`, along with the amounts actually
+    /// taken by the bin. Liquidity lost to share rounding is given back, token Y first.
+    pub fn get_shares_and_effective_amounts_in(
+        &self,
+        amount_x: u64,
+        amount_y: u64,
+        price: u128,
+    ) -> Result<(u128, u64, u64)> {
+        let user_liquidity =
+            get_liquidity(amount_x, amount_y, price).ok_or(ErrorCode::AmountOverflow)?;
+
+        if user_liquidity == 0 {
+            return Ok((0, amount_x, amount_y));
+        }
+
+        let bin_liquidity = get_liquidity(self.reserve_x, self.reserve_y, price)
+            .ok_or(ErrorCode::AmountOverflow)?;
+
+        if bin_liquidity == 0 || self.total_supply == 0 {
+            return Ok((user_liquidity, amount_x, amount_y));
+        }
+
+        let shares = mul_div(
+            user_liquidity,
+            self.total_supply,
+            bin_liquidity,
+            Rounding::Down,
+        )
+        .ok_or(ErrorCode::AmountOverflow)?;
+
+        let effective_liquidity = mul_div(shares, bin_liquidity, self.total_supply, Rounding::Up)
+            .ok_or(ErrorCode::AmountOverflow)?;
+
+        let mut amount_x = amount_x;
+        let mut amount_y = amount_y;
+
+        if user_liquidity > effective_liquidity {
+            let mut delta_liquidity = user_liquidity - effective_liquidity;
+
+            if delta_liquidity >= 1u128 << SCALE_OFFSET {
+                let delta_y = u64::try_from(delta_liquidity >> SCALE_OFFSET)
+                    .unwrap_or(u64::MAX)
+                    .min(amount_y);
+
+                amount_y -= delta_y;
+                delta_liquidity -= u128::from(delta_y) << SCALE_OFFSET;
+            }
+
+            if delta_liquidity >= price {
+                let delta_x = u64::try_from(delta_liquidity / price)
+                    .unwrap_or(u64::MAX)
+                    .min(amount_x);
+
+                amount_x -= delta_x;
+            }
+        }
+
+        Ok((shares, amount_x, amount_y))
+    }
+
+    /// Amounts of token X and Y owed to `shares` out of `total_supply`
+    pub fn get_amount_out_of_bin(&self, shares: u128, total_supply: u128) -> Result<(u64, u64)> {
+        if total_supply == 0 {
+            return Ok((0, 0));
+        }
+
+        let amount_x = convert_math_result(
+            mul_div(shares, self.reserve_x.into(), total_supply, Rounding::Down),
+            ErrorCode::AmountOverflow,
+        )?;
+        let amount_y = convert_math_result(
+            mul_div(shares, self.reserve_y.into(), total_supply, Rounding::Down),
+            ErrorCode::AmountOverflow,
+        )?;
+
+        Ok((amount_x, amount_y))
+    }
+
+    /// Composition fees charged when depositing into the active bin with a ratio that differs
+    /// from the bin's current composition. The `pair` is expected to have its volatility
+    /// parameters already updated for the current block.
+    pub fn get_composition_fees(
+        &self,
+        pair: &Pair,
+        amount_x: u64,
+        amount_y: u64,
+        shares: u128,
+    ) -> Result<(u64, u64)> {
+        if shares == 0 || self.total_supply == 0 {
+            return Ok((0, 0));
+        }
+
+        let bin_after_deposit = Bin {
+            total_supply: self
+                .total_supply
+                .checked_add(shares)
+                .ok_or(ErrorCode::AmountOverflow)?,
+            reserve_x: self
+                .reserve_x
+                .checked_add(amount_x)
+                .ok_or(ErrorCode::AmountOverflow)?,
+            reserve_y: self
+                .reserve_y
+                .checked_add(amount_y)
+                .ok_or(ErrorCode::AmountOverflow)?,
+        };
+
+        let (received_amount_x, received_amount_y) =
+            bin_after_deposit.get_amount_out_of_bin(shares, bin_after_deposit.total_supply)?;
+
+        if received_amount_x > amount_x {
+            let fee_y = pair.get_composition_fee(amount_y.saturating_sub(received_amount_y))?;
+            Ok((0, fee_y))
+        } else if received_amount_y > amount_y {
+            let fee_x = pair.get_composition_fee(amount_x.saturating_sub(received_amount_x))?;
+            Ok((fee_x, 0))
+        } else {
+            Ok((0, 0))
+        }
+    }
+
+    /// Deposit `amount_x` and `amount_y` into the bin `bin_id` and mint shares for them.
+    ///
+    /// Deposits into the active bin are charged a composition fee, deposits into any other bin
+    /// must only contain token X above the active bin or token Y below it. The `pair` is expected to
+    /// have its volatility parameters already updated for the current block.
+    pub fn deposit(
+        &mut self,
+        pair: &Pair,
+        bin_id: u32,
+        amount_x: u64,
+        amount_y: u64,
+    ) -> Result<BinDepositResult> {
+        let price = get_price_from_id(pair.bin_step, bin_id).ok_or(ErrorCode::ShlDivMathError)?;
+
+        let (mut shares, amount_x, amount_y) =
+            self.get_shares_and_effective_amounts_in(amount_x, amount_y, price)?;
+
+        let mut result = BinDepositResult {
+            amount_x,
+            amount_y,
+            ..Default::default()
+        };
+
+        if bin_id == pair.active_id {
+            let (composition_fee_x, composition_fee_y) =
+                self.get_composition_fees(pair, amount_x, amount_y, shares)?;
+
+            if composition_fee_x > 0 || composition_fee_y > 0 {
+                let user_liquidity = get_liquidity(
+                    amount_x
+                        .checked_sub(composition_fee_x)
+                        .ok_or(ErrorCode::AmountUnderflow)?,
+                    amount_y
+                        .checked_sub(composition_fee_y)
+                        .ok_or(ErrorCode::AmountUnderflow)?,
+                    price,
+                )
+                .ok_or(ErrorCode::AmountOverflow)?;
+
+                let protocol_fee_x =
+                    get_protocol_fee(composition_fee_x, pair.get_protocol_share())?;
+                let protocol_fee_y =
+                    get_protocol_fee(composition_fee_y, pair.get_protocol_share())?;
+
+                let bin_liquidity = get_liquidity(
+                    self.reserve_x
+                        .checked_add(composition_fee_x - protocol_fee_x)
+                        .ok_or(ErrorCode::AmountOverflow)?,
+                    self.reserve_y
+                        .checked_add(composition_fee_y - protocol_fee_y)
+                        .ok_or(ErrorCode::AmountOverflow)?,
+                    price,
+                )
+                .ok_or(ErrorCode::AmountOverflow)?;
+
+                shares = mul_div(
+                    user_liquidity,
+                    self.total_supply,
+                    bin_liquidity,
+                    Rounding::Down,
+                )
+                .ok_or(ErrorCode::AmountOverflow)?;
+
+                result.composition_fee_x = composition_fee_x;
+                result.composition_fee_y = composition_fee_y;
+                result.protocol_fee_x = protocol_fee_x;
+                result.protocol_fee_y = protocol_fee_y;
+            }
+        } else if (bin_id < pair.active_id && amount_x > 0)
+            || (bin_id > pair.active_id && amount_y > 0)
+        {
+            return Err(ErrorCode::InvalidLiquidityDistribution.into());
+        }
+
+        self.reserve_x = self
+            .reserve_x
+            .checked_add(amount_x - result.protocol_fee_x)
+            .ok_or(ErrorCode::AmountOverflow)?;
+        self.reserve_y = self
+            .reserve_y
+            .checked_add(amount_y - result.protocol_fee_y)
+            .ok_or(ErrorCode::AmountOverflow)?;
+        self.total_supply = self
+            .total_supply
+            .checked_add(shares)
+            .ok_or(ErrorCode::AmountOverflow)?;
+
+        result.shares = shares;
+
+        Ok(result)
+    }
+
+    /// Burn `shares` and return the amounts of token X and Y they represent
+    pub fn withdraw(&mut self, shares: u128) -> Result<(u64, u64)> {
+        if shares > self.total_supply {
+            return Err(ErrorCode::AmountUnderflow.into());
+        }
+
+        let (amount_x, amount_y) = self.get_amount_out_of_bin(shares, self.total_supply)?;
+
+        self.reserve_x = self
+            .reserve_x
+            .checked_sub(amount_x)
+            .ok_or(ErrorCode::AmountUnderflow)?;
+        self.reserve_y = self
+            .reserve_y
+            .checked_sub(amount_y)
+            .ok_or(ErrorCode::AmountUnderflow)?;
+        self.total_supply -= shares;
+
+        Ok((amount_x, amount_y))
+    }
 }
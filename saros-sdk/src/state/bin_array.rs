@@ -9,9 +9,12 @@ use solana_sdk::{
 pub const BIN_ARRAY_SIZE: u32 = 256;
 pub const BIN_ARRAY_SIZE_USIZE: usize = 256;
 
-const BIN_ARRAY_DISCRIMINATOR: [u8; 8] = [92, 142, 92, 220, 5, 148, 70, 181];
+pub const BIN_ARRAY_DISCRIMINATOR: [u8; 8] = [92, 142, 92, 220, 5, 148, 70, 181];
 
-use crate::{errors::ErrorCode, state::bin::Bin};
+use crate::{
+    errors::ErrorCode,
+    state::{bin::Bin, StateAccount},
+};
 
 #[derive(Hash, Eq, PartialEq, Clone, Copy, Debug)]
 pub enum BinArrayKind {
@@ -21,19 +24,29 @@ pub enum BinArrayKind {
     HookUpper,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 pub struct BinArrayAccount {
     pub pubkey: Pubkey,
     pub bin_array: Option<BinArray>,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BinArray {
+    #[cfg_attr(feature = "serde", serde(skip, default = "bin_array_discriminator"))]
     _discriminator: [u8; 8],
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::serde_helper::pubkey"))]
     pub pair: Pubkey,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::serde_helper::array"))]
     pub bins: [Bin; BIN_ARRAY_SIZE_USIZE],
     pub index: u32,
-    pub _space: [u8; 12],
+    #[cfg_attr(feature = "serde", serde(skip))]
+    _space: [u8; 12],
+}
+
+#[cfg(feature = "serde")]
+fn bin_array_discriminator() -> [u8; 8] {
+    BIN_ARRAY_DISCRIMINATOR
 }
 
 impl BinArray {
@@ -87,6 +100,10 @@ impl IsInitialized for BinArray {
 
 impl Sealed for BinArray {}
 
+impl StateAccount for BinArray {
+    const DISCRIMINATOR: [u8; 8] = BIN_ARRAY_DISCRIMINATOR;
+}
+
 impl Pack for BinArray {
     const LEN: usize = 8 + 32 + BIN_ARRAY_SIZE_USIZE * 32 + 4 + 12;
 
@@ -101,6 +118,7 @@ impl Pack for BinArray {
             bin.pack_into_slice(&mut bins_dst[i * Bin::LEN..]);
         }
         index_dst.copy_from_slice(&self.index.to_le_bytes());
+        _space_dst.copy_from_slice(&self._space);
     }
 
     fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
@@ -119,11 +137,12 @@ impl Pack for BinArray {
             pair: Pubkey::new_from_array(*pair_src),
             bins,
             index: u32::from_le_bytes(*index_src),
-            _space: [0; 12],
+            _space: *_space_src,
         })
     }
 }
 
+#[derive(Debug)]
 pub struct BinArrayPair {
     pub bin_array_lower: BinArray,
     pub bin_array_upper: BinArray,
@@ -0,0 +1,123 @@
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_sdk::{
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+    pubkey::Pubkey,
+};
+
+use crate::state::{fee::StaticFeeParameters, StateAccount};
+
+pub const BIN_STEP_CONFIG_DISCRIMINATOR: [u8; 8] = [44, 12, 82, 45, 127, 124, 191, 199];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ConfigStatus {
+    Inactive,
+    Active,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ConfigAvailability {
+    Closed,
+    Open,
+}
+
+/// Default fee parameters and status of a bin step, per liquidity book config
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BinStepConfig {
+    #[cfg_attr(
+        feature = "serde",
+        serde(skip, default = "bin_step_config_discriminator")
+    )]
+    _discriminator: [u8; 8],
+    pub bump: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::serde_helper::pubkey"))]
+    pub liquidity_book_config: Pubkey,
+    pub bin_step: u8,
+    pub status: ConfigStatus,
+    pub availability: ConfigAvailability,
+    pub fee_parameters: StaticFeeParameters,
+}
+
+#[cfg(feature = "serde")]
+fn bin_step_config_discriminator() -> [u8; 8] {
+    BIN_STEP_CONFIG_DISCRIMINATOR
+}
+
+impl BinStepConfig {
+    /// Whether new pairs can be created with this bin step
+    pub fn is_available(&self) -> bool {
+        self.status == ConfigStatus::Active && self.availability == ConfigAvailability::Open
+    }
+}
+
+impl IsInitialized for BinStepConfig {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+
+impl Sealed for BinStepConfig {}
+
+impl StateAccount for BinStepConfig {
+    const DISCRIMINATOR: [u8; 8] = BIN_STEP_CONFIG_DISCRIMINATOR;
+}
+
+impl Pack for BinStepConfig {
+    const LEN: usize = 8 + 1 + 32 + 1 + 1 + 1 + StaticFeeParameters::LEN;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, BinStepConfig::LEN];
+        let (
+            discriminator_dst,
+            bump_dst,
+            liquidity_book_config_dst,
+            bin_step_dst,
+            status_dst,
+            availability_dst,
+            fee_parameters_dst,
+        ) = mut_array_refs![output, 8, 1, 32, 1, 1, 1, StaticFeeParameters::LEN];
+
+        discriminator_dst.copy_from_slice(&BIN_STEP_CONFIG_DISCRIMINATOR);
+        bump_dst[0] = self.bump;
+        liquidity_book_config_dst.copy_from_slice(self.liquidity_book_config.as_ref());
+        bin_step_dst[0] = self.bin_step;
+        status_dst[0] = self.status as u8;
+        availability_dst[0] = self.availability as u8;
+        self.fee_parameters.pack_into_slice(fee_parameters_dst);
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, BinStepConfig::LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            discriminator_src,
+            bump_src,
+            liquidity_book_config_src,
+            bin_step_src,
+            status_src,
+            availability_src,
+            fee_parameters_src,
+        ) = array_refs![input, 8, 1, 32, 1, 1, 1, StaticFeeParameters::LEN];
+
+        Ok(Self {
+            _discriminator: *discriminator_src,
+            bump: bump_src[0],
+            liquidity_book_config: Pubkey::new_from_array(*liquidity_book_config_src),
+            bin_step: bin_step_src[0],
+            status: match status_src {
+                [0] => ConfigStatus::Inactive,
+                [1] => ConfigStatus::Active,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            availability: match availability_src {
+                [0] => ConfigAvailability::Closed,
+                [1] => ConfigAvailability::Open,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            fee_parameters: StaticFeeParameters::unpack_from_slice(fee_parameters_src)?,
+        })
+    }
+}
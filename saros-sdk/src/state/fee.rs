@@ -2,7 +2,8 @@ use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 use solana_sdk::program_error::ProgramError;
 use solana_sdk::program_pack::{IsInitialized, Pack, Sealed};
 
-#[derive(PartialEq)]
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StaticFeeParameters {
     pub base_factor: u16,
     pub filter_period: u16,
@@ -11,6 +12,7 @@ pub struct StaticFeeParameters {
     pub variable_fee_control: u32,
     pub max_volatility_accumulator: u32,
     pub protocol_share: u16,
+    #[cfg_attr(feature = "serde", serde(skip))]
     _space: [u8; 2],
 }
 
@@ -84,12 +86,14 @@ impl Clone for StaticFeeParameters {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DynamicFeeParameters {
     pub time_last_updated: u64,
     pub volatility_accumulator: u32,
     pub volatility_reference: u32,
     pub id_reference: u32,
+    #[cfg_attr(feature = "serde", serde(skip))]
     _space: [u8; 4],
 }
 
@@ -6,20 +6,36 @@ use solana_sdk::{
     pubkey::Pubkey,
 };
 
-use crate::constants::MAX_BIN_PER_POSITION;
+use crate::{constants::MAX_BIN_PER_POSITION, state::StateAccount};
 
-const HOOK_POSITION_DISCRIMINATOR: [u8; 8] = [125, 149, 132, 62, 52, 71, 211, 143];
+pub const HOOK_POSITION_DISCRIMINATOR: [u8; 8] = [125, 149, 132, 62, 52, 71, 211, 143];
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HookPosition {
+    #[cfg_attr(
+        feature = "serde",
+        serde(skip, default = "hook_position_discriminator")
+    )]
     _discriminator: [u8; 8],
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::utils::serde_helper::u128_string_array")
+    )]
     pub user_accrued_rewards_per_share: [u128; MAX_BIN_PER_POSITION as usize],
     pub pending_rewards: u64,
     pub bump: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::serde_helper::pubkey"))]
     pub user: Pubkey,
+    #[cfg_attr(feature = "serde", serde(skip))]
     _space: [u8; 7],
 }
 
+#[cfg(feature = "serde")]
+fn hook_position_discriminator() -> [u8; 8] {
+    HOOK_POSITION_DISCRIMINATOR
+}
+
 impl HookPosition {
     pub fn default() -> Self {
         Self {
@@ -41,6 +57,10 @@ impl IsInitialized for HookPosition {
 
 impl Sealed for HookPosition {}
 
+impl StateAccount for HookPosition {
+    const DISCRIMINATOR: [u8; 8] = HOOK_POSITION_DISCRIMINATOR;
+}
+
 impl Pack for HookPosition {
     const LEN: usize = 8 + 16 * MAX_BIN_PER_POSITION as usize + 8 + 1 + 32 + 7;
     fn pack_into_slice(&self, output: &mut [u8]) {
@@ -56,11 +76,11 @@ impl Pack for HookPosition {
         ) = mut_array_refs![output, 8, 16 * MAX_BIN_PER_POSITION as usize, 8, 1, 32, 7];
 
         discriminator_dst.copy_from_slice(&HOOK_POSITION_DISCRIMINATOR);
-        for i in 0..MAX_BIN_PER_POSITION as usize {
-            let start = i * 16;
-            let end = start + 16;
-            user_accrued_rewards_per_share_dst[start..end]
-                .copy_from_slice(&self.user_accrued_rewards_per_share[i].to_le_bytes());
+        for (reward_dst, reward) in user_accrued_rewards_per_share_dst
+            .chunks_exact_mut(16)
+            .zip(self.user_accrued_rewards_per_share.iter())
+        {
+            reward_dst.copy_from_slice(&reward.to_le_bytes());
         }
         pending_rewards_dst.copy_from_slice(&self.pending_rewards.to_le_bytes());
         bump_dst[0] = self.bump;
@@ -81,13 +101,14 @@ impl Pack for HookPosition {
         ) = array_refs![input, 8, 16 * MAX_BIN_PER_POSITION as usize, 8, 1, 32, 7];
 
         let mut user_accrued_rewards_per_share = [0u128; MAX_BIN_PER_POSITION as usize];
-        for i in 0..MAX_BIN_PER_POSITION as usize {
-            let start = i * 16;
-            let end = start + 16;
-            user_accrued_rewards_per_share[i] = u128::from_le_bytes(
-                user_accrued_rewards_per_share_src[start..end]
+        for (reward, reward_src) in user_accrued_rewards_per_share
+            .iter_mut()
+            .zip(user_accrued_rewards_per_share_src.chunks_exact(16))
+        {
+            *reward = u128::from_le_bytes(
+                reward_src
                     .try_into()
-                    .unwrap(),
+                    .map_err(|_| ProgramError::InvalidAccountData)?,
             );
         }
 
@@ -97,7 +118,7 @@ impl Pack for HookPosition {
             pending_rewards: u64::from_le_bytes(*pending_rewards_src),
             bump: bump_src[0],
             user: Pubkey::new_from_array(*user_src),
-            _space: [0; 7],
+            _space: *_space_src,
         })
     }
 }
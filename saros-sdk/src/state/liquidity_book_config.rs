@@ -0,0 +1,90 @@
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_sdk::{
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+    pubkey::Pubkey,
+};
+
+use crate::state::StateAccount;
+
+pub const LIQUIDITY_BOOK_CONFIG_DISCRIMINATOR: [u8; 8] = [173, 36, 130, 129, 45, 178, 44, 86];
+
+/// Root config of the pairs, bin step configs and quote asset badges created under it
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LiquidityBookConfig {
+    #[cfg_attr(
+        feature = "serde",
+        serde(skip, default = "liquidity_book_config_discriminator")
+    )]
+    _discriminator: [u8; 8],
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::serde_helper::pubkey"))]
+    pub preset_authority: Pubkey,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::utils::serde_helper::option_pubkey")
+    )]
+    pub pending_preset_authority: Option<Pubkey>,
+    // Bytes behind an unset pending preset authority, packed back as they were read
+    #[cfg_attr(feature = "serde", serde(skip))]
+    _pending_preset_authority_space: [u8; 32],
+}
+
+#[cfg(feature = "serde")]
+fn liquidity_book_config_discriminator() -> [u8; 8] {
+    LIQUIDITY_BOOK_CONFIG_DISCRIMINATOR
+}
+
+impl IsInitialized for LiquidityBookConfig {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+
+impl Sealed for LiquidityBookConfig {}
+
+impl StateAccount for LiquidityBookConfig {
+    const DISCRIMINATOR: [u8; 8] = LIQUIDITY_BOOK_CONFIG_DISCRIMINATOR;
+}
+
+impl Pack for LiquidityBookConfig {
+    const LEN: usize = 8 + 32 + 1 + 32;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, LiquidityBookConfig::LEN];
+        let (discriminator_dst, preset_authority_dst, pending_flag_dst, pending_pubkey_dst) =
+            mut_array_refs![output, 8, 32, 1, 32];
+
+        discriminator_dst.copy_from_slice(&LIQUIDITY_BOOK_CONFIG_DISCRIMINATOR);
+        preset_authority_dst.copy_from_slice(self.preset_authority.as_ref());
+
+        match &self.pending_preset_authority {
+            Some(pending_preset_authority) => {
+                pending_flag_dst[0] = 1;
+                pending_pubkey_dst.copy_from_slice(pending_preset_authority.as_ref());
+            }
+            None => {
+                pending_flag_dst[0] = 0;
+                pending_pubkey_dst.copy_from_slice(&self._pending_preset_authority_space);
+            }
+        }
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, LiquidityBookConfig::LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (discriminator_src, preset_authority_src, pending_flag_src, pending_pubkey_src) =
+            array_refs![input, 8, 32, 1, 32];
+
+        Ok(Self {
+            _discriminator: *discriminator_src,
+            preset_authority: Pubkey::new_from_array(*preset_authority_src),
+            pending_preset_authority: match pending_flag_src {
+                [0] => None,
+                [1] => Some(Pubkey::new_from_array(*pending_pubkey_src)),
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            _pending_preset_authority_space: *pending_pubkey_src,
+        })
+    }
+}
@@ -1,6 +1,136 @@
 pub mod bin;
 pub mod bin_array;
+pub mod bin_step_config;
 pub mod fee;
 pub mod hook_position;
+pub mod liquidity_book_config;
 pub mod pair;
 pub mod position;
+pub mod quote_asset_badge;
+
+use solana_sdk::program_pack::Pack;
+
+/// Liquidity book account decoded with `Pack`, told apart by the first 8 bytes of its data
+pub trait StateAccount: Pack {
+    const DISCRIMINATOR: [u8; 8];
+}
+
+#[cfg(test)]
+mod fuzz_tests {
+    use super::{
+        bin_array::{BinArray, BIN_ARRAY_DISCRIMINATOR},
+        bin_step_config::{BinStepConfig, BIN_STEP_CONFIG_DISCRIMINATOR},
+        hook_position::{HookPosition, HOOK_POSITION_DISCRIMINATOR},
+        liquidity_book_config::{LiquidityBookConfig, LIQUIDITY_BOOK_CONFIG_DISCRIMINATOR},
+        pair::{Pair, PAIR_DISCRIMINATOR},
+        position::{Position, POSITION_DISCRIMINATOR},
+        quote_asset_badge::{QuoteAssetBadge, QUOTE_ASSET_BADGE_DISCRIMINATOR},
+    };
+    use proptest::prelude::*;
+    use solana_sdk::program_pack::Pack;
+
+    // Account data as the program writes it: any content behind the right discriminator
+    fn account_data(len: usize, discriminator: [u8; 8]) -> impl Strategy<Value = Vec<u8>> {
+        proptest::collection::vec(any::<u8>(), len).prop_map(move |mut data| {
+            data[..8].copy_from_slice(&discriminator);
+            data
+        })
+    }
+
+    fn pair_data() -> impl Strategy<Value = Vec<u8>> {
+        (account_data(Pair::LEN, PAIR_DISCRIMINATOR), any::<bool>()).prop_map(
+            |(mut data, has_hook)| {
+                data[Pair::LEN - 33] = u8::from(has_hook);
+                data
+            },
+        )
+    }
+
+    fn bin_step_config_data() -> impl Strategy<Value = Vec<u8>> {
+        (
+            account_data(BinStepConfig::LEN, BIN_STEP_CONFIG_DISCRIMINATOR),
+            0u8..2,
+            0u8..2,
+        )
+            .prop_map(|(mut data, status, availability)| {
+                data[42] = status;
+                data[43] = availability;
+                data
+            })
+    }
+
+    fn liquidity_book_config_data() -> impl Strategy<Value = Vec<u8>> {
+        (
+            account_data(
+                LiquidityBookConfig::LEN,
+                LIQUIDITY_BOOK_CONFIG_DISCRIMINATOR,
+            ),
+            any::<bool>(),
+        )
+            .prop_map(|(mut data, has_pending)| {
+                data[40] = u8::from(has_pending);
+                data
+            })
+    }
+
+    fn quote_asset_badge_data() -> impl Strategy<Value = Vec<u8>> {
+        (
+            account_data(QuoteAssetBadge::LEN, QUOTE_ASSET_BADGE_DISCRIMINATOR),
+            0u8..2,
+        )
+            .prop_map(|(mut data, status)| {
+                data[9] = status;
+                data
+            })
+    }
+
+    fn assert_round_trip<T: Pack + PartialEq + std::fmt::Debug>(data: &[u8]) {
+        let unpacked = T::unpack_from_slice(data).unwrap();
+
+        let mut packed = vec![0u8; T::LEN];
+        unpacked.pack_into_slice(&mut packed);
+        assert_eq!(packed, data);
+        assert_eq!(T::unpack_from_slice(&packed).unwrap(), unpacked);
+    }
+
+    proptest! {
+        #[test]
+        fn test_pair_round_trip(data in pair_data()) {
+            assert_round_trip::<Pair>(&data);
+        }
+
+        #[test]
+        fn test_bin_array_round_trip(
+            data in account_data(BinArray::LEN, BIN_ARRAY_DISCRIMINATOR)
+        ) {
+            assert_round_trip::<BinArray>(&data);
+        }
+
+        #[test]
+        fn test_position_round_trip(data in account_data(Position::LEN, POSITION_DISCRIMINATOR)) {
+            assert_round_trip::<Position>(&data);
+        }
+
+        #[test]
+        fn test_hook_position_round_trip(
+            data in account_data(HookPosition::LEN, HOOK_POSITION_DISCRIMINATOR)
+        ) {
+            assert_round_trip::<HookPosition>(&data);
+        }
+
+        #[test]
+        fn test_bin_step_config_round_trip(data in bin_step_config_data()) {
+            assert_round_trip::<BinStepConfig>(&data);
+        }
+
+        #[test]
+        fn test_liquidity_book_config_round_trip(data in liquidity_book_config_data()) {
+            assert_round_trip::<LiquidityBookConfig>(&data);
+        }
+
+        #[test]
+        fn test_quote_asset_badge_round_trip(data in quote_asset_badge_data()) {
+            assert_round_trip::<QuoteAssetBadge>(&data);
+        }
+    }
+}
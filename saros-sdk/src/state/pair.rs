@@ -5,6 +5,7 @@ use crate::errors::ErrorCode;
 use crate::math::swap_manager::SwapType;
 use crate::state::bin::BIN_ARRAY_SIZE;
 use crate::state::fee::{DynamicFeeParameters, StaticFeeParameters};
+use crate::state::StateAccount;
 use anyhow::Result;
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 use solana_sdk::program_error::ProgramError;
@@ -13,16 +14,24 @@ use solana_sdk::{
     pubkey::Pubkey,
 };
 
+pub const PAIR_DISCRIMINATOR: [u8; 8] = [85, 72, 49, 176, 182, 228, 141, 82];
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Pair {
+    #[cfg_attr(feature = "serde", serde(skip, default = "pair_discriminator"))]
     _discriminator: [u8; 8],
     pub bump: [u8; 1],
 
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::serde_helper::pubkey"))]
     pub liquidity_book_config: Pubkey,
 
     pub bin_step: u8,
     pub bin_step_seed: [u8; 1],
 
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::serde_helper::pubkey"))]
     pub token_mint_x: Pubkey,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::serde_helper::pubkey"))]
     pub token_mint_y: Pubkey,
 
     pub static_fee_parameters: StaticFeeParameters,
@@ -33,7 +42,19 @@ pub struct Pair {
     pub protocol_fees_x: u64,
     pub protocol_fees_y: u64,
 
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::utils::serde_helper::option_pubkey")
+    )]
     pub hook: Option<Pubkey>,
+    // Bytes behind an unset hook, packed back as they were read
+    #[cfg_attr(feature = "serde", serde(skip))]
+    _hook_space: [u8; 32],
+}
+
+#[cfg(feature = "serde")]
+fn pair_discriminator() -> [u8; 8] {
+    PAIR_DISCRIMINATOR
 }
 
 /// IsInitialized is required to use `Pack::pack` and `Pack::unpack`
@@ -45,6 +66,10 @@ impl IsInitialized for Pair {
 
 impl Sealed for Pair {}
 
+impl StateAccount for Pair {
+    const DISCRIMINATOR: [u8; 8] = PAIR_DISCRIMINATOR;
+}
+
 impl Pack for Pair {
     const LEN: usize = 204;
 
@@ -68,7 +93,7 @@ impl Pack for Pair {
             hook_pubkey_dst,
         ) = mut_array_refs![output, 8, 1, 32, 1, 1, 32, 32, 20, 4, 24, 8, 8, 1, 32];
 
-        discriminator.copy_from_slice(&[85, 72, 49, 176, 182, 228, 141, 82]);
+        discriminator.copy_from_slice(&PAIR_DISCRIMINATOR);
         bump.copy_from_slice(&self.bump);
         liquidity_book_config.copy_from_slice(self.liquidity_book_config.as_ref());
         bin_step.copy_from_slice(&self.bin_step.to_le_bytes());
@@ -90,7 +115,7 @@ impl Pack for Pair {
             }
             None => {
                 hook_flag_dst[0] = 0;
-                hook_pubkey_dst.fill(0);
+                hook_pubkey_dst.copy_from_slice(&self._hook_space);
             }
         }
     }
@@ -135,6 +160,7 @@ impl Pack for Pair {
                 [1] => Some(Pubkey::new_from_array(*hook_pubkey_dst)),
                 _ => return Err(ProgramError::InvalidAccountData),
             },
+            _hook_space: *hook_pubkey_dst,
         })
     }
 }
@@ -155,6 +181,7 @@ impl Clone for Pair {
             protocol_fees_x: self.protocol_fees_x,
             protocol_fees_y: self.protocol_fees_y,
             hook: self.hook,
+            _hook_space: self._hook_space,
         }
     }
 }
@@ -251,6 +278,8 @@ impl Pair {
     }
 
     pub fn update_references(&mut self, block_timestamp: u64) -> Result<()> {
+        // A clock lagging behind the last swap counts as no time elapsed
+        let block_timestamp = block_timestamp.max(self.dynamic_fee_parameters.time_last_updated);
         let time_delta = block_timestamp - self.dynamic_fee_parameters.time_last_updated;
 
         if time_delta >= u64::from(self.static_fee_parameters.filter_period) {
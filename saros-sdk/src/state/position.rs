@@ -6,21 +6,39 @@ use solana_sdk::{
     pubkey::Pubkey,
 };
 
-use crate::{constants::MAX_BIN_PER_POSITION, errors::ErrorCode};
+use crate::{
+    constants::MAX_BIN_PER_POSITION,
+    errors::ErrorCode,
+    state::{bin_array::BinArrayPair, StateAccount},
+};
 
-const POSITION_DISCRIMINATOR: [u8; 8] = [170, 188, 143, 228, 122, 64, 247, 208];
+pub const POSITION_DISCRIMINATOR: [u8; 8] = [170, 188, 143, 228, 122, 64, 247, 208];
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Position {
+    #[cfg_attr(feature = "serde", serde(skip, default = "position_discriminator"))]
     _discriminator: [u8; 8],
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::serde_helper::pubkey"))]
     pub pair: Pubkey,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::serde_helper::pubkey"))]
     pub position_mint: Pubkey,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::utils::serde_helper::u128_string_array")
+    )]
     pub liquidity_shares: [u128; MAX_BIN_PER_POSITION as usize],
     pub lower_bin_id: u32,
     pub upper_bin_id: u32,
+    #[cfg_attr(feature = "serde", serde(skip))]
     _space: [u8; 8],
 }
 
+#[cfg(feature = "serde")]
+fn position_discriminator() -> [u8; 8] {
+    POSITION_DISCRIMINATOR
+}
+
 impl Default for Position {
     fn default() -> Self {
         Self {
@@ -35,6 +53,15 @@ impl Default for Position {
     }
 }
 
+/// Shares a position holds in one bin and the amounts they withdraw
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PositionBinAmounts {
+    pub bin_id: u32,
+    pub liquidity_share: u128,
+    pub amount_x: u64,
+    pub amount_y: u64,
+}
+
 impl IsInitialized for Position {
     fn is_initialized(&self) -> bool {
         true
@@ -43,6 +70,10 @@ impl IsInitialized for Position {
 
 impl Sealed for Position {}
 
+impl StateAccount for Position {
+    const DISCRIMINATOR: [u8; 8] = POSITION_DISCRIMINATOR;
+}
+
 impl Pack for Position {
     const LEN: usize = 8 + 32 + 32 + 16 * MAX_BIN_PER_POSITION as usize + 4 + 4 + 8;
     fn pack_into_slice(&self, output: &mut [u8]) {
@@ -67,19 +98,18 @@ impl Pack for Position {
             8
         ];
 
-        println!("packing discriminator_dst: {:?}", discriminator_dst);
-
         discriminator_dst.copy_from_slice(&POSITION_DISCRIMINATOR);
         pair_dst.copy_from_slice(self.pair.as_ref());
         position_mint_dst.copy_from_slice(self.position_mint.as_ref());
-        for i in 0..MAX_BIN_PER_POSITION as usize {
-            let start = i * 16;
-            let end = start + 16;
-            liquidity_shares_dst[start..end]
-                .copy_from_slice(&self.liquidity_shares[i].to_le_bytes());
+        for (share_dst, share) in liquidity_shares_dst
+            .chunks_exact_mut(16)
+            .zip(self.liquidity_shares.iter())
+        {
+            share_dst.copy_from_slice(&share.to_le_bytes());
         }
         lower_bin_id_dst.copy_from_slice(&self.lower_bin_id.to_le_bytes());
         upper_bin_id_dst.copy_from_slice(&self.upper_bin_id.to_le_bytes());
+        _space_dst.copy_from_slice(&self._space);
     }
 
     fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
@@ -105,11 +135,12 @@ impl Pack for Position {
         ];
 
         let mut liquidity_shares = [0u128; MAX_BIN_PER_POSITION as usize];
-        for i in 0..MAX_BIN_PER_POSITION as usize {
-            let start = i * 16;
-            let end = start + 16;
-            liquidity_shares[i] = u128::from_le_bytes(
-                liquidity_shares_src[start..end]
+        for (share, share_src) in liquidity_shares
+            .iter_mut()
+            .zip(liquidity_shares_src.chunks_exact(16))
+        {
+            *share = u128::from_le_bytes(
+                share_src
                     .try_into()
                     .map_err(|_| ProgramError::InvalidAccountData)?,
             );
@@ -122,12 +153,24 @@ impl Pack for Position {
             liquidity_shares,
             lower_bin_id: u32::from_le_bytes(*lower_bin_id_src),
             upper_bin_id: u32::from_le_bytes(*upper_bin_id_src),
-            _space: [_space_src[0]; 8],
+            _space: *_space_src,
         })
     }
 }
 
 impl Position {
+    /// Liquidity shares of the bins of the position range, errors on a range the program never
+    /// creates
+    pub fn get_liquidity_shares(&self) -> Result<&[u128]> {
+        let width = self
+            .upper_bin_id
+            .checked_sub(self.lower_bin_id)
+            .filter(|width| *width < MAX_BIN_PER_POSITION as u32)
+            .ok_or(ErrorCode::InvalidBinRange)?;
+
+        Ok(&self.liquidity_shares[..=width as usize])
+    }
+
     pub fn get_share_mut(&mut self, bin_id: u32) -> Result<&mut u128> {
         if bin_id < self.lower_bin_id || bin_id > self.upper_bin_id {
             return Err(ErrorCode::BinNotFound)?;
@@ -135,4 +178,48 @@ impl Position {
 
         Ok(&mut self.liquidity_shares[(bin_id - self.lower_bin_id) as usize])
     }
+
+    /// Amounts withdrawable from every bin the position holds shares in, in bin order,
+    /// `bin_array` must hold its bins
+    pub fn get_bin_amounts(&self, bin_array: &BinArrayPair) -> Result<Vec<PositionBinAmounts>> {
+        let mut bin_amounts = Vec::new();
+
+        for (bin_id, liquidity_share) in
+            (self.lower_bin_id..=self.upper_bin_id).zip(self.liquidity_shares.iter().copied())
+        {
+            if liquidity_share == 0 {
+                continue;
+            }
+
+            let bin = bin_array.get_bin(bin_id)?;
+            let (amount_x, amount_y) =
+                bin.get_amount_out_of_bin(liquidity_share, bin.total_supply)?;
+            bin_amounts.push(PositionBinAmounts {
+                bin_id,
+                liquidity_share,
+                amount_x,
+                amount_y,
+            });
+        }
+
+        Ok(bin_amounts)
+    }
+
+    /// Amounts of token X and Y withdrawable from the whole position, `bin_array` must hold its
+    /// bins
+    pub fn get_amounts(&self, bin_array: &BinArrayPair) -> Result<(u64, u64)> {
+        let mut amount_x = 0u64;
+        let mut amount_y = 0u64;
+
+        for bin_amounts in self.get_bin_amounts(bin_array)? {
+            amount_x = amount_x
+                .checked_add(bin_amounts.amount_x)
+                .ok_or(ErrorCode::AmountOverflow)?;
+            amount_y = amount_y
+                .checked_add(bin_amounts.amount_y)
+                .ok_or(ErrorCode::AmountOverflow)?;
+        }
+
+        Ok((amount_x, amount_y))
+    }
 }
@@ -0,0 +1,81 @@
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_sdk::{
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+};
+
+use crate::state::StateAccount;
+
+pub const QUOTE_ASSET_BADGE_DISCRIMINATOR: [u8; 8] = [183, 124, 99, 219, 110, 119, 157, 221];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum QuoteAssetBadgeStatus {
+    Disabled,
+    Enabled,
+}
+
+/// Marks a mint as an accepted token Y for new pairs of a liquidity book config
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QuoteAssetBadge {
+    #[cfg_attr(
+        feature = "serde",
+        serde(skip, default = "quote_asset_badge_discriminator")
+    )]
+    _discriminator: [u8; 8],
+    pub bump: u8,
+    pub status: QuoteAssetBadgeStatus,
+}
+
+#[cfg(feature = "serde")]
+fn quote_asset_badge_discriminator() -> [u8; 8] {
+    QUOTE_ASSET_BADGE_DISCRIMINATOR
+}
+
+impl QuoteAssetBadge {
+    pub fn is_enabled(&self) -> bool {
+        self.status == QuoteAssetBadgeStatus::Enabled
+    }
+}
+
+impl IsInitialized for QuoteAssetBadge {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+
+impl Sealed for QuoteAssetBadge {}
+
+impl StateAccount for QuoteAssetBadge {
+    const DISCRIMINATOR: [u8; 8] = QUOTE_ASSET_BADGE_DISCRIMINATOR;
+}
+
+impl Pack for QuoteAssetBadge {
+    const LEN: usize = 8 + 1 + 1;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, QuoteAssetBadge::LEN];
+        let (discriminator_dst, bump_dst, status_dst) = mut_array_refs![output, 8, 1, 1];
+
+        discriminator_dst.copy_from_slice(&QUOTE_ASSET_BADGE_DISCRIMINATOR);
+        bump_dst[0] = self.bump;
+        status_dst[0] = self.status as u8;
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, QuoteAssetBadge::LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (discriminator_src, bump_src, status_src) = array_refs![input, 8, 1, 1];
+
+        Ok(Self {
+            _discriminator: *discriminator_src,
+            bump: bump_src[0],
+            status: match status_src {
+                [0] => QuoteAssetBadgeStatus::Disabled,
+                [1] => QuoteAssetBadgeStatus::Enabled,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+        })
+    }
+}
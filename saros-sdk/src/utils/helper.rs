@@ -129,6 +129,49 @@ pub fn is_swap_for_y(source_mint: Pubkey, token_x: Pubkey) -> bool {
     source_mint == token_x
 }
 
+pub fn find_pair(
+    liquidity_book_config: Pubkey,
+    token_mint_x: Pubkey,
+    token_mint_y: Pubkey,
+    bin_step: u8,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"pair".as_ref(),
+            liquidity_book_config.as_ref(),
+            token_mint_x.as_ref(),
+            token_mint_y.as_ref(),
+            &[bin_step],
+        ],
+        &liquidity_book::ID,
+    )
+    .0
+}
+
+pub fn find_bin_step_config(liquidity_book_config: Pubkey, bin_step: u8) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"bin_step_config".as_ref(),
+            liquidity_book_config.as_ref(),
+            &[bin_step],
+        ],
+        &liquidity_book::ID,
+    )
+    .0
+}
+
+pub fn find_quote_asset_badge(liquidity_book_config: Pubkey, token_mint: Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"quote_asset_badge".as_ref(),
+            liquidity_book_config.as_ref(),
+            token_mint.as_ref(),
+        ],
+        &liquidity_book::ID,
+    )
+    .0
+}
+
 pub fn find_position(position_mint: Pubkey) -> Pubkey {
     Pubkey::find_program_address(
         &[b"position".as_ref(), position_mint.as_ref()],
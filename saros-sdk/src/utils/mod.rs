@@ -1,2 +1,3 @@
 pub mod helper;
-
+#[cfg(feature = "serde")]
+pub mod serde_helper;
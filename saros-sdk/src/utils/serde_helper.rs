@@ -0,0 +1,195 @@
+//! `#[serde(with = ...)]` adapters giving state accounts a stable JSON form: pubkeys as base58 and
+//! u128 as decimal strings, so values survive JSON parsers limited to 53 bit integers
+
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use solana_sdk::pubkey::Pubkey;
+
+pub mod pubkey {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(pubkey: &Pubkey, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(pubkey)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pubkey, D::Error> {
+        let pubkey = String::deserialize(deserializer)?;
+        Pubkey::from_str(&pubkey).map_err(de::Error::custom)
+    }
+}
+
+pub mod option_pubkey {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        pubkey: &Option<Pubkey>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        pubkey
+            .map(|pubkey| pubkey.to_string())
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Pubkey>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|pubkey| Pubkey::from_str(&pubkey).map_err(de::Error::custom))
+            .transpose()
+    }
+}
+
+pub mod u128_string {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &u128, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(de::Error::custom)
+    }
+}
+
+pub mod u128_string_array {
+    use super::*;
+
+    pub fn serialize<S: Serializer, const N: usize>(
+        values: &[u128; N],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(values.iter().map(|value| value.to_string()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<[u128; N], D::Error> {
+        let values = Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|value| value.parse().map_err(de::Error::custom))
+            .collect::<Result<Vec<u128>, D::Error>>()?;
+        into_array(values)
+    }
+}
+
+/// Fixed size arrays longer than the 32 elements serde supports
+pub mod array {
+    use super::*;
+
+    pub fn serialize<S: Serializer, T: Serialize, const N: usize>(
+        values: &[T; N],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(values)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: Deserialize<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<[T; N], D::Error> {
+        into_array(Vec::<T>::deserialize(deserializer)?)
+    }
+}
+
+fn into_array<E: de::Error, T, const N: usize>(values: Vec<T>) -> Result<[T; N], E> {
+    let len = values.len();
+    values
+        .try_into()
+        .map_err(|_| E::invalid_length(len, &format!("an array of {N} elements").as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::program_pack::Pack;
+
+    use crate::{
+        constants::MIDDLE_BIN_ID,
+        state::{bin_array::BinArray, hook_position::HookPosition, pair::Pair, position::Position},
+    };
+
+    use super::*;
+
+    fn pack<T: Pack>(value: &T) -> Vec<u8> {
+        let mut data = vec![0u8; T::LEN];
+        value.pack_into_slice(&mut data);
+        data
+    }
+
+    fn round_trip<T: Serialize + for<'de> Deserialize<'de>>(value: &T) -> (serde_json::Value, T) {
+        let json = serde_json::to_value(value).unwrap();
+        let value = serde_json::from_value(json.clone()).unwrap();
+        (json, value)
+    }
+
+    #[test]
+    fn test_pair_json() {
+        let mut data = vec![0u8; Pair::LEN];
+        data[..8].copy_from_slice(&crate::state::pair::PAIR_DISCRIMINATOR);
+        let mut pair = Pair::unpack_from_slice(&data).unwrap();
+        pair.token_mint_x = Pubkey::new_unique();
+        pair.bin_step = 20;
+        pair.active_id = MIDDLE_BIN_ID as u32;
+        pair.static_fee_parameters.base_factor = 10_000;
+        pair.dynamic_fee_parameters.time_last_updated = u64::MAX;
+        pair.protocol_fees_x = 42;
+        pair.hook = Some(Pubkey::new_unique());
+
+        let (json, decoded) = round_trip(&pair);
+        assert_eq!(json["token_mint_x"], pair.token_mint_x.to_string());
+        assert_eq!(json["hook"], pair.hook.unwrap().to_string());
+        assert_eq!(json["static_fee_parameters"]["base_factor"], 10_000);
+        assert!(json.get("_discriminator").is_none());
+        assert_eq!(pack(&decoded), pack(&pair));
+
+        pair.hook = None;
+        let (json, decoded) = round_trip(&pair);
+        assert!(json["hook"].is_null());
+        assert_eq!(pack(&decoded), pack(&pair));
+    }
+
+    #[test]
+    fn test_bin_array_json() {
+        let mut bin_array = BinArray::default();
+        bin_array.initialize(Pubkey::new_unique(), 32_767);
+        bin_array.bins[0].total_supply = u128::MAX;
+        bin_array.bins[255].reserve_x = 7;
+
+        let (json, decoded) = round_trip(&bin_array);
+        assert_eq!(json["bins"].as_array().unwrap().len(), 256);
+        assert_eq!(json["bins"][0]["total_supply"], u128::MAX.to_string());
+        assert_eq!(pack(&decoded), pack(&bin_array));
+
+        let mut truncated = json;
+        truncated["bins"].as_array_mut().unwrap().pop();
+        assert!(serde_json::from_value::<BinArray>(truncated).is_err());
+    }
+
+    #[test]
+    fn test_position_json() {
+        let mut position = Position::default();
+        position.pair = Pubkey::new_unique();
+        position.position_mint = Pubkey::new_unique();
+        position.liquidity_shares[3] = u128::MAX - 1;
+        position.lower_bin_id = 100;
+        position.upper_bin_id = 163;
+
+        let (json, decoded) = round_trip(&position);
+        assert_eq!(json["pair"], position.pair.to_string());
+        assert_eq!(json["liquidity_shares"][3], (u128::MAX - 1).to_string());
+        assert_eq!(pack(&decoded), pack(&position));
+
+        let mut hook_position = HookPosition::default();
+        hook_position.user = Pubkey::new_unique();
+        hook_position.user_accrued_rewards_per_share[63] = 1 << 100;
+        hook_position.pending_rewards = 5;
+
+        let (json, decoded) = round_trip(&hook_position);
+        assert_eq!(json["user"], hook_position.user.to_string());
+        assert_eq!(pack(&decoded), pack(&hook_position));
+
+        let mut invalid = json;
+        invalid["user"] = "not a pubkey".into();
+        assert!(serde_json::from_value::<HookPosition>(invalid).is_err());
+    }
+}